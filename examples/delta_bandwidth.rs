@@ -0,0 +1,175 @@
+//! Compare the bandwidth used by protocol-v5 and the experimental delta
+//! protocol for the server-to-client packets of a recorded match.
+//!
+//! ```text
+//! cargo run --release --example delta_bandwidth [replay-file]
+//! ```
+//!
+//! No recorded match is included with the crate. When no replay is given a
+//! synthetic match (planes flying around with random inputs) is simulated and
+//! recorded in memory instead. The numbers printed for it only show that the
+//! protocol works, they say little about the savings in a real match. Record
+//! one with `ReplayWriter` on a server to get meaningful numbers.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::time::Duration;
+
+use airmash_protocol::delta::ProtocolDelta;
+use airmash_protocol::physics::{self, PlaneState};
+use airmash_protocol::replay::{Direction, ReplayHeader, ReplayPacket, ReplayReader, ReplayWriter};
+use airmash_protocol::server::PlayerUpdate;
+use airmash_protocol::{v5, ClientPacket, GameType, PlaneType, Protocol, ServerPacket};
+
+/// The length of a single game tick.
+const TICK: Duration = Duration::from_micros(16_667);
+/// How many ticks it takes for an `Ack` from the client to reach the server.
+const ACK_DELAY: u64 = 6;
+
+const PLAYERS: u16 = 40;
+const SYNTHETIC_TICKS: u32 = 60 * 60 * 5;
+
+fn main() -> Result<(), Box<dyn Error>> {
+  let (source, replay): (String, Box<dyn Read>) = match std::env::args_os().nth(1) {
+    Some(path) => (
+      path.to_string_lossy().into_owned(),
+      Box::new(BufReader::new(File::open(path)?)),
+    ),
+    None => (
+      "synthetic match (pass a replay file for real numbers)".to_owned(),
+      Box::new(Cursor::new(synthetic_match()?)),
+    ),
+  };
+
+  let mut reader = ReplayReader::new(replay)?;
+  let server = ProtocolDelta::new();
+  let client = ProtocolDelta::new();
+
+  // Client `Ack`s along with the tick at which they reach the server.
+  let mut acks = VecDeque::new();
+  let mut ticks = 0;
+  let mut updates = 0;
+  let mut v5_bytes = 0;
+  let mut delta_bytes = 0;
+
+  while let Some((time, direction, packet)) = reader.next_packet()? {
+    let packet = match (direction, packet) {
+      (Direction::ServerToClient, ReplayPacket::Server(packet)) => packet,
+      _ => continue,
+    };
+
+    let tick = (time.as_micros() / TICK.as_micros()) as u64;
+    ticks = ticks.max(tick + 1);
+
+    while matches!(acks.front(), Some(&(arrival, _)) if arrival <= tick) {
+      let (_, ack): (u64, Vec<u8>) = acks.pop_front().unwrap();
+      server.deserialize_client(&ack)?;
+    }
+
+    if let ServerPacket::PlayerUpdate(_) = packet {
+      updates += 1;
+    }
+    v5_bytes += v5::serialize(&packet)?.len();
+
+    for frame in server.serialize_server(&packet)? {
+      delta_bytes += frame.len();
+
+      if let ServerPacket::Ack = client.deserialize_server(&frame)? {
+        for reply in client.serialize_client(&ClientPacket::Ack)? {
+          acks.push_back((tick + ACK_DELAY, reply));
+        }
+      }
+    }
+  }
+
+  let ticks = ticks.max(1) as f64;
+  println!("source:           {}", source);
+  println!("ticks:            {}", ticks);
+  println!("player updates:   {}", updates);
+  println!("v5 bytes/tick:    {:.1}", v5_bytes as f64 / ticks);
+  println!("delta bytes/tick: {:.1}", delta_bytes as f64 / ticks);
+  println!(
+    "savings:          {:.1}%",
+    100.0 * (1.0 - delta_bytes as f64 / v5_bytes.max(1) as f64)
+  );
+
+  Ok(())
+}
+
+/// Simulate a match with a number of planes flying around at random and
+/// record the updates that a server would send for it.
+fn synthetic_match() -> Result<Vec<u8>, Box<dyn Error>> {
+  let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+  let mut writer = ReplayWriter::new(Vec::new(), ReplayHeader::new("synthetic", GameType::FFA))?;
+
+  let planes = [
+    PlaneType::Predator,
+    PlaneType::Goliath,
+    PlaneType::Mohawk,
+    PlaneType::Tornado,
+    PlaneType::Prowler,
+  ];
+  let mut states: Vec<_> = (0..PLAYERS)
+    .map(|id| {
+      let mut state = PlaneState::new(planes[id as usize % planes.len()]);
+      state.pos.x = rng.range(-8000.0, 8000.0);
+      state.pos.y = rng.range(-4000.0, 4000.0);
+      state.keystate.up = true;
+      state
+    })
+    .collect();
+
+  for tick in 0..SYNTHETIC_TICKS {
+    let time = TICK * tick;
+
+    for (id, state) in states.iter_mut().enumerate() {
+      physics::step(state, 1.0);
+
+      // Like the official server, send an update whenever the inputs change
+      // and otherwise about once a second.
+      let changed = rng.next().is_multiple_of(30);
+      if changed {
+        let keys = rng.next();
+        state.keystate.up = keys & 1 != 0;
+        state.keystate.left = keys & 2 != 0;
+        state.keystate.right = keys & 4 != 0 && !state.keystate.left;
+        state.keystate.boost = keys & 24 == 24;
+      }
+      if !changed && !(tick + id as u32).is_multiple_of(60) {
+        continue;
+      }
+
+      let update = PlayerUpdate {
+        clock: (time.as_micros() / 10) as u32,
+        id: id as u16,
+        keystate: state.keystate,
+        upgrades: state.upgrades,
+        pos: state.pos,
+        rot: state.rot,
+        speed: state.speed,
+      };
+      writer.write_server(time, &update.into())?;
+    }
+  }
+
+  Ok(writer.finish()?)
+}
+
+/// A small xorshift generator so that the synthetic match is reproducible.
+struct Rng(u64);
+
+impl Rng {
+  fn next(&mut self) -> u64 {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 7;
+    self.0 ^= self.0 << 17;
+    self.0
+  }
+
+  fn range(&mut self, min: f32, max: f32) -> f32 {
+    let frac = (self.next() >> 40) as f32 / (1u64 << 24) as f32;
+    min + frac * (max - min)
+  }
+}
@@ -0,0 +1,386 @@
+//! A container frame that bundles multiple server packets together.
+//!
+//! Normally every websocket frame carries exactly one packet. Right after
+//! logging in a client receives a large burst of packets (e.g. a
+//! [`PlayerNew`](crate::server::PlayerNew) for every player in the game) and
+//! on high-latency links the per-frame overhead of these adds up. A batch
+//! frame combines many packet frames into one:
+//!
+//! ```text
+//! u8   packet id (BATCH_PACKET_NO)
+//! repeated until the end of the frame:
+//!   u16  length of the packet frame
+//!   ...  the packet frame
+//! ```
+//!
+//! The contained frames are encoded by whichever [`Protocol`] the connection
+//! uses, so batching works the same way for every protocol.
+//!
+//! # Handshake
+//! Clients opt in to batching by setting [`BATCH_FLAG`] in the protocol
+//! version of their [`Login`](crate::client::Login) packet. The
+//! [`ProtocolRegistry`](crate::ProtocolRegistry) ignores the flag when
+//! picking a protocol and servers can check for it with [`requested`].
+//! Servers must not send batch frames to clients that didn't ask for them.
+//!
+//! ```
+//! # use airmash_protocol::{batch, v5, ClientPacket, Protocol, ServerPacket};
+//! # use airmash_protocol::client::Login;
+//! # use airmash_protocol::server::PlayerLeave;
+//! # use airmash_protocol::v5::ProtocolV5;
+//! # fn main() -> Result<(), v5::Error> {
+//! let login = v5::serialize(&ClientPacket::Login(Login {
+//!   protocol: ProtocolV5::VERSION | batch::BATCH_FLAG,
+//!   name: "test".into(),
+//!   session: "none".into(),
+//!   horizon_x: 1920,
+//!   horizon_y: 1920,
+//!   flag: "UN".into(),
+//! }))?;
+//! assert!(batch::requested(&login));
+//!
+//! // On the server
+//! let mut encoder = batch::BatchEncoder::new();
+//! for id in 0..100 {
+//!   encoder.push(&ProtocolV5, &PlayerLeave { id }.into())?;
+//! }
+//! let frames = encoder.finish();
+//! assert_eq!(frames.len(), 1);
+//!
+//! // On the client
+//! let mut packets = vec![];
+//! for frame in batch::frames(&frames[0]) {
+//!   packets.push(ProtocolV5.deserialize_server(frame?)?);
+//! }
+//! assert_eq!(packets.len(), 100);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::mem;
+
+use crate::registry::requested_version;
+use crate::v5::{Error, ErrorExt as _, Result};
+use crate::{Protocol, ServerPacket};
+
+/// The packet id of a batch frame.
+///
+/// This is not used by any v5 packet.
+pub const BATCH_PACKET_NO: u8 = 209;
+
+/// Bit set in the protocol version of the client's `Login` packet to request
+/// batch frames.
+///
+/// Since this bit is ignored when negotiating the protocol, no protocol
+/// should use a version with this bit set.
+pub const BATCH_FLAG: u8 = 0x40;
+
+/// Whether the first frame sent by a client requests batch frames.
+///
+/// Returns `false` for [`Backup`](crate::client::Backup) packets and for
+/// frames that can't be used to negotiate a protocol.
+pub fn requested(frame: &[u8]) -> bool {
+  matches!(requested_version(frame), Ok(Some(version)) if version & BATCH_FLAG != 0)
+}
+
+/// Whether `frame` is a batch frame.
+pub fn is_batch(frame: &[u8]) -> bool {
+  frame.first() == Some(&BATCH_PACKET_NO)
+}
+
+/// Combines packet frames into as few batch frames as possible.
+///
+/// Batches are never longer than the maximum length of the encoder unless
+/// a single packet frame is longer than that. A batch that would only contain
+/// a single packet is sent as that packet's frame instead. Packet frames
+/// longer than `u16::MAX` bytes can't be part of a batch and are also sent by
+/// themselves.
+#[derive(Clone, Debug)]
+pub struct BatchEncoder {
+  max_len: usize,
+  pending: Vec<Vec<u8>>,
+  /// The length of the batch frame that `pending` would be encoded as.
+  pending_len: usize,
+  frames: Vec<Vec<u8>>,
+}
+
+impl BatchEncoder {
+  /// The default maximum length of a batch frame.
+  pub const DEFAULT_MAX_LEN: usize = 16 * 1024;
+
+  pub fn new() -> Self {
+    Self::with_max_len(Self::DEFAULT_MAX_LEN)
+  }
+
+  /// Create an encoder that produces batch frames of at most `max_len`
+  /// bytes.
+  pub fn with_max_len(max_len: usize) -> Self {
+    Self {
+      max_len,
+      pending: Vec::new(),
+      pending_len: 1,
+      frames: Vec::new(),
+    }
+  }
+
+  /// Serialize a packet using `protocol` and add the resulting frames to the
+  /// current batch.
+  pub fn push<P>(
+    &mut self,
+    protocol: &P,
+    packet: &ServerPacket,
+  ) -> std::result::Result<(), P::SerializeError>
+  where
+    P: Protocol + ?Sized,
+  {
+    for frame in protocol.serialize_server(packet)? {
+      self.push_frame(frame);
+    }
+
+    Ok(())
+  }
+
+  /// Add an already serialized packet frame to the current batch.
+  pub fn push_frame(&mut self, frame: Vec<u8>) {
+    if frame.len() > u16::MAX as usize {
+      self.flush();
+      self.frames.push(frame);
+      return;
+    }
+
+    let len = 2 + frame.len();
+    if !self.pending.is_empty() && self.pending_len + len > self.max_len {
+      self.flush();
+    }
+
+    self.pending_len += len;
+    self.pending.push(frame);
+  }
+
+  /// End the current batch. Frames pushed afterwards start a new one.
+  pub fn flush(&mut self) {
+    if self.pending.len() == 1 {
+      self.frames.extend(self.pending.pop());
+    } else if !self.pending.is_empty() {
+      let mut batch = Vec::with_capacity(self.pending_len);
+      batch.push(BATCH_PACKET_NO);
+
+      for frame in self.pending.drain(..) {
+        batch.extend_from_slice(&(frame.len() as u16).to_le_bytes());
+        batch.extend_from_slice(&frame);
+      }
+
+      self.frames.push(batch);
+    }
+
+    self.pending_len = 1;
+  }
+
+  /// Flush the current batch and take all the frames that are ready to be
+  /// sent. The encoder can be reused afterwards.
+  pub fn finish(&mut self) -> Vec<Vec<u8>> {
+    self.flush();
+    mem::take(&mut self.frames)
+  }
+
+  /// Whether nothing has been pushed since the last call to
+  /// [`finish`](Self::finish).
+  pub fn is_empty(&self) -> bool {
+    self.pending.is_empty() && self.frames.is_empty()
+  }
+}
+
+impl Default for BatchEncoder {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Iterate over the packet frames within a frame received from the server.
+///
+/// Frames that aren't batches are yielded unchanged so a client that opted in
+/// to batching can pass every frame it receives through this.
+pub fn frames(frame: &[u8]) -> Frames<'_> {
+  Frames {
+    offset: is_batch(frame) as usize,
+    single: !is_batch(frame),
+    data: frame,
+  }
+}
+
+/// Iterator over the packet frames within a batch frame.
+///
+/// Created by [`frames`]. If the batch is truncated then the last item is an
+/// error and the iterator ends after it.
+#[derive(Clone, Debug)]
+pub struct Frames<'a> {
+  data: &'a [u8],
+  offset: usize,
+  single: bool,
+}
+
+impl<'a> Iterator for Frames<'a> {
+  type Item = Result<&'a [u8]>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let data = &self.data[self.offset..];
+
+    if self.single {
+      self.single = false;
+      self.offset = self.data.len();
+      return Some(Ok(data));
+    }
+
+    let error = match data {
+      [] => return None,
+      [lo, hi, rest @ ..] => {
+        let len = u16::from_le_bytes([*lo, *hi]) as usize;
+        if let Some(frame) = rest.get(..len) {
+          self.offset += 2 + len;
+          return Some(Ok(frame));
+        }
+
+        Error::end_of_buffer(len, rest.len()).with_offset(self.offset + 2)
+      }
+      _ => Error::end_of_buffer(2, data.len()).with_offset(self.offset),
+    };
+
+    // Nothing after a truncated frame can be read.
+    self.offset = self.data.len();
+    Some(Err(
+      error.with_packet_id(BATCH_PACKET_NO).with_context("Batch"),
+    ))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::server::{PlayerLeave, PlayerNew};
+  use crate::v5::{self, ErrorKind, ProtocolV5};
+  use crate::ClientPacket;
+
+  fn leave(id: u16) -> ServerPacket {
+    PlayerLeave { id }.into()
+  }
+
+  fn decode(frames: &[Vec<u8>]) -> Vec<ServerPacket> {
+    frames
+      .iter()
+      .flat_map(|frame| super::frames(frame))
+      .map(|frame| v5::deserialize(frame.unwrap()).unwrap())
+      .collect()
+  }
+
+  #[test]
+  fn batch_roundtrip() {
+    let mut encoder = BatchEncoder::new();
+    for id in 0..10 {
+      encoder.push(&ProtocolV5, &leave(id)).unwrap();
+    }
+
+    let frames = encoder.finish();
+    assert_eq!(frames.len(), 1);
+    assert!(is_batch(&frames[0]));
+    // 1 byte for the id and 2 for each length on top of the 3 byte packets.
+    assert_eq!(frames[0].len(), 1 + 10 * 5);
+
+    let packets = decode(&frames);
+    assert_eq!(packets.len(), 10);
+    for (id, packet) in packets.into_iter().enumerate() {
+      match packet {
+        ServerPacket::PlayerLeave(leave) => assert_eq!(leave.id as usize, id),
+        other => panic!("unexpected packet {:?}", other),
+      }
+    }
+
+    assert!(encoder.is_empty());
+  }
+
+  #[test]
+  fn single_packet_is_not_batched() {
+    let mut encoder = BatchEncoder::new();
+    encoder.push(&ProtocolV5, &leave(3)).unwrap();
+
+    let frames = encoder.finish();
+    assert_eq!(frames, [v5::serialize(&leave(3)).unwrap()]);
+    assert_eq!(decode(&frames).len(), 1);
+  }
+
+  #[test]
+  fn batches_respect_max_len() {
+    let mut encoder = BatchEncoder::with_max_len(64);
+    for id in 0..100 {
+      encoder.push(&ProtocolV5, &leave(id)).unwrap();
+    }
+
+    let frames = encoder.finish();
+    assert!(frames.len() > 1);
+    assert!(frames.iter().all(|frame| frame.len() <= 64));
+    assert_eq!(decode(&frames).len(), 100);
+  }
+
+  #[test]
+  fn large_frames_are_sent_alone() {
+    let packet: ServerPacket = PlayerNew {
+      id: 1,
+      status: Default::default(),
+      name: vec![b'a'; 255].into(),
+      ty: Default::default(),
+      team: 1,
+      pos: [0.0, 0.0].into(),
+      rot: 0.0,
+      flag: Default::default(),
+      upgrades: Default::default(),
+    }
+    .into();
+    let frame = v5::serialize(&packet).unwrap();
+
+    let mut encoder = BatchEncoder::with_max_len(16);
+    encoder.push(&ProtocolV5, &leave(0)).unwrap();
+    encoder.push(&ProtocolV5, &leave(1)).unwrap();
+    encoder.push_frame(frame.clone());
+    encoder.push_frame(vec![0; u16::MAX as usize + 1]);
+
+    let frames = encoder.finish();
+    assert_eq!(frames.len(), 3);
+    assert!(is_batch(&frames[0]));
+    assert_eq!(frames[1], frame);
+    assert_eq!(frames[2].len(), u16::MAX as usize + 1);
+  }
+
+  #[test]
+  fn truncated_batch() {
+    let mut frame = vec![BATCH_PACKET_NO, 3, 0, 11, 5, 0, 3, 0, 11];
+    let mut iter = frames(&frame);
+    assert_eq!(iter.next().unwrap().unwrap(), [11, 5, 0]);
+
+    let err = iter.next().unwrap().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::EndOfBuffer);
+    assert_eq!(err.context(), ["Batch"]);
+    assert!(iter.next().is_none());
+
+    frame.truncate(7);
+    let err = frames(&frame).nth(1).unwrap().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::EndOfBuffer);
+  }
+
+  #[test]
+  fn requested_from_login() {
+    let login = |protocol| {
+      v5::serialize(&ClientPacket::Login(crate::client::Login {
+        protocol,
+        name: "test".into(),
+        session: "none".into(),
+        horizon_x: 0,
+        horizon_y: 0,
+        flag: "UN".into(),
+      }))
+      .unwrap()
+    };
+
+    assert!(requested(&login(5 | BATCH_FLAG)));
+    assert!(!requested(&login(5)));
+    assert!(!requested(&[]));
+  }
+}
@@ -0,0 +1,325 @@
+//! Decode captured airmash frames and print them in a readable form.
+//!
+//! Run with `--help` for usage details.
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::exit;
+
+use airmash_protocol::replay::{Direction, ReplayFrame, ReplayPacket, ReplayReader};
+use airmash_protocol::v5::{self, AirmashDeserializerV5, DeserializeV5, FieldSpan};
+use airmash_protocol::{ClientPacket, ServerPacket};
+use base64::Engine as _;
+
+const USAGE: &str = "\
+Decode captured airmash protocol v5 frames.
+
+USAGE:
+    airmash-dump [OPTIONS] [FILE]
+
+Frames are read from FILE, or from stdin if no file is given. For hex and
+base64 input every non-empty line is treated as a single frame.
+
+OPTIONS:
+    -i, --input <FORMAT>     Input format: hex (default), base64, or replay
+    -o, --output <FORMAT>    Output format: debug (default), json, or annotate
+    -c, --client             Frames were sent by the client
+    -s, --server             Frames were sent by the server (default)
+    -h, --help               Print this help message
+";
+
+#[derive(Copy, Clone, PartialEq)]
+enum Input {
+  Hex,
+  Base64,
+  Replay,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Output {
+  Debug,
+  Json,
+  Annotate,
+}
+
+struct Args {
+  input: Input,
+  output: Output,
+  direction: Direction,
+  file: Option<String>,
+}
+
+fn usage_error(msg: &str) -> ! {
+  eprintln!("error: {}\n\n{}", msg, USAGE);
+  exit(2);
+}
+
+fn parse_args() -> Args {
+  let mut args = Args {
+    input: Input::Hex,
+    output: Output::Debug,
+    direction: Direction::ServerToClient,
+    file: None,
+  };
+
+  let mut iter = std::env::args().skip(1);
+  while let Some(arg) = iter.next() {
+    match arg.as_str() {
+      "-h" | "--help" => {
+        print!("{}", USAGE);
+        exit(0);
+      }
+      "-c" | "--client" => args.direction = Direction::ClientToServer,
+      "-s" | "--server" => args.direction = Direction::ServerToClient,
+      "-i" | "--input" => {
+        args.input = match iter.next().as_deref() {
+          Some("hex") => Input::Hex,
+          Some("base64") => Input::Base64,
+          Some("replay") => Input::Replay,
+          Some(other) => usage_error(&format!("unknown input format `{}`", other)),
+          None => usage_error("missing input format"),
+        }
+      }
+      "-o" | "--output" => {
+        args.output = match iter.next().as_deref() {
+          Some("debug") => Output::Debug,
+          Some("json") => Output::Json,
+          Some("annotate") => Output::Annotate,
+          Some(other) => usage_error(&format!("unknown output format `{}`", other)),
+          None => usage_error("missing output format"),
+        }
+      }
+      flag if flag.starts_with('-') && flag != "-" => {
+        usage_error(&format!("unknown option `{}`", flag))
+      }
+      file if args.file.is_none() => args.file = Some(file.to_owned()),
+      _ => usage_error("only one input file may be given"),
+    }
+  }
+
+  args
+}
+
+fn open_input(file: Option<&str>) -> io::Result<Box<dyn Read>> {
+  Ok(match file {
+    None | Some("-") => Box::new(io::stdin()),
+    Some(path) => Box::new(File::open(path)?),
+  })
+}
+
+/// Read all frames from a text input where every line is one encoded frame.
+fn read_text_frames(input: Box<dyn Read>, format: Input, direction: Direction) -> Vec<Frame> {
+  let mut frames = Vec::new();
+
+  for (lineno, line) in BufReader::new(input).lines().enumerate() {
+    let line = line.unwrap_or_else(|e| fatal(&format!("failed to read input: {}", e)));
+    let line: String = line.split_whitespace().collect();
+    if line.is_empty() {
+      continue;
+    }
+
+    let data = match format {
+      Input::Hex => hex::decode(&line).map_err(|e| e.to_string()),
+      Input::Base64 => base64::engine::general_purpose::STANDARD
+        .decode(&line)
+        .map_err(|e| e.to_string()),
+      Input::Replay => unreachable!(),
+    };
+
+    match data {
+      Ok(data) => frames.push(Frame {
+        label: format!("line {}", lineno + 1),
+        direction,
+        data,
+      }),
+      Err(e) => fatal(&format!("line {}: invalid input: {}", lineno + 1, e)),
+    }
+  }
+
+  frames
+}
+
+fn read_replay_frames(input: Box<dyn Read>) -> Vec<Frame> {
+  let mut reader = ReplayReader::new(BufReader::new(input))
+    .unwrap_or_else(|e| fatal(&format!("failed to read replay: {}", e)));
+
+  let header = reader.header();
+  println!(
+    "# replay: room {:?}, game type {:?}, protocol v{}",
+    header.room, header.game_type, header.protocol_version
+  );
+
+  let mut frames = Vec::new();
+  loop {
+    match reader.next_frame() {
+      Ok(Some(ReplayFrame {
+        time,
+        direction,
+        data,
+      })) => frames.push(Frame {
+        label: format!("t={:.3}s", time.as_secs_f64()),
+        direction,
+        data,
+      }),
+      Ok(None) => break,
+      Err(e) => {
+        eprintln!("error: failed to read replay frame: {}", e);
+        break;
+      }
+    }
+  }
+
+  frames
+}
+
+struct Frame {
+  label: String,
+  direction: Direction,
+  data: Vec<u8>,
+}
+
+struct Decoded<T> {
+  result: v5::Result<T>,
+  spans: Vec<FieldSpan>,
+  offset: usize,
+}
+
+impl<T> Decoded<T> {
+  fn map<U>(self, func: impl FnOnce(T) -> U) -> Decoded<U> {
+    Decoded {
+      result: self.result.map(func),
+      spans: self.spans,
+      offset: self.offset,
+    }
+  }
+}
+
+fn decode<T>(data: &[u8]) -> Decoded<T>
+where
+  T: for<'de> DeserializeV5<'de>,
+{
+  let mut de = AirmashDeserializerV5::with_trace(data);
+  let result = de
+    .deserialize::<T>()
+    .and_then(|value| match de.remainder().is_empty() {
+      true => Ok(value),
+      false => Err(v5::Error::new(v5::ErrorKind::UnexpectedDataRemaining)),
+    });
+
+  Decoded {
+    result,
+    offset: de.offset(),
+    spans: de.take_trace(),
+  }
+}
+
+fn hex_bytes(data: &[u8]) -> String {
+  const MAX_BYTES: usize = 12;
+
+  let mut out = String::new();
+  for byte in data.iter().take(MAX_BYTES) {
+    let _ = write!(out, "{:02x} ", byte);
+  }
+  if data.len() > MAX_BYTES {
+    out.push_str("...");
+  }
+  out
+}
+
+fn print_annotations(data: &[u8], spans: &[FieldSpan]) {
+  for span in spans {
+    println!(
+      "  {:04x}..{:04x}  {:40} {}{}",
+      span.range.start,
+      span.range.end,
+      hex_bytes(&data[span.range.clone()]),
+      "  ".repeat(span.depth),
+      span.path
+    );
+  }
+}
+
+fn print_frame(frame: &Frame, output: Output) -> bool {
+  let decoded = match frame.direction {
+    Direction::ServerToClient => decode::<ServerPacket>(&frame.data).map(ReplayPacket::Server),
+    Direction::ClientToServer => decode::<ClientPacket>(&frame.data).map(ReplayPacket::Client),
+  };
+
+  println!(
+    "# {} ({:?}, {} bytes)",
+    frame.label,
+    frame.direction,
+    frame.data.len()
+  );
+
+  let packet = match decoded.result {
+    Ok(packet) => packet,
+    Err(e) => {
+      println!("error: {}", e.to_string().trim_end());
+      if e.offset().is_none() {
+        println!("At byte offset: {}", decoded.offset);
+      }
+      if !decoded.spans.is_empty() {
+        println!("decoded before failure:");
+        print_annotations(&frame.data, &decoded.spans);
+      }
+      return false;
+    }
+  };
+
+  match output {
+    Output::Debug => match &packet {
+      ReplayPacket::Server(p) => println!("{:#?}", p),
+      ReplayPacket::Client(p) => println!("{:#?}", p),
+    },
+    Output::Json => {
+      let json = match &packet {
+        ReplayPacket::Server(p) => serde_json::to_string_pretty(p),
+        ReplayPacket::Client(p) => serde_json::to_string_pretty(p),
+      };
+
+      match json {
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+          println!("error: failed to convert packet to JSON: {}", e);
+          return false;
+        }
+      }
+    }
+    Output::Annotate => print_annotations(&frame.data, &decoded.spans),
+  }
+
+  true
+}
+
+fn fatal(msg: &str) -> ! {
+  eprintln!("error: {}", msg);
+  exit(1);
+}
+
+fn main() {
+  let args = parse_args();
+  let input = open_input(args.file.as_deref()).unwrap_or_else(|e| {
+    fatal(&format!(
+      "failed to open `{}`: {}",
+      args.file.as_deref().unwrap_or("-"),
+      e
+    ))
+  });
+
+  let frames = match args.input {
+    Input::Replay => read_replay_frames(input),
+    format => read_text_frames(input, format, args.direction),
+  };
+
+  let mut ok = true;
+  for frame in &frames {
+    ok &= print_frame(frame, args.output);
+  }
+
+  let _ = io::stdout().flush();
+  if !ok {
+    exit(1);
+  }
+}
@@ -39,6 +39,15 @@ pub enum ClientPacket {
   Say(Say),
   VoteMute(VoteMute),
   LocalPing(LocalPing),
+
+  #[cfg(feature = "ab-server")]
+  SyncAuth(SyncAuth),
+  #[cfg(feature = "ab-server")]
+  SyncInit(SyncInit),
+  #[cfg(feature = "ab-server")]
+  SyncUpdate(SyncUpdate),
+  #[cfg(feature = "ab-server")]
+  SyncAck(SyncAck),
 }
 
 macro_rules! impl_from_newtype {
@@ -66,5 +75,13 @@ impl_from_newtype!(Say);
 impl_from_newtype!(VoteMute);
 impl_from_newtype!(LocalPing);
 
+#[cfg(feature = "ab-server")]
+const _: () = {
+  impl_from_newtype!(SyncAuth);
+  impl_from_newtype!(SyncInit);
+  impl_from_newtype!(SyncUpdate);
+  impl_from_newtype!(SyncAck);
+};
+
 impl_from_empty!(Ack);
 impl_from_empty!(ScoreDetailed);
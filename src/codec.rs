@@ -0,0 +1,296 @@
+//! [`tokio_util::codec`] integration for the v5 protocol.
+//!
+//! A v5 packet doesn't carry its own length so it needs to be wrapped in some
+//! sort of framing before it can be sent over a byte stream. By default the
+//! codecs here prefix every packet with its length using a
+//! [`LengthDelimitedCodec`]. For transports that already preserve message
+//! boundaries (e.g. individual websocket messages) the codecs can instead be
+//! created with [`CodecV5::message_framed`], in which case every buffer passed
+//! to the decoder is treated as exactly one packet.
+//!
+//! ```
+//! # use airmash_protocol::codec::ClientCodecV5;
+//! # use airmash_protocol::{ClientPacket, KeyCode, client::Key};
+//! use tokio_util::codec::Encoder;
+//! # use bytes::BytesMut;
+//! # fn main() -> Result<(), airmash_protocol::codec::CodecError> {
+//! let mut codec = ClientCodecV5::new();
+//! let mut buffer = BytesMut::new();
+//!
+//! codec.encode(
+//!   ClientPacket::from(Key {
+//!     seq: 0,
+//!     key: KeyCode::Fire,
+//!     state: true,
+//!   }),
+//!   &mut buffer,
+//! )?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+
+use bytes::{Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder, LengthDelimitedCodec};
+
+use crate::v5::{self, DeserializeV5, SerializeV5};
+use crate::{ClientPacket, ServerPacket};
+
+/// Codec to be used by clients: encodes [`ClientPacket`]s and decodes
+/// [`ServerPacket`]s.
+pub type ClientCodecV5 = CodecV5<ClientPacket, ServerPacket>;
+
+/// Codec to be used by servers: encodes [`ServerPacket`]s and decodes
+/// [`ClientPacket`]s.
+pub type ServerCodecV5 = CodecV5<ServerPacket, ClientPacket>;
+
+/// Error returned by [`CodecV5`].
+#[derive(Debug)]
+pub enum CodecError {
+  /// The underlying transport or framing failed.
+  Io(io::Error),
+  /// A packet could not be serialized or deserialized.
+  Protocol(v5::Error),
+}
+
+impl fmt::Display for CodecError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Io(e) => write!(f, "io error: {}", e),
+      Self::Protocol(e) => e.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for CodecError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Io(e) => Some(e),
+      Self::Protocol(e) => Some(e),
+    }
+  }
+}
+
+impl From<io::Error> for CodecError {
+  fn from(e: io::Error) -> Self {
+    Self::Io(e)
+  }
+}
+
+impl From<v5::Error> for CodecError {
+  fn from(e: v5::Error) -> Self {
+    Self::Protocol(e)
+  }
+}
+
+impl From<CodecError> for io::Error {
+  fn from(e: CodecError) -> Self {
+    match e {
+      CodecError::Io(e) => e,
+      CodecError::Protocol(e) => io::Error::new(io::ErrorKind::InvalidData, e),
+    }
+  }
+}
+
+enum Framing {
+  LengthDelimited(LengthDelimitedCodec),
+  Message,
+}
+
+/// A [`Decoder`] and [`Encoder`] for v5 packets.
+///
+/// `E` is the packet type being encoded and `D` is the packet type being
+/// decoded. Usually you'll want to use one of [`ClientCodecV5`] or
+/// [`ServerCodecV5`] instead of naming this type directly.
+pub struct CodecV5<E, D> {
+  framing: Framing,
+  _marker: PhantomData<fn(E) -> D>,
+}
+
+impl<E, D> CodecV5<E, D> {
+  /// Create a codec which prefixes each packet with its length using the
+  /// default settings of [`LengthDelimitedCodec`].
+  pub fn new() -> Self {
+    Self::length_delimited(LengthDelimitedCodec::new())
+  }
+
+  /// Create a codec which frames packets using the provided
+  /// [`LengthDelimitedCodec`].
+  pub fn length_delimited(codec: LengthDelimitedCodec) -> Self {
+    Self {
+      framing: Framing::LengthDelimited(codec),
+      _marker: PhantomData,
+    }
+  }
+
+  /// Create a codec for a transport that already preserves message boundaries.
+  ///
+  /// Packets are encoded without any framing and each non-empty buffer passed
+  /// to [`decode`](Decoder::decode) is decoded as a single packet.
+  pub fn message_framed() -> Self {
+    Self {
+      framing: Framing::Message,
+      _marker: PhantomData,
+    }
+  }
+}
+
+impl<E, D> Default for CodecV5<E, D> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<E, D> fmt::Debug for CodecV5<E, D> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let framing = match self.framing {
+      Framing::LengthDelimited(_) => "LengthDelimited",
+      Framing::Message => "Message",
+    };
+
+    f.debug_struct("CodecV5")
+      .field("framing", &framing)
+      .finish()
+  }
+}
+
+impl<E, D> Decoder for CodecV5<E, D>
+where
+  D: for<'de> DeserializeV5<'de>,
+{
+  type Item = D;
+  type Error = CodecError;
+
+  fn decode(&mut self, src: &mut BytesMut) -> Result<Option<D>, CodecError> {
+    let frame = match &mut self.framing {
+      Framing::LengthDelimited(codec) => match codec.decode(src)? {
+        Some(frame) => frame,
+        None => return Ok(None),
+      },
+      Framing::Message if src.is_empty() => return Ok(None),
+      Framing::Message => src.split(),
+    };
+
+    Ok(Some(v5::deserialize(&frame)?))
+  }
+}
+
+impl<E, D> Encoder<E> for CodecV5<E, D>
+where
+  E: SerializeV5,
+{
+  type Error = CodecError;
+
+  fn encode(&mut self, item: E, dst: &mut BytesMut) -> Result<(), CodecError> {
+    Encoder::<&E>::encode(self, &item, dst)
+  }
+}
+
+impl<'a, E, D> Encoder<&'a E> for CodecV5<E, D>
+where
+  E: SerializeV5,
+{
+  type Error = CodecError;
+
+  fn encode(&mut self, item: &'a E, dst: &mut BytesMut) -> Result<(), CodecError> {
+    match &mut self.framing {
+      Framing::LengthDelimited(codec) => codec.encode(Bytes::from(v5::serialize(item)?), dst)?,
+      Framing::Message => {
+        // Don't leave a partially serialized packet in the buffer on failure.
+        let len = dst.len();
+        if let Err(e) = v5::serialize_into(item, dst) {
+          dst.truncate(len);
+          return Err(e.into());
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use futures_util::{SinkExt, StreamExt};
+  use tokio_util::codec::Framed;
+
+  use super::*;
+  use crate::client::Chat;
+  use crate::server::ChatPublic;
+  use crate::v5::ErrorKind;
+
+  #[tokio::test]
+  async fn duplex_roundtrip() {
+    let (client, server) = tokio::io::duplex(64);
+    let mut client = Framed::new(client, ClientCodecV5::new());
+    let mut server = Framed::new(server, ServerCodecV5::new());
+
+    client
+      .send(ClientPacket::Chat(Chat {
+        text: "hello".into(),
+      }))
+      .await
+      .unwrap();
+    client.send(ClientPacket::Ack).await.unwrap();
+
+    match server.next().await.unwrap().unwrap() {
+      ClientPacket::Chat(chat) => assert_eq!(chat.text, "hello"),
+      packet => panic!("unexpected packet {:?}", packet),
+    }
+    assert!(matches!(
+      server.next().await.unwrap().unwrap(),
+      ClientPacket::Ack
+    ));
+
+    server
+      .send(ServerPacket::ChatPublic(ChatPublic {
+        id: 5,
+        text: "world".into(),
+      }))
+      .await
+      .unwrap();
+
+    match client.next().await.unwrap().unwrap() {
+      ServerPacket::ChatPublic(chat) => {
+        assert_eq!(chat.id, 5);
+        assert_eq!(chat.text, "world");
+      }
+      packet => panic!("unexpected packet {:?}", packet),
+    }
+  }
+
+  #[tokio::test]
+  async fn invalid_packet_is_protocol_error() {
+    let (client, server) = tokio::io::duplex(64);
+    let mut client = Framed::new(client, LengthDelimitedCodec::new());
+    let mut server = Framed::new(server, ServerCodecV5::new());
+
+    client.send(Bytes::from_static(&[200])).await.unwrap();
+
+    match server.next().await.unwrap() {
+      Err(CodecError::Protocol(e)) => assert_eq!(e.kind(), ErrorKind::InvalidEnumValue),
+      result => panic!("unexpected result {:?}", result),
+    }
+  }
+
+  #[test]
+  fn message_framed() {
+    let mut codec = ServerCodecV5::message_framed();
+    let mut buffer = BytesMut::new();
+
+    assert!(codec.decode(&mut buffer).unwrap().is_none());
+
+    let mut client = ClientCodecV5::message_framed();
+    client.encode(ClientPacket::Ack, &mut buffer).unwrap();
+    assert_eq!(&buffer[..], &[5]);
+
+    assert!(matches!(
+      codec.decode(&mut buffer).unwrap(),
+      Some(ClientPacket::Ack)
+    ));
+    assert!(buffer.is_empty());
+  }
+}
@@ -0,0 +1,297 @@
+use std::fmt;
+
+use bstr::{BStr, BString, ByteSlice};
+
+use super::{GameCommand, InvalidCommandData, SpectateTarget};
+use crate::client::{Chat, Command, Say, TeamChat, VoteMute, Whisper};
+use crate::enums::{PlaneType, UpgradeType};
+use crate::types::Player;
+use crate::ClientPacket;
+
+/// Error returned by [`parse_chat`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ChatParseError {
+  /// The line started with `/` but the command isn't known.
+  UnknownCommand(BString),
+  /// The command needs an argument that wasn't provided.
+  MissingArgument(&'static str),
+  /// The player name didn't match any player.
+  UnknownPlayer(BString),
+  /// The argument of a command that maps to a [`GameCommand`] was invalid.
+  InvalidArgument(InvalidCommandData),
+}
+
+impl fmt::Display for ChatParseError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::UnknownCommand(com) => write!(f, "unknown command `/{}`", com),
+      Self::MissingArgument(com) => write!(f, "missing argument for `/{}`", com),
+      Self::UnknownPlayer(name) => write!(f, "unknown player `{}`", name),
+      Self::InvalidArgument(e) => e.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for ChatParseError {}
+
+const PLANES: &[(&str, PlaneType)] = &[
+  ("predator", PlaneType::Predator),
+  ("goliath", PlaneType::Goliath),
+  ("mohawk", PlaneType::Mohawk),
+  ("tornado", PlaneType::Tornado),
+  ("prowler", PlaneType::Prowler),
+];
+
+const UPGRADES: &[(&str, UpgradeType)] = &[
+  ("speed", UpgradeType::Speed),
+  ("defense", UpgradeType::Defense),
+  ("energy", UpgradeType::Energy),
+  ("missile", UpgradeType::Missile),
+];
+
+fn trim_start(text: &[u8]) -> &[u8] {
+  let start = text.iter().position(|b| !b.is_ascii_whitespace());
+  &text[start.unwrap_or(text.len())..]
+}
+
+fn trim(text: &[u8]) -> &[u8] {
+  let text = trim_start(text);
+  let end = text.iter().rposition(|b| !b.is_ascii_whitespace());
+  &text[..end.map(|end| end + 1).unwrap_or(0)]
+}
+
+/// Split off the first whitespace-separated word of `text`.
+fn split_word(text: &[u8]) -> (&[u8], &[u8]) {
+  let text = trim_start(text);
+  match text.find_byte(b' ') {
+    Some(idx) => (&text[..idx], trim_start(&text[idx + 1..])),
+    None => (text, b""),
+  }
+}
+
+/// Split a player name off the front of `text`.
+///
+/// Player names may contain spaces so this picks the longest prefix (ending
+/// at a word boundary) that `lookup` recognizes.
+fn split_player<'a, F>(text: &'a [u8], lookup: &mut F) -> Result<(Player, &'a [u8]), ChatParseError>
+where
+  F: FnMut(&BStr) -> Option<Player>,
+{
+  let text = trim_start(text);
+  let ends = text
+    .iter()
+    .enumerate()
+    .filter(|&(_, &b)| b == b' ')
+    .map(|(idx, _)| idx)
+    .chain(std::iter::once(text.len()));
+
+  let mut found = None;
+  for end in ends {
+    if let Some(id) = lookup(text[..end].as_bstr()) {
+      found = Some((id, trim_start(&text[end..])));
+    }
+  }
+
+  found.ok_or_else(|| ChatParseError::UnknownPlayer(split_word(text).0.into()))
+}
+
+fn named<T: Copy>(table: &[(&str, T)], arg: &[u8], from: fn(u8) -> T) -> Option<T> {
+  let arg = trim(arg);
+  table
+    .iter()
+    .find(|(name, _)| arg.eq_ignore_ascii_case(name.as_bytes()))
+    .map(|&(_, value)| value)
+    .or_else(|| arg.to_str().ok()?.parse().ok().map(from))
+}
+
+fn game_command(com: &'static str, arg: &[u8], command: Option<GameCommand>) -> ClientPacket {
+  match command {
+    Some(command) => command.into(),
+    // Let the GameCommand parser decide whether the argument is valid.
+    None => Command {
+      com: com.into(),
+      data: arg.into(),
+    }
+    .into(),
+  }
+}
+
+/// Parse a line typed into the chat box into the packet that it represents.
+///
+/// The supported syntax is
+///
+/// | Input                  | Packet                              |
+/// |------------------------|-------------------------------------|
+/// | `text`                 | [`Chat`]                            |
+/// | `/s text`              | [`Say`]                             |
+/// | `/t text`              | [`TeamChat`]                        |
+/// | `/w name text`         | [`Whisper`]                         |
+/// | `/votemute name`       | [`VoteMute`]                        |
+/// | `/flag code`           | `flag` [`Command`]                  |
+/// | `/spectate [name]`     | `spectate` [`Command`]              |
+/// | `/respawn plane`       | `respawn` [`Command`]               |
+/// | `/upgrade type`        | `upgrade` [`Command`]               |
+/// | `#com data`            | [`Command`] with any `com`          |
+///
+/// Player names are resolved using `lookup`. Plane and upgrade types may be
+/// given either by name or by number.
+///
+/// ```
+/// # use airmash_protocol::command::parse_chat;
+/// # use airmash_protocol::ClientPacket;
+/// let packet = parse_chat("/w some player hello there", |name| match &**name {
+///   b"some player" => Some(17),
+///   _ => None,
+/// })
+/// .unwrap();
+///
+/// match packet {
+///   ClientPacket::Whisper(w) => {
+///     assert_eq!(w.id, 17);
+///     assert_eq!(w.text, "hello there");
+///   }
+///   _ => unreachable!(),
+/// }
+/// ```
+pub fn parse_chat<T, F>(line: &T, mut lookup: F) -> Result<ClientPacket, ChatParseError>
+where
+  T: AsRef<[u8]> + ?Sized,
+  F: FnMut(&BStr) -> Option<Player>,
+{
+  let line = line.as_ref();
+
+  if let Some(rest) = line.strip_prefix(b"#") {
+    let (com, data) = split_word(rest);
+    return Ok(
+      Command {
+        com: com.into(),
+        data: data.into(),
+      }
+      .into(),
+    );
+  }
+
+  let rest = match line.strip_prefix(b"/") {
+    Some(rest) => rest,
+    None => return Ok(Chat { text: line.into() }.into()),
+  };
+
+  let (com, arg) = split_word(rest);
+  let required = |name: &'static str| match arg.is_empty() {
+    true => Err(ChatParseError::MissingArgument(name)),
+    false => Ok(arg),
+  };
+
+  let packet = match com {
+    b"s" => Say {
+      text: required("s")?.into(),
+    }
+    .into(),
+    b"t" => TeamChat {
+      text: required("t")?.into(),
+    }
+    .into(),
+    b"w" => {
+      let (id, text) = split_player(required("w")?, &mut lookup)?;
+      if text.is_empty() {
+        return Err(ChatParseError::MissingArgument("w"));
+      }
+
+      Whisper {
+        id,
+        text: text.into(),
+      }
+      .into()
+    }
+    b"votemute" => {
+      let (id, _) = split_player(required("votemute")?, &mut lookup)?;
+      VoteMute { id }.into()
+    }
+    b"flag" => Command {
+      com: "flag".into(),
+      data: trim(required("flag")?).into(),
+    }
+    .into(),
+    b"spectate" if arg.is_empty() => GameCommand::Spectate(SpectateTarget::Force).into(),
+    b"spectate" => {
+      let (id, _) = split_player(arg, &mut lookup)?;
+      GameCommand::Spectate(SpectateTarget::Player(id)).into()
+    }
+    b"respawn" => {
+      let arg = required("respawn")?;
+      let plane = named(PLANES, arg, PlaneType::from).map(GameCommand::Respawn);
+      game_command("respawn", arg, plane)
+    }
+    b"upgrade" => {
+      let arg = required("upgrade")?;
+      let upgrade = named(UPGRADES, arg, UpgradeType::from).map(GameCommand::Upgrade);
+      game_command("upgrade", arg, upgrade)
+    }
+    _ => return Err(ChatParseError::UnknownCommand(com.into())),
+  };
+
+  // Make sure that commands we produce are ones the server will accept.
+  if let ClientPacket::Command(command) = &packet {
+    use std::convert::TryFrom;
+
+    GameCommand::try_from(command).map_err(ChatParseError::InvalidArgument)?;
+  }
+
+  Ok(packet)
+}
+
+/// Format a packet as the line that would be typed into the chat box to send
+/// it. This is the inverse of [`parse_chat`].
+///
+/// Player names are resolved using `name_of`. Returns `None` if the packet
+/// isn't one that can be sent through chat, or if a player name couldn't be
+/// found.
+///
+/// Note that a [`Chat`] whose text starts with `/` or `#` will not parse back
+/// into the same packet.
+pub fn format_chat<F>(packet: &ClientPacket, mut name_of: F) -> Option<BString>
+where
+  F: FnMut(Player) -> Option<BString>,
+{
+  use std::convert::TryFrom;
+
+  let mut out = BString::from(Vec::new());
+  let mut push = |parts: &[&[u8]]| {
+    for part in parts {
+      out.extend_from_slice(part);
+    }
+  };
+
+  match packet {
+    ClientPacket::Chat(chat) => push(&[&chat.text]),
+    ClientPacket::Say(say) => push(&[b"/s ", &say.text]),
+    ClientPacket::TeamChat(chat) => push(&[b"/t ", &chat.text]),
+    ClientPacket::Whisper(w) => push(&[b"/w ", &name_of(w.id)?, b" ", &w.text]),
+    ClientPacket::VoteMute(v) => push(&[b"/votemute ", &name_of(v.id)?]),
+    ClientPacket::Command(command) => match GameCommand::try_from(command) {
+      Ok(GameCommand::Flag(_)) => push(&[b"/flag ", &command.data]),
+      Ok(GameCommand::Spectate(SpectateTarget::Force)) => push(&[b"/spectate"]),
+      Ok(GameCommand::Spectate(SpectateTarget::Player(id))) => {
+        push(&[b"/spectate ", &name_of(id)?])
+      }
+      Ok(GameCommand::Respawn(plane)) => match PLANES.iter().find(|(_, p)| *p == plane) {
+        Some((name, _)) => push(&[b"/respawn ", name.as_bytes()]),
+        None => push(&[b"/respawn ", &command.data]),
+      },
+      Ok(GameCommand::Upgrade(upgrade)) => match UPGRADES.iter().find(|(_, u)| *u == upgrade) {
+        Some((name, _)) => push(&[b"/upgrade ", name.as_bytes()]),
+        None => push(&[b"/upgrade ", &command.data]),
+      },
+      // Everything else has no slash command so use the raw form.
+      _ => {
+        push(&[b"#", &command.com]);
+        if !command.data.is_empty() {
+          push(&[b" ", &command.data]);
+        }
+      }
+    },
+    _ => return None,
+  }
+
+  Some(out)
+}
@@ -0,0 +1,193 @@
+//! Typed versions of the commands that clients send via
+//! [`Command`](crate::client::Command) packets, along with the chat syntax
+//! used to send them (see [`parse_chat`] and [`format_chat`]).
+//!
+//! ```
+//! # use std::convert::TryFrom;
+//! # use airmash_protocol::client::Command;
+//! # use airmash_protocol::command::{GameCommand, SpectateTarget};
+//! let command = Command::from(GameCommand::Spectate(SpectateTarget::Force));
+//! assert_eq!(command.com, "spectate");
+//! assert_eq!(command.data, "-3");
+//!
+//! match GameCommand::try_from(&command).unwrap() {
+//!   GameCommand::Spectate(SpectateTarget::Force) => (),
+//!   _ => unreachable!(),
+//! }
+//! ```
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use bstr::{BString, ByteSlice};
+
+use crate::client::Command;
+use crate::enums::{FlagCode, PlaneType, UpgradeType};
+use crate::types::Player;
+
+mod chat;
+#[cfg(test)]
+mod tests;
+
+pub use self::chat::{format_chat, parse_chat, ChatParseError};
+
+/// The player to focus on when spectating.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum SpectateTarget {
+  /// Switch focus to the next player.
+  Next,
+  /// Switch focus to the previous player.
+  Prev,
+  /// Force the player into spectate mode.
+  Force,
+  /// Spectate a specific player.
+  Player(Player),
+}
+
+impl SpectateTarget {
+  fn from_data(data: &[u8]) -> Option<Self> {
+    Some(match parse_int::<i32>(data)? {
+      -1 => Self::Next,
+      -2 => Self::Prev,
+      -3 => Self::Force,
+      id => Self::Player(Player::try_from(id).ok()?),
+    })
+  }
+
+  fn to_data(self) -> BString {
+    match self {
+      Self::Next => "-1".into(),
+      Self::Prev => "-2".into(),
+      Self::Force => "-3".into(),
+      Self::Player(id) => id.to_string().into(),
+    }
+  }
+}
+
+/// A [`Command`] with its data parsed according to the command name.
+///
+/// Commands that the official server doesn't recognize are kept in
+/// [`Other`](GameCommand::Other).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GameCommand {
+  /// `"spectate"`
+  Spectate(SpectateTarget),
+  /// `"upgrade"`
+  Upgrade(UpgradeType),
+  /// `"flag"`
+  Flag(FlagCode),
+  /// `"respawn"`
+  Respawn(PlaneType),
+  /// Any other command.
+  Other { com: BString, data: BString },
+}
+
+impl GameCommand {
+  /// The name of this command as sent in [`Command::com`].
+  pub fn name(&self) -> &[u8] {
+    match self {
+      Self::Spectate(_) => b"spectate",
+      Self::Upgrade(_) => b"upgrade",
+      Self::Flag(_) => b"flag",
+      Self::Respawn(_) => b"respawn",
+      Self::Other { com, .. } => com.as_bytes(),
+    }
+  }
+}
+
+/// Error for when the data of a known command couldn't be parsed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidCommandData {
+  /// The name of the command.
+  pub com: &'static str,
+  /// The data that failed to parse.
+  pub data: BString,
+}
+
+impl fmt::Display for InvalidCommandData {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "invalid data for `{}` command: {:?}",
+      self.com, self.data
+    )
+  }
+}
+
+impl std::error::Error for InvalidCommandData {}
+
+fn parse_int<T: std::str::FromStr>(data: &[u8]) -> Option<T> {
+  data.to_str().ok()?.trim().parse().ok()
+}
+
+impl<'a> TryFrom<&'a Command> for GameCommand {
+  type Error = InvalidCommandData;
+
+  fn try_from(command: &'a Command) -> Result<Self, Self::Error> {
+    let data = command.data.as_bytes();
+    let (com, parsed) = match command.com.as_bytes() {
+      b"spectate" => (
+        "spectate",
+        SpectateTarget::from_data(data).map(Self::Spectate),
+      ),
+      b"upgrade" => (
+        "upgrade",
+        parse_int::<u8>(data).map(|v| Self::Upgrade(v.into())),
+      ),
+      b"flag" => (
+        "flag",
+        data
+          .to_str()
+          .ok()
+          .and_then(|s| s.parse().ok())
+          .map(Self::Flag),
+      ),
+      b"respawn" => (
+        "respawn",
+        parse_int::<u8>(data).map(|v| Self::Respawn(v.into())),
+      ),
+      _ => {
+        return Ok(Self::Other {
+          com: command.com.clone(),
+          data: command.data.clone(),
+        })
+      }
+    };
+
+    parsed.ok_or_else(|| InvalidCommandData {
+      com,
+      data: command.data.clone(),
+    })
+  }
+}
+
+impl TryFrom<Command> for GameCommand {
+  type Error = InvalidCommandData;
+
+  fn try_from(command: Command) -> Result<Self, Self::Error> {
+    Self::try_from(&command)
+  }
+}
+
+impl From<GameCommand> for Command {
+  fn from(command: GameCommand) -> Self {
+    let (com, data): (&str, BString) = match command {
+      GameCommand::Spectate(target) => ("spectate", target.to_data()),
+      GameCommand::Upgrade(upgrade) => ("upgrade", u8::from(upgrade).to_string().into()),
+      GameCommand::Flag(flag) => ("flag", String::from(flag).into()),
+      GameCommand::Respawn(plane) => ("respawn", u8::from(plane).to_string().into()),
+      GameCommand::Other { com, data } => return Command { com, data },
+    };
+
+    Command {
+      com: com.into(),
+      data,
+    }
+  }
+}
+
+impl From<GameCommand> for crate::ClientPacket {
+  fn from(command: GameCommand) -> Self {
+    Self::Command(command.into())
+  }
+}
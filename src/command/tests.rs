@@ -0,0 +1,266 @@
+use std::convert::TryFrom;
+
+use bstr::BString;
+
+use super::*;
+
+fn command(com: &str, data: &str) -> Command {
+  Command {
+    com: com.into(),
+    data: data.into(),
+  }
+}
+
+fn parse(com: &str, data: &str) -> Result<GameCommand, InvalidCommandData> {
+  GameCommand::try_from(&command(com, data))
+}
+
+#[test]
+fn parse_known_commands() {
+  assert_eq!(
+    parse("spectate", "-3"),
+    Ok(GameCommand::Spectate(SpectateTarget::Force))
+  );
+  assert_eq!(
+    parse("spectate", "-1"),
+    Ok(GameCommand::Spectate(SpectateTarget::Next))
+  );
+  assert_eq!(
+    parse("spectate", "1052"),
+    Ok(GameCommand::Spectate(SpectateTarget::Player(1052)))
+  );
+  assert_eq!(
+    parse("upgrade", "1"),
+    Ok(GameCommand::Upgrade(UpgradeType::Speed))
+  );
+  assert_eq!(parse("flag", "ca"), Ok(GameCommand::Flag(FlagCode::Canada)));
+  assert_eq!(
+    parse("respawn", "5"),
+    Ok(GameCommand::Respawn(PlaneType::Prowler))
+  );
+}
+
+#[test]
+fn unknown_values_are_kept() {
+  assert_eq!(
+    parse("respawn", "9"),
+    Ok(GameCommand::Respawn(PlaneType::Unknown(9)))
+  );
+  assert_eq!(
+    parse("upgrade", "200"),
+    Ok(GameCommand::Upgrade(UpgradeType::Unknown(200)))
+  );
+}
+
+#[test]
+fn invalid_data() {
+  for (com, data) in [
+    ("spectate", "-4"),
+    ("spectate", "70000"),
+    ("spectate", "me"),
+    ("upgrade", "speed"),
+    ("flag", "XX"),
+    ("respawn", ""),
+  ] {
+    let err = parse(com, data).unwrap_err();
+    assert_eq!(err.com, com);
+    assert_eq!(err.data, data);
+  }
+}
+
+#[test]
+fn other_commands() {
+  assert_eq!(
+    parse("drop", ""),
+    Ok(GameCommand::Other {
+      com: "drop".into(),
+      data: "".into()
+    })
+  );
+  // Command names are case-sensitive.
+  assert!(matches!(parse("Flag", "CA"), Ok(GameCommand::Other { .. })));
+}
+
+#[test]
+fn roundtrip() {
+  let commands = [
+    GameCommand::Spectate(SpectateTarget::Prev),
+    GameCommand::Spectate(SpectateTarget::Player(7)),
+    GameCommand::Upgrade(UpgradeType::Missile),
+    GameCommand::Flag(FlagCode::JollyRogers),
+    GameCommand::Respawn(PlaneType::Goliath),
+    GameCommand::Other {
+      com: "su".into(),
+      data: "password".into(),
+    },
+  ];
+
+  for cmd in commands.iter().cloned() {
+    let name = cmd.name().to_vec();
+    let command = Command::from(cmd.clone());
+    assert_eq!(command.com, name);
+    assert_eq!(GameCommand::try_from(&command), Ok(cmd));
+  }
+}
+
+fn lookup(name: &bstr::BStr) -> Option<u16> {
+  match &**name {
+    b"bob" => Some(1),
+    b"bob the builder" => Some(2),
+    b"alice" => Some(3),
+    _ => None,
+  }
+}
+
+fn name_of(id: u16) -> Option<BString> {
+  match id {
+    1 => Some("bob".into()),
+    2 => Some("bob the builder".into()),
+    3 => Some("alice".into()),
+    _ => None,
+  }
+}
+
+#[test]
+fn chat_plain_and_channels() {
+  use crate::ClientPacket;
+
+  match parse_chat("hello world", lookup).unwrap() {
+    ClientPacket::Chat(c) => assert_eq!(c.text, "hello world"),
+    p => panic!("unexpected packet {:?}", p),
+  }
+  match parse_chat("/s hi all", lookup).unwrap() {
+    ClientPacket::Say(c) => assert_eq!(c.text, "hi all"),
+    p => panic!("unexpected packet {:?}", p),
+  }
+  match parse_chat("/t  go go", lookup).unwrap() {
+    ClientPacket::TeamChat(c) => assert_eq!(c.text, "go go"),
+    p => panic!("unexpected packet {:?}", p),
+  }
+  assert_eq!(
+    parse_chat("/t", lookup).unwrap_err(),
+    ChatParseError::MissingArgument("t")
+  );
+}
+
+#[test]
+fn chat_whisper_uses_longest_name() {
+  use crate::ClientPacket;
+
+  match parse_chat("/w bob the builder can we fix it", lookup).unwrap() {
+    ClientPacket::Whisper(w) => {
+      assert_eq!(w.id, 2);
+      assert_eq!(w.text, "can we fix it");
+    }
+    p => panic!("unexpected packet {:?}", p),
+  }
+  match parse_chat("/w bob hi", lookup).unwrap() {
+    ClientPacket::Whisper(w) => {
+      assert_eq!(w.id, 1);
+      assert_eq!(w.text, "hi");
+    }
+    p => panic!("unexpected packet {:?}", p),
+  }
+
+  assert_eq!(
+    parse_chat("/w carol hi", lookup).unwrap_err(),
+    ChatParseError::UnknownPlayer("carol".into())
+  );
+  assert_eq!(
+    parse_chat("/w alice", lookup).unwrap_err(),
+    ChatParseError::MissingArgument("w")
+  );
+}
+
+#[test]
+fn chat_commands() {
+  use crate::ClientPacket;
+
+  let game_command = |line: &str| match parse_chat(line, lookup).unwrap() {
+    ClientPacket::Command(c) => GameCommand::try_from(&c).unwrap(),
+    p => panic!("unexpected packet {:?}", p),
+  };
+
+  assert_eq!(
+    game_command("/spectate"),
+    GameCommand::Spectate(SpectateTarget::Force)
+  );
+  assert_eq!(
+    game_command("/spectate alice"),
+    GameCommand::Spectate(SpectateTarget::Player(3))
+  );
+  assert_eq!(
+    game_command("/flag jolly"),
+    GameCommand::Flag(FlagCode::JollyRogers)
+  );
+  assert_eq!(
+    game_command("/respawn Tornado"),
+    GameCommand::Respawn(PlaneType::Tornado)
+  );
+  assert_eq!(
+    game_command("/respawn 3"),
+    GameCommand::Respawn(PlaneType::Mohawk)
+  );
+  assert_eq!(
+    game_command("/upgrade energy"),
+    GameCommand::Upgrade(UpgradeType::Energy)
+  );
+  assert_eq!(
+    game_command("#spectate -1"),
+    GameCommand::Spectate(SpectateTarget::Next)
+  );
+  assert_eq!(
+    game_command("#drop"),
+    GameCommand::Other {
+      com: "drop".into(),
+      data: "".into()
+    }
+  );
+
+  match parse_chat("/votemute bob", lookup).unwrap() {
+    ClientPacket::VoteMute(v) => assert_eq!(v.id, 1),
+    p => panic!("unexpected packet {:?}", p),
+  }
+
+  assert!(matches!(
+    parse_chat("/flag nowhere", lookup),
+    Err(ChatParseError::InvalidArgument(_))
+  ));
+  assert!(matches!(
+    parse_chat("/respawn jet", lookup),
+    Err(ChatParseError::InvalidArgument(_))
+  ));
+  assert_eq!(
+    parse_chat("/dance", lookup).unwrap_err(),
+    ChatParseError::UnknownCommand("dance".into())
+  );
+}
+
+#[test]
+fn chat_format_roundtrip() {
+  for line in [
+    "hello",
+    "/s hi all",
+    "/t go go",
+    "/w bob the builder hello",
+    "/votemute alice",
+    "/flag CA",
+    "/spectate",
+    "/spectate bob",
+    "/respawn prowler",
+    "/upgrade missile",
+    "#spectate -2",
+    "#su password",
+    "#drop",
+  ] {
+    let packet = parse_chat(line, lookup).unwrap();
+    assert_eq!(format_chat(&packet, name_of).unwrap(), line);
+  }
+
+  let packet = crate::ClientPacket::Whisper(crate::client::Whisper {
+    id: 99,
+    text: "hi".into(),
+  });
+  assert_eq!(format_chat(&packet, name_of), None);
+  assert_eq!(format_chat(&crate::ClientPacket::Ack, name_of), None);
+}
@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+
+use bstr::BString;
+use serde_json::{Map, Value};
+
+use crate::enums::GameType;
+use crate::server::{Login, Login2};
+use crate::Team;
+
+/// Serde serialization declaration for the `config` field of a
+/// [`Login2`][0] packet.
+///
+/// This struct will serialize from/deserialize to the JSON representation
+/// sent by servers implementing ab-protocol.
+///
+/// # Serialization Notes
+/// - All known keys are optional and are omitted from the JSON if they are
+///   not set.
+/// - Any keys that aren't recognized are kept in `extra` so that parsing and
+///   then re-serializing a config doesn't lose anything.
+///
+/// [0]: crate::server::Login2
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct ServerConfig {
+  /// Identifier of the map that the server is running.
+  #[cfg_attr(
+    feature = "serde",
+    serde(default, skip_serializing_if = "Option::is_none")
+  )]
+  pub map_id: Option<String>,
+  /// Settings specific to the game mode.
+  #[cfg_attr(
+    feature = "serde",
+    serde(default, skip_serializing_if = "Option::is_none")
+  )]
+  pub game: Option<GameSettings>,
+  /// CSS colours that the client should use for each team.
+  #[cfg_attr(
+    feature = "serde",
+    serde(default, skip_serializing_if = "BTreeMap::is_empty")
+  )]
+  pub team_colors: BTreeMap<Team, String>,
+  /// Settings for the server-side bots.
+  #[cfg_attr(
+    feature = "serde",
+    serde(default, skip_serializing_if = "Option::is_none")
+  )]
+  pub bots: Option<BotSettings>,
+  /// All other keys within the config.
+  #[cfg_attr(feature = "serde", serde(flatten))]
+  pub extra: Map<String, Value>,
+}
+
+/// Game mode settings within a [`ServerConfig`].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct GameSettings {
+  #[cfg_attr(feature = "serde", serde(rename = "type"))]
+  #[cfg_attr(
+    feature = "serde",
+    serde(default, skip_serializing_if = "Option::is_none")
+  )]
+  pub ty: Option<GameType>,
+  #[cfg_attr(
+    feature = "serde",
+    serde(default, skip_serializing_if = "Option::is_none")
+  )]
+  pub max_players: Option<u32>,
+  /// Time (in milliseconds) that a player has to wait before respawning.
+  #[cfg_attr(
+    feature = "serde",
+    serde(default, skip_serializing_if = "Option::is_none")
+  )]
+  pub respawn_delay: Option<u32>,
+  #[cfg_attr(feature = "serde", serde(flatten))]
+  pub extra: Map<String, Value>,
+}
+
+/// Bot settings within a [`ServerConfig`].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct BotSettings {
+  /// The number of bots that the server will keep in the game.
+  #[cfg_attr(
+    feature = "serde",
+    serde(default, skip_serializing_if = "Option::is_none")
+  )]
+  pub count: Option<u32>,
+  /// Prefix added to the names of all bots.
+  #[cfg_attr(
+    feature = "serde",
+    serde(default, skip_serializing_if = "Option::is_none")
+  )]
+  pub name_prefix: Option<String>,
+  #[cfg_attr(feature = "serde", serde(flatten))]
+  pub extra: Map<String, Value>,
+}
+
+impl Login2 {
+  /// Parse the `config` field of this packet.
+  ///
+  /// An empty config is treated the same as an empty JSON object.
+  pub fn parse_config(&self) -> Result<ServerConfig, serde_json::Error> {
+    if self.config.is_empty() {
+      return Ok(ServerConfig::default());
+    }
+
+    serde_json::from_slice(&self.config)
+  }
+
+  /// Create a `Login2` packet with `config` encoded as its config field and
+  /// no bots.
+  pub fn with_config(login: Login, config: &ServerConfig) -> Self {
+    Self {
+      login,
+      // The config only contains strings, numbers, and JSON values which can
+      // all be serialized.
+      config: BString::from(serde_json::to_vec(config).expect("failed to serialize ServerConfig")),
+      bots: Vec::new(),
+    }
+  }
+}
@@ -0,0 +1,62 @@
+use bstr::BString;
+
+use super::{BTRData, CTFData, SwitchGameSuggestion};
+use crate::enums::ServerCustomType;
+use crate::server::ServerCustom;
+
+/// The decoded `data` field of a [`ServerCustom`] packet.
+///
+/// Use [`ServerCustom::decode_data`] to get one of these from a packet and
+/// [`ServerCustom::btr`], [`ServerCustom::ctf`], or
+/// [`ServerCustom::switch_game_suggestion`] to go the other way.
+#[derive(Clone, Debug)]
+pub enum CustomData {
+  BTR(BTRData),
+  CTF(CTFData),
+  SwitchGameSuggestion(SwitchGameSuggestion),
+  /// The data of a packet with a type that doesn't have a known schema.
+  Unknown(BString),
+}
+
+impl ServerCustom {
+  /// Decode the JSON within `data` according to `ty`.
+  ///
+  /// Packets with a type that has no known schema are returned as
+  /// [`CustomData::Unknown`] without looking at the data. An error is only
+  /// returned if the type is known but the data doesn't match its schema.
+  pub fn decode_data(&self) -> Result<CustomData, serde_json::Error> {
+    Ok(match self.ty {
+      ServerCustomType::BTR => CustomData::BTR(serde_json::from_slice(&self.data)?),
+      ServerCustomType::CTF => CustomData::CTF(serde_json::from_slice(&self.data)?),
+      ServerCustomType::SwitchGameSuggestion => {
+        CustomData::SwitchGameSuggestion(serde_json::from_slice(&self.data)?)
+      }
+      _ => CustomData::Unknown(self.data.clone()),
+    })
+  }
+
+  /// Create a packet that shows the BTR game-end screen.
+  pub fn btr(data: &BTRData) -> Self {
+    Self::encode(ServerCustomType::BTR, data)
+  }
+
+  /// Create a packet that shows the CTF game-end screen.
+  pub fn ctf(data: &CTFData) -> Self {
+    Self::encode(ServerCustomType::CTF, data)
+  }
+
+  /// Create a packet that suggests a different game to the player.
+  pub fn switch_game_suggestion(data: &SwitchGameSuggestion) -> Self {
+    Self::encode(ServerCustomType::SwitchGameSuggestion, data)
+  }
+
+  fn encode<T: serde::Serialize>(ty: ServerCustomType, data: &T) -> Self {
+    Self {
+      ty,
+      // None of the data types contain anything that can fail to serialize.
+      data: serde_json::to_vec(data)
+        .expect("failed to serialize ServerCustom data")
+        .into(),
+    }
+  }
+}
@@ -1,9 +1,20 @@
 //! Data serialization declarations for `ServerCustom`
-//! packets.
+//! packets, `CommandReply` popups, and the `Login2` server config.
 
 mod btr;
+mod config;
 mod ctf;
+mod data;
+mod popup;
+mod switch_game;
 mod utils;
 
+#[cfg(test)]
+mod tests;
+
 pub use self::btr::BTRData;
+pub use self::config::{BotSettings, GameSettings, ServerConfig};
 pub use self::ctf::CTFData;
+pub use self::data::CustomData;
+pub use self::popup::{HelpPopup, Popup, RespawnPopup, UpgradePopup};
+pub use self::switch_game::SwitchGameSuggestion;
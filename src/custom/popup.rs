@@ -0,0 +1,130 @@
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::enums::{CommandReplyType, UpgradeType};
+use crate::server::CommandReply;
+
+/// Serde serialization declaration for the text of a [`CommandReply`][0] that
+/// is shown in a popup.
+///
+/// This will serialize from/deserialize to a JSON object with a `type` field
+/// that determines the rest of the fields within the object. Popups with a
+/// type that isn't known are kept as [`Popup::Other`].
+///
+/// # Popup Format
+/// The original client only requires the text of a popup to be valid JSON,
+/// neither it nor ab-protocol define what that JSON looks like. The
+/// `upgrade`, `respawn` and `help` shapes here are a convention defined by
+/// this crate so that servers and clients using it agree with each other.
+/// Popups sent by other servers will usually end up as [`Popup::Other`].
+///
+/// [0]: crate::server::CommandReply
+#[derive(Clone, Debug, PartialEq)]
+pub enum Popup {
+  Upgrade(UpgradePopup),
+  Respawn(RespawnPopup),
+  Help(HelpPopup),
+  /// Any other JSON value.
+  Other(Value),
+}
+
+/// Popup shown in response to an upgrade command.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UpgradePopup {
+  pub upgrade: UpgradeType,
+  /// The number of unused upgrades that the player has left.
+  pub amount: u8,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub text: Option<String>,
+}
+
+/// Popup shown when a player isn't allowed to respawn yet.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RespawnPopup {
+  /// Time (in seconds) until the player is allowed to respawn.
+  pub delay: u32,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub text: Option<String>,
+}
+
+/// Popup listing help text, e.g. the available commands.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HelpPopup {
+  pub title: String,
+  #[serde(default)]
+  pub lines: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum TaggedRef<'a> {
+  Upgrade(&'a UpgradePopup),
+  Respawn(&'a RespawnPopup),
+  Help(&'a HelpPopup),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Tagged {
+  Upgrade(UpgradePopup),
+  Respawn(RespawnPopup),
+  Help(HelpPopup),
+}
+
+impl Serialize for Popup {
+  fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+    match self {
+      Self::Upgrade(p) => TaggedRef::Upgrade(p).serialize(ser),
+      Self::Respawn(p) => TaggedRef::Respawn(p).serialize(ser),
+      Self::Help(p) => TaggedRef::Help(p).serialize(ser),
+      Self::Other(value) => value.serialize(ser),
+    }
+  }
+}
+
+impl<'de> Deserialize<'de> for Popup {
+  fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+    let value = Value::deserialize(de)?;
+
+    let known = matches!(
+      value.get("type").and_then(Value::as_str),
+      Some("upgrade" | "respawn" | "help")
+    );
+    if !known {
+      return Ok(Self::Other(value));
+    }
+
+    Ok(
+      match Tagged::deserialize(value).map_err(D::Error::custom)? {
+        Tagged::Upgrade(p) => Self::Upgrade(p),
+        Tagged::Respawn(p) => Self::Respawn(p),
+        Tagged::Help(p) => Self::Help(p),
+      },
+    )
+  }
+}
+
+impl CommandReply {
+  /// Create a command reply that shows `popup` on the client.
+  pub fn popup(popup: &Popup) -> Self {
+    Self {
+      ty: CommandReplyType::ShowInPopup,
+      // Popup only contains values that can be represented as JSON.
+      text: serde_json::to_vec(popup)
+        .expect("failed to serialize Popup")
+        .into(),
+    }
+  }
+
+  /// Parse the text of this command reply as a popup.
+  ///
+  /// Returns `None` if the reply is meant to be shown in the console.
+  pub fn parse_popup(&self) -> Result<Option<Popup>, serde_json::Error> {
+    if self.ty == CommandReplyType::ShowInConsole {
+      return Ok(None);
+    }
+
+    serde_json::from_slice(&self.text).map(Some)
+  }
+}
@@ -0,0 +1,22 @@
+/// Serde serialization declaration for
+/// [`SwitchGameSuggestion`][0] [`ServerCustom`][1] data.
+///
+/// This struct will serialize from/deserialize to the JSON
+/// representation used in the `data` field of `ServerCustom`.
+///
+/// # Serialization Notes
+/// - `text` is omitted from the JSON if it is `None`.
+///
+/// [0]: crate::ServerCustomType::SwitchGameSuggestion
+/// [1]: crate::server::ServerCustom
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SwitchGameSuggestion {
+  /// The room identifier of the suggested game (e.g. `eu-ffa1`).
+  #[cfg_attr(feature = "serde", serde(rename = "r"))]
+  pub room: String,
+  /// An optional message to show to the player alongside the suggestion.
+  #[cfg_attr(feature = "serde", serde(rename = "m", default))]
+  #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+  pub text: Option<String>,
+}
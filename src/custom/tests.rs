@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+use super::*;
+use crate::server::ServerCustom;
+use crate::{FlagCode, ServerCustomType};
+
+#[test]
+fn decode_btr() {
+  let packet = ServerCustom {
+    ty: ServerCustomType::BTR,
+    data: r#"{"p":"someone","b":400,"f":87,"k":12,"t":15}"#.into(),
+  };
+
+  match packet.decode_data().unwrap() {
+    CustomData::BTR(data) => {
+      assert_eq!(data.player, "someone");
+      assert_eq!(data.bounty, 400);
+      assert_eq!(data.flag, FlagCode::from(87u16));
+      assert_eq!(data.kills, 12);
+      assert_eq!(data.duration, Duration::from_secs(15));
+    }
+    data => panic!("unexpected data {:?}", data),
+  }
+}
+
+#[test]
+fn ctf_roundtrip() {
+  let packet = ServerCustom::ctf(&CTFData {
+    winner: 2,
+    bounty: 100,
+    duration: Duration::from_secs(13),
+  });
+  assert_eq!(packet.ty, ServerCustomType::CTF);
+
+  match packet.decode_data().unwrap() {
+    CustomData::CTF(data) => {
+      assert_eq!(data.winner, 2);
+      assert_eq!(data.bounty, 100);
+      assert_eq!(data.duration, Duration::from_secs(13));
+    }
+    data => panic!("unexpected data {:?}", data),
+  }
+}
+
+#[test]
+fn switch_game_suggestion_roundtrip() {
+  let packet = ServerCustom::switch_game_suggestion(&SwitchGameSuggestion {
+    room: "eu-ctf1".into(),
+    text: None,
+  });
+  assert_eq!(packet.ty, ServerCustomType::SwitchGameSuggestion);
+  assert_eq!(packet.data, r#"{"r":"eu-ctf1"}"#);
+
+  match packet.decode_data().unwrap() {
+    CustomData::SwitchGameSuggestion(data) => {
+      assert_eq!(data.room, "eu-ctf1");
+      assert_eq!(data.text, None);
+    }
+    data => panic!("unexpected data {:?}", data),
+  }
+}
+
+#[test]
+fn unknown_type_is_not_parsed() {
+  let packet = ServerCustom {
+    ty: ServerCustomType::Unknown(55),
+    data: "not json".into(),
+  };
+
+  match packet.decode_data().unwrap() {
+    CustomData::Unknown(data) => assert_eq!(data, "not json"),
+    data => panic!("unexpected data {:?}", data),
+  }
+}
+
+#[test]
+fn invalid_data_for_known_type() {
+  let packet = ServerCustom {
+    ty: ServerCustomType::CTF,
+    data: r#"{"w":1}"#.into(),
+  };
+
+  assert!(packet.decode_data().is_err());
+}
+
+fn login() -> crate::server::Login {
+  crate::server::Login {
+    success: true,
+    id: 1,
+    team: 1,
+    clock: 0,
+    token: "token".into(),
+    ty: crate::GameType::CTF,
+    room: "ctf1".into(),
+    players: vec![],
+  }
+}
+
+#[test]
+fn server_config_parse() {
+  use crate::server::Login2;
+
+  let packet = Login2 {
+    login: login(),
+    config: r##"{
+      "mapId": "ctf-classic",
+      "game": { "type": 2, "maxPlayers": 80, "ctfExtraSpawns": true },
+      "teamColors": { "1": "#4076E2", "2": "#EA4242" },
+      "bots": { "count": 4, "namePrefix": "[bot] " },
+      "sf": 5500
+    }"##
+      .into(),
+    bots: vec![],
+  };
+
+  let config = packet.parse_config().unwrap();
+  assert_eq!(config.map_id.as_deref(), Some("ctf-classic"));
+
+  let game = config.game.as_ref().unwrap();
+  assert_eq!(game.ty, Some(crate::GameType::CTF));
+  assert_eq!(game.max_players, Some(80));
+  assert_eq!(game.respawn_delay, None);
+  assert_eq!(game.extra["ctfExtraSpawns"], true);
+
+  assert_eq!(config.team_colors[&2], "#EA4242");
+  assert_eq!(config.bots.as_ref().unwrap().count, Some(4));
+  assert_eq!(config.extra["sf"], 5500);
+
+  // Nothing is lost when the config is written back out.
+  let reencoded = Login2::with_config(login(), &config);
+  assert_eq!(reencoded.parse_config().unwrap(), config);
+}
+
+#[test]
+fn server_config_empty() {
+  let packet = crate::server::Login2 {
+    login: login(),
+    config: "".into(),
+    bots: vec![],
+  };
+  assert_eq!(packet.parse_config().unwrap(), ServerConfig::default());
+
+  let packet = crate::server::Login2::with_config(login(), &ServerConfig::default());
+  assert_eq!(packet.config, "{}");
+}
+
+#[test]
+fn popup_roundtrip() {
+  use crate::server::CommandReply;
+  use crate::UpgradeType;
+
+  let popup = Popup::Upgrade(UpgradePopup {
+    upgrade: UpgradeType::Speed,
+    amount: 3,
+    text: None,
+  });
+  let reply = CommandReply::popup(&popup);
+  assert_eq!(reply.ty, crate::CommandReplyType::ShowInPopup);
+  assert_eq!(reply.parse_popup().unwrap(), Some(popup));
+
+  // Popups have to survive being sent over the wire.
+  let bytes = crate::v5::serialize(&reply).unwrap();
+  let decoded: CommandReply = crate::v5::deserialize(&bytes).unwrap();
+  assert_eq!(decoded.text, reply.text);
+}
+
+#[test]
+fn popup_parse() {
+  use crate::server::CommandReply;
+
+  let reply = CommandReply {
+    ty: crate::CommandReplyType::ShowInPopup,
+    text: r#"{"type":"help","title":"Commands","lines":["/flag <code>","/spectate"]}"#.into(),
+  };
+  match reply.parse_popup().unwrap() {
+    Some(Popup::Help(help)) => {
+      assert_eq!(help.title, "Commands");
+      assert_eq!(help.lines, ["/flag <code>", "/spectate"]);
+    }
+    popup => panic!("unexpected popup {:?}", popup),
+  }
+
+  let reply = CommandReply {
+    ty: crate::CommandReplyType::ShowInPopup,
+    text: r#"{"type":"custom","x":1}"#.into(),
+  };
+  assert!(matches!(
+    reply.parse_popup().unwrap(),
+    Some(Popup::Other(_))
+  ));
+
+  let reply = CommandReply {
+    ty: crate::CommandReplyType::ShowInPopup,
+    text: r#"{"type":"respawn"}"#.into(),
+  };
+  assert!(reply.parse_popup().is_err());
+
+  let reply = CommandReply {
+    ty: crate::CommandReplyType::ShowInConsole,
+    text: "not json".into(),
+  };
+  assert_eq!(reply.parse_popup().unwrap(), None);
+}
@@ -0,0 +1,176 @@
+//! Wire format of delta-encoded player updates.
+//!
+//! ```text
+//! u8   packet id (DELTA_UPDATE_PACKET_NO)
+//! u8   epoch of the baseline
+//! u16  player id
+//! u8   mask of the fields that follow
+//! ...  the fields in the mask, in the order of the mask bits
+//! ```
+//!
+//! Fields are encoded the same way as in the v5 `PlayerUpdate` packet. The
+//! clock is either sent as a `u16` offset from the baseline clock or as a
+//! full `u32`.
+
+use super::DELTA_UPDATE_PACKET_NO;
+use crate::server::PlayerUpdate;
+use crate::v5::{
+  AirmashDeserializerV5, AirmashSerializerV5, Error, ErrorExt as _, ErrorKind, Result,
+};
+use crate::{Player, ServerKeyState, Upgrades};
+
+const KEYSTATE: u8 = 1 << 0;
+const UPGRADES: u8 = 1 << 1;
+const POS_X: u8 = 1 << 2;
+const POS_Y: u8 = 1 << 3;
+const ROT: u8 = 1 << 4;
+const SPEED: u8 = 1 << 5;
+const CLOCK_SHORT: u8 = 1 << 6;
+const CLOCK_FULL: u8 = 1 << 7;
+
+/// The fields that must be present when there is no baseline.
+const ALL_FIELDS: u8 = KEYSTATE | UPGRADES | POS_X | POS_Y | ROT | SPEED | CLOCK_FULL;
+
+fn mask(baseline: Option<&PlayerUpdate>, update: &PlayerUpdate) -> u8 {
+  let base = match baseline {
+    Some(base) => base,
+    None => return ALL_FIELDS,
+  };
+
+  let mut mask = 0;
+  let fields = [
+    (KEYSTATE, base.keystate != update.keystate),
+    (UPGRADES, base.upgrades != update.upgrades),
+    (POS_X, base.pos.x != update.pos.x),
+    (POS_Y, base.pos.y != update.pos.y),
+    (ROT, base.rot != update.rot),
+    (SPEED, base.speed != update.speed),
+  ];
+
+  for (bit, changed) in fields.iter().copied() {
+    if changed {
+      mask |= bit;
+    }
+  }
+
+  match update.clock.wrapping_sub(base.clock) {
+    0 => (),
+    delta if delta <= u16::MAX as u32 => mask |= CLOCK_SHORT,
+    _ => mask |= CLOCK_FULL,
+  }
+
+  mask
+}
+
+/// Encode `update` as a delta against `baseline`. The update must already
+/// be quantized.
+pub(super) fn encode(
+  epoch: u8,
+  baseline: Option<&PlayerUpdate>,
+  update: &PlayerUpdate,
+) -> Result<Vec<u8>> {
+  let mask = mask(baseline, update);
+  let mut data = Vec::with_capacity(24);
+  let mut ser = AirmashSerializerV5::new(&mut data);
+
+  ser.serialize_u8(DELTA_UPDATE_PACKET_NO)?;
+  ser.serialize_u8(epoch)?;
+  ser.serialize_u16(update.id)?;
+  ser.serialize_u8(mask)?;
+
+  if mask & CLOCK_SHORT != 0 {
+    let base = baseline.map(|base| base.clock).unwrap_or(0);
+    ser.serialize_u16(update.clock.wrapping_sub(base) as u16)?;
+  }
+  if mask & CLOCK_FULL != 0 {
+    ser.serialize_u32(update.clock)?;
+  }
+  if mask & KEYSTATE != 0 {
+    ser.serialize(&update.keystate)?;
+  }
+  if mask & UPGRADES != 0 {
+    ser.serialize(&update.upgrades)?;
+  }
+  if mask & POS_X != 0 {
+    ser.serialize_coord24(update.pos.x)?;
+  }
+  if mask & POS_Y != 0 {
+    ser.serialize_coord24(update.pos.y)?;
+  }
+  if mask & ROT != 0 {
+    ser.serialize_rot(update.rot)?;
+  }
+  if mask & SPEED != 0 {
+    ser.serialize_vel(update.speed)?;
+  }
+
+  Ok(data)
+}
+
+/// Read the epoch of the baseline that a delta update was encoded against.
+pub(super) fn epoch(data: &[u8]) -> Result<u8> {
+  match data.get(1) {
+    Some(&epoch) => Ok(epoch),
+    None => Err(Error::end_of_buffer(2, data.len()).with_offset(data.len())),
+  }
+}
+
+/// Decode a delta update. `baseline` is called with the player id to get the
+/// state that the update is relative to.
+pub(super) fn decode<F>(data: &[u8], baseline: F) -> Result<PlayerUpdate>
+where
+  F: FnOnce(Player) -> Result<Option<PlayerUpdate>>,
+{
+  let mut de = AirmashDeserializerV5::new(data);
+
+  let _id = de.deserialize_u8()?;
+  let _epoch = de.deserialize_u8()?;
+  let id = de.deserialize_u16()?;
+  let mask = de.deserialize_u8()?;
+
+  let mut update = match baseline(id)? {
+    Some(base) => base,
+    None if mask & ALL_FIELDS == ALL_FIELDS => PlayerUpdate {
+      clock: 0,
+      id,
+      keystate: ServerKeyState::default(),
+      upgrades: Upgrades::default(),
+      pos: [0.0, 0.0].into(),
+      rot: 0.0,
+      speed: [0.0, 0.0].into(),
+    },
+    None => return Err(Error::invalid_value(mask).with_context("mask")),
+  };
+  update.id = id;
+
+  if mask & CLOCK_SHORT != 0 {
+    update.clock = update.clock.wrapping_add(de.deserialize_u16()? as u32);
+  }
+  if mask & CLOCK_FULL != 0 {
+    update.clock = de.deserialize_u32()?;
+  }
+  if mask & KEYSTATE != 0 {
+    update.keystate = de.deserialize()?;
+  }
+  if mask & UPGRADES != 0 {
+    update.upgrades = de.deserialize()?;
+  }
+  if mask & POS_X != 0 {
+    update.pos.x = de.deserialize_coord24()?;
+  }
+  if mask & POS_Y != 0 {
+    update.pos.y = de.deserialize_coord24()?;
+  }
+  if mask & ROT != 0 {
+    update.rot = de.deserialize_rot()?;
+  }
+  if mask & SPEED != 0 {
+    update.speed = de.deserialize_vel()?;
+  }
+
+  if !de.remainder().is_empty() {
+    return Err(Error::new(ErrorKind::UnexpectedDataRemaining).with_offset(de.offset()));
+  }
+
+  Ok(update)
+}
@@ -0,0 +1,308 @@
+//! An experimental extension of protocol-v5 that delta-compresses
+//! [`PlayerUpdate`] packets.
+//!
+//! v5 resends every field of a [`PlayerUpdate`] each time. [`ProtocolDelta`]
+//! instead sends only the fields that differ from a baseline that the client
+//! is known to have. All other packets are encoded exactly as in v5.
+//!
+//! # Baselines
+//! Baselines are established using the existing [`Ack`](ServerPacket::Ack)
+//! packets:
+//!
+//! 1. Every so often the server sends an `Ack`. The state of every player as
+//!    of that `Ack` becomes a candidate baseline on both ends.
+//! 2. The client replies to every `Ack` from the server with an
+//!    [`Ack`](ClientPacket::Ack) of its own.
+//! 3. When the server receives the reply the matching candidate becomes the
+//!    baseline that further updates are encoded against.
+//!
+//! Each delta update records which baseline it was encoded against so
+//! updates that were already in flight when a new baseline was acknowledged
+//! are still decoded correctly. The server inserts the `Ack`s itself (see
+//! [`ProtocolDelta::with_ack_interval`]) but the client application is
+//! responsible for replying to them. Until it does all updates are sent in
+//! full.
+//!
+//! The protocol keeps state for each connection. When it is used through a
+//! [`ProtocolRegistry`](crate::ProtocolRegistry) each accepted connection gets
+//! its own instance via [`Protocol::new_connection`]. This also means that
+//! packets have to be serialized separately for every client, even those
+//! that [`ServerPacket::delivery`] classifies as broadcasts.
+//!
+//! ```
+//! # use airmash_protocol::delta::ProtocolDelta;
+//! # use airmash_protocol::{ClientPacket, Protocol, ServerPacket, server::PlayerUpdate};
+//! # fn main() -> Result<(), airmash_protocol::v5::Error> {
+//! let server = ProtocolDelta::new();
+//! let client = ProtocolDelta::new();
+//!
+//! let mut update = PlayerUpdate {
+//!   clock: 100,
+//!   id: 1,
+//!   keystate: Default::default(),
+//!   upgrades: Default::default(),
+//!   pos: [10.0, 20.0].into(),
+//!   rot: 1.5,
+//!   speed: [0.0, 0.0].into(),
+//! };
+//!
+//! for _ in 0..10 {
+//!   update.clock += 10;
+//!   update.pos.x += 1.0;
+//!
+//!   for frame in server.serialize_server(&update.into())? {
+//!     match client.deserialize_server(&frame)? {
+//!       ServerPacket::PlayerUpdate(decoded) => assert_eq!(decoded.pos.x, update.pos.x),
+//!       ServerPacket::Ack => {
+//!         // Reply to the server so that it can start sending deltas.
+//!         let reply = client.serialize_client(&ClientPacket::Ack)?.next().unwrap();
+//!         server.deserialize_client(&reply)?;
+//!       }
+//!       _ => unreachable!(),
+//!     }
+//!   }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod codec;
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::server::PlayerUpdate;
+use crate::traits::{DynProtocol, Protocol, ServerPacketIterator};
+use crate::v5::{self, Error, ProtocolV5, Result};
+use crate::{ClientPacket, Player, ServerPacket};
+
+/// The packet id used for delta-encoded player updates.
+///
+/// This is not used by any v5 packet.
+pub const DELTA_UPDATE_PACKET_NO: u8 = 208;
+
+/// The maximum number of baselines that the server will wait on at once.
+/// The server stops inserting `Ack`s of its own once this many are pending
+/// and drops the oldest baseline if the application sends more.
+const MAX_PENDING: usize = 16;
+
+type Baseline = HashMap<Player, PlayerUpdate>;
+
+/// Server-side state: what has been sent and what the client has confirmed.
+#[derive(Debug, Default)]
+struct Encoder {
+  /// The latest state of every player that has been sent.
+  latest: Baseline,
+  /// Candidate baselines for the `Ack`s that haven't been replied to yet,
+  /// oldest first, along with the number of `Ack`s sent before each.
+  pending: VecDeque<(u64, Baseline)>,
+  /// The baseline that the client has acknowledged.
+  acked: Baseline,
+  /// The number of acknowledged baselines.
+  epoch: u8,
+  /// The number of `Ack`s that have been sent.
+  marked: u64,
+  /// The number of replies to those `Ack`s.
+  replies: u64,
+  /// The number of player updates sent since the last `Ack`.
+  since_ack: u32,
+}
+
+/// Client-side state: the candidate baselines that the server may use.
+#[derive(Debug)]
+struct Decoder {
+  latest: Baseline,
+  /// Candidate baselines along with their epoch, oldest first. There is
+  /// always at least one.
+  baselines: VecDeque<(u8, Baseline)>,
+  epoch: u8,
+}
+
+impl Default for Decoder {
+  fn default() -> Self {
+    Self {
+      latest: Baseline::new(),
+      baselines: std::iter::once((0, Baseline::new())).collect(),
+      epoch: 0,
+    }
+  }
+}
+
+/// [`Protocol`] implementation for the experimental delta-compressed
+/// protocol. See the [module docs](self) for details.
+#[derive(Debug)]
+pub struct ProtocolDelta {
+  ack_interval: u32,
+  encoder: Mutex<Encoder>,
+  decoder: Mutex<Decoder>,
+}
+
+impl ProtocolDelta {
+  /// The protocol version sent by clients in their
+  /// [`Login`](crate::client::Login) packet to request this protocol.
+  pub const VERSION: u8 = 0x80 | ProtocolV5::VERSION;
+
+  /// The default number of player updates between the `Ack`s inserted by
+  /// the server.
+  pub const DEFAULT_ACK_INTERVAL: u32 = 64;
+
+  pub fn new() -> Self {
+    Self::with_ack_interval(Self::DEFAULT_ACK_INTERVAL)
+  }
+
+  /// Create a protocol which sends an `Ack` after every `interval` player
+  /// updates.
+  ///
+  /// Shorter intervals mean that baselines are closer to the current state
+  /// at the cost of more `Ack` packets.
+  pub fn with_ack_interval(interval: u32) -> Self {
+    Self {
+      ack_interval: interval.max(1),
+      encoder: Mutex::default(),
+      decoder: Mutex::default(),
+    }
+  }
+
+  fn encoder(&self) -> MutexGuard<'_, Encoder> {
+    self.encoder.lock().unwrap_or_else(|e| e.into_inner())
+  }
+
+  fn decoder(&self) -> MutexGuard<'_, Decoder> {
+    self.decoder.lock().unwrap_or_else(|e| e.into_inner())
+  }
+}
+
+impl Default for ProtocolDelta {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Encoder {
+  fn mark(&mut self) {
+    self.pending.push_back((self.marked, self.latest.clone()));
+    self.marked += 1;
+    if self.pending.len() > MAX_PENDING {
+      self.pending.pop_front();
+    }
+    self.since_ack = 0;
+  }
+
+  fn acknowledge(&mut self) {
+    // Replies without a matching `Ack` are ignored.
+    if self.replies == self.marked {
+      return;
+    }
+
+    // The client numbers its baselines the same way so the epoch has to
+    // advance even if the baseline itself was dropped. Without it updates
+    // are sent in full, which the client decodes against any baseline.
+    self.acked = match self.pending.front() {
+      Some(&(ack, _)) if ack == self.replies => self.pending.pop_front().unwrap().1,
+      _ => Baseline::new(),
+    };
+    self.replies += 1;
+    self.epoch = self.epoch.wrapping_add(1);
+  }
+}
+
+impl Decoder {
+  fn mark(&mut self) {
+    self.epoch = self.epoch.wrapping_add(1);
+    self.baselines.push_back((self.epoch, self.latest.clone()));
+  }
+
+  /// Find the baseline for `epoch`, discarding all older ones since the
+  /// server will never use them again.
+  fn baseline(&mut self, epoch: u8) -> Result<&Baseline> {
+    while self.baselines.len() > 1 && self.baselines[0].0 != epoch {
+      self.baselines.pop_front();
+    }
+
+    match self.baselines.front() {
+      Some((e, baseline)) if *e == epoch => Ok(baseline),
+      _ => Err(Error::invalid_value(epoch)),
+    }
+  }
+}
+
+impl Protocol for ProtocolDelta {
+  type SerializeError = Error;
+  type DeserializeError = Error;
+
+  fn version(&self) -> u8 {
+    Self::VERSION
+  }
+
+  fn serialize_client(&self, packet: &ClientPacket) -> Result<ServerPacketIterator> {
+    ProtocolV5.serialize_client(packet)
+  }
+
+  fn serialize_server(&self, packet: &ServerPacket) -> Result<ServerPacketIterator> {
+    let update = match packet {
+      ServerPacket::PlayerUpdate(update) => update.quantized(),
+      ServerPacket::Ack => {
+        self.encoder().mark();
+        return ProtocolV5.serialize_server(packet);
+      }
+      _ => return ProtocolV5.serialize_server(packet),
+    };
+
+    let mut encoder = self.encoder();
+    let baseline = encoder.acked.get(&update.id);
+    let frame = codec::encode(encoder.epoch, baseline, &update)?;
+
+    // The quantized update is exactly what the client decodes so both ends
+    // build their baselines from the same values.
+    encoder.latest.insert(update.id, update);
+    encoder.since_ack += 1;
+
+    let mut frames = vec![frame];
+    if encoder.since_ack >= self.ack_interval && encoder.pending.len() < MAX_PENDING {
+      encoder.mark();
+      frames.push(v5::serialize(&ServerPacket::Ack)?);
+    }
+
+    Ok(Box::new(frames.into_iter()))
+  }
+
+  fn deserialize_client(&self, data: &[u8]) -> Result<ClientPacket> {
+    let packet = ProtocolV5.deserialize_client(data)?;
+
+    if let ClientPacket::Ack = packet {
+      self.encoder().acknowledge();
+    }
+
+    Ok(packet)
+  }
+
+  fn deserialize_server(&self, data: &[u8]) -> Result<ServerPacket> {
+    let mut decoder = self.decoder();
+
+    let packet = match data.first() {
+      Some(&DELTA_UPDATE_PACKET_NO) => {
+        let epoch = codec::epoch(data)?;
+        let update = codec::decode(data, |id| Ok(decoder.baseline(epoch)?.get(&id).copied()))?;
+        ServerPacket::PlayerUpdate(update)
+      }
+      _ => ProtocolV5.deserialize_server(data)?,
+    };
+
+    match &packet {
+      ServerPacket::PlayerUpdate(update) => {
+        decoder.latest.insert(update.id, *update);
+      }
+      ServerPacket::Ack => decoder.mark(),
+      _ => (),
+    }
+
+    Ok(packet)
+  }
+
+  fn new_connection(&self) -> Option<Arc<DynProtocol>> {
+    Some(Arc::new(Self::with_ack_interval(self.ack_interval)))
+  }
+}
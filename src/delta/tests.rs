@@ -0,0 +1,244 @@
+use std::collections::VecDeque;
+
+use super::*;
+use crate::server::{PlayerLeave, PlayerUpdate};
+use crate::types::VectorExt;
+use crate::v5::ErrorKind;
+use crate::{Position, ProtocolRegistry, ServerKeyState, Upgrades};
+
+fn update(id: Player, clock: u32, x: f32) -> PlayerUpdate {
+  PlayerUpdate {
+    clock,
+    id,
+    keystate: ServerKeyState {
+      up: true,
+      ..Default::default()
+    },
+    upgrades: Upgrades::default(),
+    pos: Position::new(x, -100.25),
+    rot: 1.234,
+    speed: Position::new(3.5, 0.0),
+  }
+}
+
+fn assert_same(a: &PlayerUpdate, b: &PlayerUpdate) {
+  assert_eq!(a.clock, b.clock);
+  assert_eq!(a.id, b.id);
+  assert_eq!(a.keystate, b.keystate);
+  assert_eq!(a.upgrades, b.upgrades);
+  assert_eq!(a.pos, b.pos);
+  assert_eq!(a.rot, b.rot);
+  assert_eq!(a.speed, b.speed);
+}
+
+/// Like [`assert_same`] but floats have to match bit for bit.
+fn assert_identical(a: &PlayerUpdate, b: &PlayerUpdate) {
+  let bits = |u: &PlayerUpdate| [u.pos.x, u.pos.y, u.rot, u.speed.x, u.speed.y].map(f32::to_bits);
+
+  assert_same(a, b);
+  assert_eq!(bits(a), bits(b));
+}
+
+fn frames(protocol: &ProtocolDelta, packet: ServerPacket) -> Vec<Vec<u8>> {
+  protocol.serialize_server(&packet).unwrap().collect()
+}
+
+#[test]
+fn updates_roundtrip_with_delayed_acks() {
+  let server = ProtocolDelta::with_ack_interval(4);
+  let client = ProtocolDelta::new();
+
+  // Replies from the client that haven't reached the server yet.
+  let mut in_flight = VecDeque::new();
+  let mut sizes = vec![];
+
+  for i in 0..200u32 {
+    let mut original = update((i % 3) as u16, 1000 + i * 17, i as f32 * 2.7);
+    if i % 10 == 0 {
+      original.keystate.boost = true;
+      original.rot += 0.5;
+    }
+
+    for frame in frames(&server, original.into()) {
+      match client.deserialize_server(&frame).unwrap() {
+        ServerPacket::PlayerUpdate(decoded) => {
+          assert_same(&decoded, &original.quantized());
+          sizes.push(frame.len());
+        }
+        ServerPacket::Ack => {
+          let reply = client.serialize_client(&ClientPacket::Ack).unwrap();
+          in_flight.extend(reply);
+        }
+        other => panic!("unexpected packet {:?}", other),
+      }
+    }
+
+    // The replies take a few updates to reach the server.
+    if in_flight.len() > 2 {
+      let reply = in_flight.pop_front().unwrap();
+      server.deserialize_client(&reply).unwrap();
+    }
+  }
+
+  let v5_len = v5::serialize(&ServerPacket::PlayerUpdate(update(0, 0, 0.0)))
+    .unwrap()
+    .len();
+
+  // Before anything has been acknowledged updates are sent in full.
+  assert!(sizes[0] >= v5_len);
+  // Afterwards the unchanged fields are left out.
+  assert!(sizes[100..].iter().all(|&len| len < v5_len));
+}
+
+#[test]
+fn baselines_reencode_identically() {
+  for i in 0..1000u32 {
+    let mut original = update((i % 7) as u16, i * 331, i as f32 * 13.37 - 6000.0);
+    original.rot = i as f32 * 0.0173;
+    original.speed = Position::new(i as f32 * 0.011 - 5.0, 3.3);
+
+    let frame = codec::encode(0, None, &original).unwrap();
+    let baseline = codec::decode(&frame, |_| Ok(None)).unwrap();
+
+    // Sending the decoded value again has to produce the same bytes and the
+    // same value, otherwise the two ends would drift apart.
+    let reencoded = codec::encode(0, None, &baseline).unwrap();
+    assert_eq!(frame, reencoded);
+    assert_identical(&codec::decode(&reencoded, |_| Ok(None)).unwrap(), &baseline);
+    // The server keeps the quantized update as its baseline.
+    assert_identical(&original.quantized(), &baseline);
+
+    // Nothing differs from the baseline so only the header is sent.
+    let delta = codec::encode(0, Some(&baseline), &baseline).unwrap();
+    assert_eq!(delta.len(), 5);
+  }
+}
+
+#[test]
+fn server_and_client_baselines_match() {
+  let server = ProtocolDelta::with_ack_interval(3);
+  let client = ProtocolDelta::new();
+
+  for i in 0..100u32 {
+    let mut original = update((i % 4) as u16, 500 + i * 7, i as f32 * 0.123 + 0.001);
+    original.rot = 0.1 + i as f32 * 0.031;
+
+    for frame in frames(&server, original.into()) {
+      if let ServerPacket::Ack = client.deserialize_server(&frame).unwrap() {
+        for reply in client.serialize_client(&ClientPacket::Ack).unwrap() {
+          server.deserialize_client(&reply).unwrap();
+        }
+      }
+    }
+  }
+
+  let encoder = server.encoder();
+  let decoder = client.decoder();
+  assert_eq!(encoder.latest.len(), decoder.latest.len());
+  for (id, sent) in &encoder.latest {
+    assert_identical(sent, &decoder.latest[id]);
+  }
+}
+
+#[test]
+fn unanswered_acks_are_bounded() {
+  let server = ProtocolDelta::with_ack_interval(1000);
+  let client = ProtocolDelta::new();
+  let mut replies = VecDeque::new();
+
+  let send = |packet: ServerPacket, replies: &mut VecDeque<Vec<u8>>| {
+    let mut decoded = vec![];
+    for frame in frames(&server, packet) {
+      match client.deserialize_server(&frame).unwrap() {
+        ServerPacket::Ack => replies.extend(client.serialize_client(&ClientPacket::Ack).unwrap()),
+        packet => decoded.push(packet),
+      }
+    }
+    decoded
+  };
+
+  // The application sends its own `Ack`s but the replies are held back.
+  for i in 0..100u32 {
+    send(update(1, i * 10, i as f32).into(), &mut replies);
+    send(ServerPacket::Ack, &mut replies);
+  }
+  assert!(server.encoder().pending.len() <= MAX_PENDING);
+
+  // Once the replies arrive updates still decode correctly, even those that
+  // were encoded against a baseline that the server had to drop.
+  for i in 100..300u32 {
+    let original = update(1, i * 10, i as f32 * 1.5);
+    match &send(original.into(), &mut replies)[..] {
+      [ServerPacket::PlayerUpdate(decoded)] => assert_same(decoded, &original.quantized()),
+      packets => panic!("unexpected packets {:?}", packets),
+    }
+
+    if let Some(reply) = replies.pop_front() {
+      server.deserialize_client(&reply).unwrap();
+    }
+  }
+
+  assert!(replies.is_empty());
+  assert!(server.encoder().pending.is_empty());
+}
+
+#[test]
+fn other_packets_match_v5() {
+  let protocol = ProtocolDelta::new();
+  let packet = ServerPacket::PlayerLeave(PlayerLeave { id: 7 });
+
+  assert_eq!(
+    frames(&protocol, packet.clone()),
+    [v5::serialize(&packet).unwrap()]
+  );
+  assert_eq!(
+    protocol
+      .serialize_client(&ClientPacket::Ack)
+      .unwrap()
+      .collect::<Vec<_>>(),
+    [v5::serialize(&ClientPacket::Ack).unwrap()]
+  );
+}
+
+#[test]
+fn unknown_epoch_is_an_error() {
+  let server = ProtocolDelta::with_ack_interval(1);
+  let client = ProtocolDelta::new();
+
+  // The server thinks that the client has acknowledged a baseline that the
+  // client never received.
+  frames(&server, update(1, 10, 0.0).into());
+  server
+    .deserialize_client(&v5::serialize(&ClientPacket::Ack).unwrap())
+    .unwrap();
+
+  let frame = frames(&server, update(1, 20, 5.0).into()).remove(0);
+  let err = client.deserialize_server(&frame).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::InvalidEnumValue);
+  assert_eq!(err.invalid_enum_value(), Some(1));
+}
+
+#[test]
+fn registry_creates_state_per_connection() {
+  let mut registry = ProtocolRegistry::new();
+  registry.register(ProtocolDelta::new());
+
+  let login = v5::serialize(&ClientPacket::Login(crate::client::Login {
+    protocol: ProtocolDelta::VERSION,
+    name: "test".into(),
+    session: "none".into(),
+    horizon_x: 0,
+    horizon_y: 0,
+    flag: "UN".into(),
+  }))
+  .unwrap();
+
+  let accept = || match registry.negotiate(&login).unwrap() {
+    crate::Handshake::Accepted(protocol) => protocol,
+    other => panic!("expected protocol to be accepted, got {:?}", other),
+  };
+
+  let (a, b) = (accept(), accept());
+  assert_eq!(a.version(), ProtocolDelta::VERSION);
+  assert!(!Arc::ptr_eq(&a, &b));
+}
@@ -12,9 +12,9 @@ macro_rules! decl_serde_v5 {
     enum $name:ident ;
   } => {
     impl crate::v5::SerializeV5 for $name {
-      fn serialize<'ser>(
+      fn serialize<S: crate::v5::Sink + ?Sized>(
         &self,
-        ser: &mut crate::v5::AirmashSerializerV5<'ser>
+        ser: &mut crate::v5::AirmashSerializerV5<S>
       ) -> ::std::result::Result<(), crate::v5::Error> {
         ser.serialize(&<$basety>::from(*self))
       }
@@ -24,11 +24,16 @@ macro_rules! decl_serde_v5 {
       fn deserialize(
         de: &mut crate::v5::AirmashDeserializerV5<'de>
       ) -> ::std::result::Result<Self, crate::v5::Error> {
-        use ::std::convert::TryFrom;
-
+        let offset = de.offset();
         let val: $basety = de.deserialize()?;
-        Self::try_from(val)
-          .map_err(|_| crate::v5::Error::new(crate::v5::ErrorKind::InvalidEnumValue))
+
+        match Self::from_known_value(val) {
+          Some(known) => Ok(known),
+          None if de.options().rejects_unknown_enums() => {
+            Err(crate::v5::Error::invalid_value(val).with_offset(offset))
+          }
+          None => Ok(Self::from(val)),
+        }
       }
     }
   }
@@ -116,6 +121,13 @@ macro_rules! decl_enum_utils {
         $elem,
       )*
 
+      /// A value that doesn't correspond to any of the known variants.
+      ///
+      /// The raw value is preserved so converting it back (or serializing it)
+      /// results in the same value that was originally received. v5
+      /// deserialization produces this variant unless
+      /// [`reject_unknown_enums`](crate::v5::DeserializeOptions::reject_unknown_enums)
+      /// is enabled.
       Unknown(enum_basetype!($($basety)?))
     }
   };
@@ -206,6 +218,23 @@ macro_rules! decl_enum {
           }
         }
 
+        impl $name {
+          /// Convert a raw value, returning `None` if it would end up in the
+          /// `Unknown` variant.
+          #[allow(unused_variables, unreachable_patterns)]
+          pub(crate) fn from_known_value(v: BaseTy) -> Option<Self> {
+            match v {
+              $( $value => Some(Self::$elem), )*
+              v => decl_enum_utils! {
+                match $( catchall($catchall) )? {
+                  catchall => { $( Some(Self::$catchall) )? },
+                  default  => None,
+                }
+              }
+            }
+          }
+        }
+
         impl From<$name> for BaseTy {
           fn from(v: $name) -> Self {
             match v {
@@ -233,7 +262,6 @@ macro_rules! decl_enum {
 
         type BaseTy = enum_basetype!($($basety)?);
 
-        const NAME: &'static str = stringify!($name);
         const VARIANTS: &'static [&'static str] = &[
           $( stringify!($elem), )*
         ];
@@ -20,7 +20,10 @@ decl_enum! {
     /// Show a popup with the message.
     ///
     /// Note that the original client will fail to show any message that is not
-    /// valid JSON if shown in a popup.
+    /// valid JSON if shown in a popup. Serializing such a reply with the v5
+    /// protocol results in an [`InvalidPopup`] error.
+    ///
+    /// [`InvalidPopup`]: crate::v5::ErrorKind::InvalidPopup
     ShowInPopup = 1,
   }
 
@@ -39,8 +42,8 @@ decl_enum! {
   ///
   /// These are all server errors that the vanilla AIRMASH client (and the
   /// current STARMASH client) understands. [Ab-server][ab-server] has some
-  /// additional custom error types for its sync protocols but this crate
-  /// doesn't handle those packet types at the moment.
+  /// additional custom error types, those are available as associated
+  /// constants when the `ab-server` feature is enabled.
   ///
   /// [ab-server]: https://github.com/wight-airmash/ab-protocol
   pub enum ErrorType {
@@ -66,7 +69,10 @@ decl_enum! {
   }
 
   /// This is used to control whether the firewall exists in BTR.
-  ##[catchall = Present]
+  ///
+  /// The client treats any status other than `Removed` as meaning that the
+  /// firewall is present. Use [`is_present`](FirewallStatus::is_present) to
+  /// check for that instead of matching on `Present`.
   pub enum FirewallStatus {
     /// If this status is sent then the client will remove the ring of fire.
     Removed = 0,
@@ -262,6 +268,31 @@ impl ErrorType {
   pub const IncorrectProtocolLevel: Self = Self::IncorrectProtocol;
 }
 
+#[cfg(feature = "ab-server")]
+#[allow(non_upper_case_globals)]
+impl ErrorType {
+  /// Ab-server only. The player tried to respawn again too soon.
+  pub const RespawnThrottled: Self = Self::Unknown(32);
+  /// Ab-server only. The player tried to spectate again too soon.
+  pub const SpectateThrottled: Self = Self::Unknown(33);
+  /// Ab-server only. The sync service failed to authenticate.
+  pub const SyncAuthFailed: Self = Self::Unknown(200);
+}
+
+#[cfg(feature = "ab-server")]
+#[allow(non_upper_case_globals)]
+impl ServerCustomType {
+  /// Ab-server only. Triggers the game-end screen in FFA.
+  pub const FFA: Self = Self::Unknown(3);
+}
+
+impl FirewallStatus {
+  /// Whether the client will show the firewall for this status.
+  pub fn is_present(&self) -> bool {
+    !matches!(self, Self::Removed)
+  }
+}
+
 impl MobType {
   pub fn is_missile(&self) -> bool {
     use self::MobType::*;
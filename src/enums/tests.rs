@@ -21,7 +21,7 @@ mod serde {
 
   fn de<'de, T: serde::Deserialize<'de>>(data: &'de str) -> T {
     serde_json::from_str(data) //
-      .expect(&format!("Failed to deserialize from `{}`", data))
+      .unwrap_or_else(|_| panic!("Failed to deserialize from `{}`", data))
   }
 
   roundtrip_planetype_test!(roundtrip_predator => Predator);
@@ -36,7 +36,7 @@ mod serde {
 
     fn de(data: &str) -> PlaneType {
       serde_json::from_str(data) //
-        .expect(&format!("Failed to deserialize planetype from `{}`", data))
+        .unwrap_or_else(|_| panic!("Failed to deserialize planetype from `{}`", data))
     }
 
     // raw numerical values should work
@@ -74,10 +74,13 @@ mod serde {
   fn firewall_status_test() {
     use self::FirewallStatus::*;
 
-    // the catchall case should work as expectd
+    // unknown statuses are kept but still count as present
     assert_eq!(Removed, de("0"));
-    assert_eq!(Present, de("2"));
-    assert_eq!(Present, de("255"));
+    assert_eq!(Present, de("1"));
+    assert_eq!(Unknown(2), de("2"));
+    assert_eq!(Unknown(255), de("255"));
+    assert!(de::<FirewallStatus>("2").is_present());
+    assert!(!de::<FirewallStatus>("0").is_present());
   }
 
   #[test]
@@ -86,3 +89,128 @@ mod serde {
     de::<FirewallStatus>("256");
   }
 }
+
+mod v5 {
+  use crate::v5::{deserialize, serialize, DeserializeOptions, ErrorKind};
+  use crate::*;
+
+  const REJECT: DeserializeOptions = DeserializeOptions::strict().reject_unknown_enums(true);
+
+  /// Check that every possible encoded value of the enum decodes and then
+  /// re-encodes to the exact same bytes, and that the listed values end up in
+  /// the `Unknown` variant by default and are rejected when
+  /// `reject_unknown_enums` is set.
+  macro_rules! roundtrip_test {
+    ($test:ident => $enum:ident [$( $unknown:expr ),*]) => {
+      #[test]
+      fn $test() {
+        for value in 0..=u8::MAX {
+          let bytes = [value];
+          let decoded: $enum = deserialize(&bytes).unwrap();
+          assert_eq!(serialize(&decoded).unwrap(), bytes, "{:?}", decoded);
+        }
+
+        $(
+          let decoded: $enum = deserialize(&[$unknown]).unwrap();
+          assert_eq!(decoded, $enum::Unknown($unknown));
+
+          let err = REJECT.deserialize::<$enum>(&[$unknown]).unwrap_err();
+          assert_eq!(err.kind(), ErrorKind::InvalidEnumValue);
+          assert_eq!(err.invalid_enum_value(), Some($unknown));
+          assert_eq!(err.offset(), Some(0));
+        )*
+      }
+    };
+  }
+
+  roundtrip_test!(command_reply_type => CommandReplyType [2, 255]);
+  roundtrip_test!(despawn_type => DespawnType [2, 255]);
+  roundtrip_test!(error_type => ErrorType [0, 14, 101]);
+  roundtrip_test!(firewall_status => FirewallStatus [2, 255]);
+  roundtrip_test!(flag_update_type => FlagUpdateType [0, 3]);
+  roundtrip_test!(game_type => GameType [0, 4]);
+  roundtrip_test!(key_code => KeyCode [0, 10]);
+  roundtrip_test!(leave_horizon_type => LeaveHorizonType [2, 255]);
+  roundtrip_test!(mob_type => MobType [0, 42]);
+  roundtrip_test!(plane_type => PlaneType [0, 9]);
+  roundtrip_test!(player_level_type => PlayerLevelType [2, 255]);
+  roundtrip_test!(player_status => PlayerStatus [2, 255]);
+  roundtrip_test!(powerup_type => PowerupType [0, 3]);
+  roundtrip_test!(server_custom_type => ServerCustomType [0, 200]);
+  roundtrip_test!(server_message_type => ServerMessageType [0, 16]);
+  roundtrip_test!(upgrade_type => UpgradeType [5, 255]);
+
+  #[test]
+  fn firewall_status_roundtrips_within_packets() {
+    use crate::server::GameFirewall;
+
+    let packet = ServerPacket::GameFirewall(GameFirewall {
+      ty: 1,
+      status: FirewallStatus::Unknown(2),
+      pos: Vector2::new(0.0, 0.0),
+      radius: 100.0,
+      speed: 1.0,
+    });
+
+    let bytes = serialize(&packet).unwrap();
+    let decoded: ServerPacket = deserialize(&bytes).unwrap();
+    assert_eq!(serialize(&decoded).unwrap(), bytes);
+
+    match decoded {
+      ServerPacket::GameFirewall(p) => {
+        assert_eq!(p.status, FirewallStatus::Unknown(2));
+        assert!(p.status.is_present());
+      }
+      packet => panic!("unexpected packet {:?}", packet),
+    }
+  }
+
+  #[test]
+  fn flag_code() {
+    for value in (0..=u16::MAX).step_by(7).chain([0, 1, 200, u16::MAX]) {
+      let bytes = value.to_le_bytes();
+      let decoded: FlagCode = deserialize(&bytes).unwrap();
+      assert_eq!(serialize(&decoded).unwrap(), bytes, "{:?}", decoded);
+    }
+
+    assert_eq!(
+      deserialize::<FlagCode>(&[0, 1]).unwrap(),
+      FlagCode::Unknown(256)
+    );
+    assert!(REJECT.deserialize::<FlagCode>(&[0, 1]).is_err());
+  }
+
+  #[test]
+  fn unknown_values_roundtrip_within_packets() {
+    use crate::server::PlayerNew;
+
+    let packet = ServerPacket::PlayerNew(PlayerNew {
+      id: 5,
+      status: PlayerStatus::Unknown(7),
+      name: "name".into(),
+      ty: PlaneType::Unknown(9),
+      team: 5,
+      pos: Vector2::new(0.0, 0.0),
+      rot: 0.0,
+      flag: FlagCode::Unknown(1000),
+      upgrades: Default::default(),
+    });
+
+    let bytes = serialize(&packet).unwrap();
+    let err = REJECT.deserialize::<ServerPacket>(&bytes).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidEnumValue);
+    assert_eq!(err.context(), ["status", "PlayerNew", "ServerPacket"]);
+
+    let decoded: ServerPacket = deserialize(&bytes).unwrap();
+    assert_eq!(serialize(&decoded).unwrap(), bytes);
+
+    match decoded {
+      ServerPacket::PlayerNew(p) => {
+        assert_eq!(p.status, PlayerStatus::Unknown(7));
+        assert_eq!(p.ty, PlaneType::Unknown(9));
+        assert_eq!(p.flag, FlagCode::Unknown(1000));
+      }
+      packet => panic!("unexpected packet {:?}", packet),
+    }
+  }
+}
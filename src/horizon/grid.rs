@@ -0,0 +1,114 @@
+use super::bounds;
+use crate::{Distance, Position};
+
+/// A uniform grid of buckets covering the whole map.
+///
+/// Items are bucketed by position so that finding everything within a
+/// rectangle only has to look at the cells that overlap it. Positions outside
+/// the map bounds are stored in the nearest edge cell.
+#[derive(Clone, Debug)]
+pub struct SpatialGrid<T> {
+  cell_size: Distance,
+  cols: usize,
+  rows: usize,
+  cells: Vec<Vec<(T, Position)>>,
+}
+
+impl<T: Copy + Eq> SpatialGrid<T> {
+  /// Create an empty grid with square cells of the given size.
+  ///
+  /// # Panics
+  /// Panics if `cell_size` is not positive.
+  pub fn new(cell_size: Distance) -> Self {
+    assert!(cell_size > 0.0, "cell size must be positive");
+
+    let cols = ((bounds::MAX_X - bounds::MIN_X) / cell_size)
+      .ceil()
+      .max(1.0) as usize;
+    let rows = ((bounds::MAX_Y - bounds::MIN_Y) / cell_size)
+      .ceil()
+      .max(1.0) as usize;
+
+    Self {
+      cell_size,
+      cols,
+      rows,
+      cells: vec![Vec::new(); cols * rows],
+    }
+  }
+
+  pub fn cell_size(&self) -> Distance {
+    self.cell_size
+  }
+
+  fn coords(&self, pos: Position) -> (usize, usize) {
+    let x = ((pos.x - bounds::MIN_X) / self.cell_size).max(0.0) as usize;
+    let y = ((pos.y - bounds::MIN_Y) / self.cell_size).max(0.0) as usize;
+
+    (x.min(self.cols - 1), y.min(self.rows - 1))
+  }
+
+  fn index(&self, pos: Position) -> usize {
+    let (x, y) = self.coords(pos);
+    y * self.cols + x
+  }
+
+  /// Add an item at `pos`.
+  ///
+  /// This does not check whether the item is already present.
+  pub fn insert(&mut self, item: T, pos: Position) {
+    let idx = self.index(pos);
+    self.cells[idx].push((item, pos));
+  }
+
+  /// Remove an item that was last placed at `pos`. Returns whether the item
+  /// was found.
+  pub fn remove(&mut self, item: T, pos: Position) -> bool {
+    let idx = self.index(pos);
+    let cell = &mut self.cells[idx];
+
+    match cell.iter().position(|(x, _)| *x == item) {
+      Some(i) => {
+        cell.swap_remove(i);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Move an item from `old` to `new`. If the item wasn't present then it is
+  /// inserted.
+  pub fn relocate(&mut self, item: T, old: Position, new: Position) {
+    let (from, to) = (self.index(old), self.index(new));
+
+    if from == to {
+      if let Some(entry) = self.cells[from].iter_mut().find(|(x, _)| *x == item) {
+        entry.1 = new;
+        return;
+      }
+    } else {
+      self.remove(item, old);
+    }
+
+    self.cells[to].push((item, new));
+  }
+
+  /// Iterate over all items within the rectangle from `min` to `max`
+  /// (inclusive).
+  pub fn query(&self, min: Position, max: Position) -> impl Iterator<Item = (T, Position)> + '_ {
+    let (x0, y0) = self.coords(min);
+    let (x1, y1) = self.coords(max);
+
+    (y0..=y1)
+      .flat_map(move |y| (x0..=x1).map(move |x| y * self.cols + x))
+      .flat_map(move |idx| self.cells[idx].iter().copied())
+      .filter(move |(_, pos)| pos.x >= min.x && pos.x <= max.x && pos.y >= min.y && pos.y <= max.y)
+  }
+
+  /// Remove all items from the grid.
+  pub fn clear(&mut self) {
+    for cell in &mut self.cells {
+      cell.clear();
+    }
+  }
+}
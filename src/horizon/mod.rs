@@ -0,0 +1,378 @@
+//! Server-side interest management.
+//!
+//! Servers don't send every update to every client. Each client has a
+//! horizon - a rectangle centred on its own plane, or on the player it is
+//! spectating - and only hears about players and mobs within it. Once
+//! something it could see moves out of the horizon the client is sent an
+//! [`EventLeaveHorizon`] so that it stops drawing it.
+//!
+//! [`HorizonTracker`] keeps the positions of all entities in a
+//! [`SpatialGrid`] so that working out what each client can see doesn't
+//! require checking every entity against every client.
+//!
+//! ```
+//! # use airmash_protocol::horizon::HorizonTracker;
+//! # use airmash_protocol::{ServerPacket, server::PlayerUpdate};
+//! # fn update(id: u16, x: f32, y: f32) -> ServerPacket {
+//! #   ServerPacket::PlayerUpdate(PlayerUpdate {
+//! #     clock: 0,
+//! #     id,
+//! #     keystate: Default::default(),
+//! #     upgrades: Default::default(),
+//! #     pos: [x, y].into(),
+//! #     rot: 0.0,
+//! #     speed: [0.0, 0.0].into(),
+//! #   })
+//! # }
+//! let mut tracker = HorizonTracker::new();
+//! tracker.add_client(1, 1500, 1000);
+//! tracker.add_client(2, 1500, 1000);
+//!
+//! tracker.observe(&update(1, 0.0, 0.0));
+//! tracker.observe(&update(2, 5000.0, 0.0));
+//!
+//! // Player 2 is too far away for player 1 to see.
+//! assert_eq!(tracker.recipients(&update(2, 5000.0, 0.0)), [2]);
+//!
+//! // Once player 2 moves closer both clients get its updates.
+//! let packet = update(2, 1000.0, 0.0);
+//! assert_eq!(tracker.recipients(&packet), [1, 2]);
+//! tracker.observe(&packet);
+//! assert!(tracker.update().is_empty());
+//!
+//! // When it moves away again each player is told that the other left its
+//! // horizon.
+//! tracker.observe(&update(2, 5000.0, 0.0));
+//! let events = tracker.update();
+//! assert_eq!(events.len(), 2);
+//! assert_eq!((events[0].0, events[0].1.id), (1, 2));
+//! assert_eq!((events[1].0, events[1].1.id), (2, 1));
+//! ```
+
+mod grid;
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::{HashMap, HashSet};
+
+pub use self::grid::SpatialGrid;
+use crate::server::EventLeaveHorizon;
+use crate::{Distance, LeaveHorizonType, Mob, Player, Position, ServerPacket};
+
+/// The bounds of the full map.
+///
+/// Unlike [`physics::bounds`](crate::physics::bounds) these cover the whole
+/// map, including the strip along the edge that planes can't enter.
+pub mod bounds {
+  use crate::Distance;
+
+  pub const MIN_X: Distance = -16384.0;
+  pub const MAX_X: Distance = 16384.0;
+  pub const MIN_Y: Distance = -8192.0;
+  pub const MAX_Y: Distance = 8192.0;
+}
+
+/// The cell size used by [`HorizonTracker::new`].
+pub const DEFAULT_CELL_SIZE: Distance = 1024.0;
+
+/// Something that can enter or leave a client's horizon.
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Entity {
+  Player(Player),
+  Mob(Mob),
+}
+
+impl Entity {
+  /// The packet telling a client that this entity has left its horizon.
+  pub fn leave_event(self) -> EventLeaveHorizon {
+    match self {
+      Self::Player(id) => EventLeaveHorizon {
+        ty: LeaveHorizonType::Player,
+        id,
+      },
+      Self::Mob(id) => EventLeaveHorizon {
+        ty: LeaveHorizonType::Mob,
+        id,
+      },
+    }
+  }
+}
+
+#[derive(Clone, Debug)]
+struct Client {
+  horizon_x: Distance,
+  horizon_y: Distance,
+  focus: Player,
+  visible: HashSet<Entity>,
+}
+
+/// Tracks entity positions and what each client is able to see.
+///
+/// Clients are identified by the id of their player. Entity positions are
+/// usually fed in by passing every outgoing packet to [`observe`]. After all
+/// packets for a frame have been observed [`update`] works out which entities
+/// have left each client's horizon.
+///
+/// [`observe`]: HorizonTracker::observe
+/// [`update`]: HorizonTracker::update
+#[derive(Clone, Debug)]
+pub struct HorizonTracker {
+  grid: SpatialGrid<Entity>,
+  positions: HashMap<Entity, Position>,
+  clients: HashMap<Player, Client>,
+}
+
+impl HorizonTracker {
+  pub fn new() -> Self {
+    Self::with_cell_size(DEFAULT_CELL_SIZE)
+  }
+
+  /// Create a tracker whose spatial grid uses cells of the given size.
+  ///
+  /// Cells around the size of a typical horizon work best.
+  pub fn with_cell_size(cell_size: Distance) -> Self {
+    Self {
+      grid: SpatialGrid::new(cell_size),
+      positions: HashMap::new(),
+      clients: HashMap::new(),
+    }
+  }
+
+  /// Set the position of an entity, adding it if it isn't already tracked.
+  pub fn set_position(&mut self, entity: Entity, pos: Position) {
+    match self.positions.insert(entity, pos) {
+      Some(old) => self.grid.relocate(entity, old, pos),
+      None => self.grid.insert(entity, pos),
+    }
+  }
+
+  /// Stop tracking an entity.
+  ///
+  /// No [`EventLeaveHorizon`] is generated for removed entities since the
+  /// client is expected to be told about them through some other packet
+  /// (e.g. [`PlayerLeave`](crate::server::PlayerLeave) or
+  /// [`MobDespawn`](crate::server::MobDespawn)).
+  pub fn remove(&mut self, entity: Entity) -> Option<Position> {
+    let pos = self.positions.remove(&entity)?;
+    self.grid.remove(entity, pos);
+
+    for client in self.clients.values_mut() {
+      client.visible.remove(&entity);
+    }
+
+    Some(pos)
+  }
+
+  pub fn position(&self, entity: Entity) -> Option<Position> {
+    self.positions.get(&entity).copied()
+  }
+
+  /// Start tracking what a client can see.
+  ///
+  /// `horizon_x` and `horizon_y` are the distances from the centre of the
+  /// view to its edges, as sent in the client's
+  /// [`Login`](crate::client::Login) packet.
+  pub fn add_client(&mut self, id: Player, horizon_x: u16, horizon_y: u16) {
+    self.clients.insert(
+      id,
+      Client {
+        horizon_x: horizon_x.into(),
+        horizon_y: horizon_y.into(),
+        focus: id,
+        visible: HashSet::new(),
+      },
+    );
+  }
+
+  /// Change the size of a client's horizon, e.g. in response to a
+  /// [`Horizon`](crate::client::Horizon) packet. Returns `false` if the
+  /// client isn't being tracked.
+  pub fn set_horizon(&mut self, id: Player, horizon_x: u16, horizon_y: u16) -> bool {
+    match self.clients.get_mut(&id) {
+      Some(client) => {
+        client.horizon_x = horizon_x.into();
+        client.horizon_y = horizon_y.into();
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Centre a client's horizon on another player. Pass the client's own id
+  /// to stop spectating. Returns `false` if the client isn't being tracked.
+  pub fn spectate(&mut self, id: Player, target: Player) -> bool {
+    match self.clients.get_mut(&id) {
+      Some(client) => {
+        client.focus = target;
+        true
+      }
+      None => false,
+    }
+  }
+
+  pub fn remove_client(&mut self, id: Player) -> bool {
+    self.clients.remove(&id).is_some()
+  }
+
+  /// The ids of all tracked clients, in ascending order.
+  pub fn clients(&self) -> Vec<Player> {
+    let mut clients: Vec<_> = self.clients.keys().copied().collect();
+    clients.sort_unstable();
+    clients
+  }
+
+  /// The entities that were within a client's horizon as of the last call to
+  /// [`update`](HorizonTracker::update).
+  pub fn visible(&self, id: Player) -> impl Iterator<Item = Entity> + '_ {
+    self
+      .clients
+      .get(&id)
+      .into_iter()
+      .flat_map(|client| client.visible.iter().copied())
+  }
+
+  /// Whether an entity is currently within a client's horizon.
+  ///
+  /// A client can always see its own plane and the plane it is spectating.
+  pub fn can_see(&self, id: Player, entity: Entity) -> bool {
+    match (self.clients.get(&id), self.position(entity)) {
+      (Some(client), Some(pos)) => self.sees(id, client, entity, pos),
+      _ => false,
+    }
+  }
+
+  fn view(&self, client: &Client) -> Option<(Position, Position)> {
+    let centre = self.position(Entity::Player(client.focus))?;
+    Some(view_rect(client, centre))
+  }
+
+  fn sees(&self, id: Player, client: &Client, entity: Entity, pos: Position) -> bool {
+    if entity == Entity::Player(id) || entity == Entity::Player(client.focus) {
+      return true;
+    }
+
+    match self.view(client) {
+      Some((min, max)) => pos.x >= min.x && pos.x <= max.x && pos.y >= min.y && pos.y <= max.y,
+      None => false,
+    }
+  }
+
+  /// Update entity positions from an outgoing packet.
+  ///
+  /// Packets that don't carry an entity position are ignored.
+  pub fn observe(&mut self, packet: &ServerPacket) {
+    use self::ServerPacket::*;
+
+    match packet {
+      PlayerNew(p) => self.set_position(Entity::Player(p.id), p.pos),
+      PlayerUpdate(p) => self.set_position(Entity::Player(p.id), p.pos),
+      PlayerRespawn(p) => self.set_position(Entity::Player(p.id), p.pos),
+      EventBoost(p) => self.set_position(Entity::Player(p.id), p.pos),
+      EventBounce(p) => self.set_position(Entity::Player(p.id), p.pos),
+      PlayerLeave(p) => {
+        self.remove(Entity::Player(p.id));
+      }
+      MobUpdate(p) => self.set_position(Entity::Mob(p.id), p.pos),
+      MobUpdate2(p) => self.set_position(Entity::Mob(p.update.id), p.update.pos),
+      MobUpdateStationary(p) => self.set_position(Entity::Mob(p.id), p.pos),
+      MobDespawn(p) => {
+        self.remove(Entity::Mob(p.id));
+      }
+      MobDespawnCoords(p) => {
+        self.remove(Entity::Mob(p.id));
+      }
+      _ => (),
+    }
+  }
+
+  /// The clients that should be sent a packet, in ascending order.
+  ///
+  /// Position updates for players and mobs only go to the clients that can
+  /// see the new position. Every other packet goes to all clients. This
+  /// should be called before the packet is passed to
+  /// [`observe`](HorizonTracker::observe).
+  pub fn recipients(&self, packet: &ServerPacket) -> Vec<Player> {
+    use self::ServerPacket::*;
+
+    let (entity, pos) = match packet {
+      PlayerUpdate(p) => (Entity::Player(p.id), p.pos),
+      EventBoost(p) => (Entity::Player(p.id), p.pos),
+      EventBounce(p) => (Entity::Player(p.id), p.pos),
+      MobUpdate(p) => (Entity::Mob(p.id), p.pos),
+      MobUpdate2(p) => (Entity::Mob(p.update.id), p.update.pos),
+      MobUpdateStationary(p) => (Entity::Mob(p.id), p.pos),
+      _ => return self.clients(),
+    };
+
+    let mut recipients: Vec<_> = self
+      .clients
+      .iter()
+      .filter(|(&id, client)| self.sees(id, client, entity, pos))
+      .map(|(&id, _)| id)
+      .collect();
+    recipients.sort_unstable();
+    recipients
+  }
+
+  /// Recompute what each client can see.
+  ///
+  /// Returns an [`EventLeaveHorizon`] for every entity that was visible to a
+  /// client at the previous update but no longer is, ordered by client id.
+  pub fn update(&mut self) -> Vec<(Player, EventLeaveHorizon)> {
+    let Self {
+      grid,
+      positions,
+      clients,
+    } = self;
+
+    let mut events = Vec::new();
+
+    for (&id, client) in clients.iter_mut() {
+      let mut visible = HashSet::new();
+
+      for entity in [Entity::Player(id), Entity::Player(client.focus)].iter() {
+        if positions.contains_key(entity) {
+          visible.insert(*entity);
+        }
+      }
+
+      if let Some(centre) = positions.get(&Entity::Player(client.focus)) {
+        let (min, max) = view_rect(client, *centre);
+        visible.extend(grid.query(min, max).map(|(entity, _)| entity));
+      }
+
+      events.extend(
+        client
+          .visible
+          .difference(&visible)
+          .map(|&entity| (id, entity)),
+      );
+      client.visible = visible;
+    }
+
+    events.sort_unstable();
+    events
+      .into_iter()
+      .map(|(id, entity)| (id, entity.leave_event()))
+      .collect()
+  }
+}
+
+impl Default for HorizonTracker {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+fn view_rect(client: &Client, centre: Position) -> (Position, Position) {
+  let mut min = centre;
+  let mut max = centre;
+
+  min.x -= client.horizon_x;
+  min.y -= client.horizon_y;
+  max.x += client.horizon_x;
+  max.y += client.horizon_y;
+
+  (min, max)
+}
@@ -0,0 +1,163 @@
+use super::*;
+use crate::server::*;
+use crate::types::VectorExt;
+use crate::{DespawnType, LeaveHorizonType, MobType};
+
+fn player_update(id: Player, x: f32, y: f32) -> ServerPacket {
+  ServerPacket::PlayerUpdate(PlayerUpdate {
+    clock: 0,
+    id,
+    keystate: Default::default(),
+    upgrades: Default::default(),
+    pos: Position::new(x, y),
+    rot: 0.0,
+    speed: Position::new(0.0, 0.0),
+  })
+}
+
+fn mob_update(id: Mob, x: f32, y: f32) -> ServerPacket {
+  ServerPacket::MobUpdateStationary(MobUpdateStationary {
+    id,
+    ty: MobType::Upgrade,
+    pos: Position::new(x, y),
+  })
+}
+
+fn tracker() -> HorizonTracker {
+  let mut tracker = HorizonTracker::new();
+  tracker.add_client(1, 1000, 500);
+  tracker.add_client(2, 1000, 500);
+  tracker.observe(&player_update(1, 0.0, 0.0));
+  tracker.observe(&player_update(2, 10000.0, 0.0));
+  tracker.update();
+  tracker
+}
+
+#[test]
+fn grid_query_matches_exact_rect() {
+  let mut grid = SpatialGrid::new(256.0);
+  grid.insert(1, Position::new(0.0, 0.0));
+  grid.insert(2, Position::new(300.0, 0.0));
+  grid.insert(3, Position::new(-300.0, 200.0));
+  grid.insert(4, Position::new(20000.0, 20000.0));
+
+  let mut found: Vec<_> = grid
+    .query(Position::new(-100.0, -100.0), Position::new(300.0, 100.0))
+    .map(|(x, _)| x)
+    .collect();
+  found.sort_unstable();
+  assert_eq!(found, [1, 2]);
+
+  // Out of bounds positions are clamped into the edge cells.
+  let found: Vec<_> = grid
+    .query(
+      Position::new(19000.0, 19000.0),
+      Position::new(21000.0, 21000.0),
+    )
+    .map(|(x, _)| x)
+    .collect();
+  assert_eq!(found, [4]);
+}
+
+#[test]
+fn grid_relocate_moves_between_cells() {
+  let mut grid = SpatialGrid::new(256.0);
+  grid.insert(1, Position::new(0.0, 0.0));
+  grid.relocate(1, Position::new(0.0, 0.0), Position::new(10.0, 0.0));
+  grid.relocate(1, Position::new(10.0, 0.0), Position::new(5000.0, 0.0));
+
+  let near = Position::new(-100.0, -100.0);
+  let far = Position::new(100.0, 100.0);
+  assert_eq!(grid.query(near, far).count(), 0);
+
+  let found: Vec<_> = grid
+    .query(Position::new(4900.0, -100.0), Position::new(5100.0, 100.0))
+    .collect();
+  assert_eq!(found.len(), 1);
+  assert_eq!(found[0].1.x, 5000.0);
+
+  assert!(grid.remove(1, Position::new(5000.0, 0.0)));
+  assert!(!grid.remove(1, Position::new(5000.0, 0.0)));
+}
+
+#[test]
+fn recipients_filter_position_updates() {
+  let tracker = tracker();
+
+  assert_eq!(tracker.recipients(&mob_update(7, 500.0, 0.0)), [1]);
+  assert_eq!(tracker.recipients(&mob_update(7, 9500.0, 400.0)), [2]);
+  assert!(tracker.recipients(&mob_update(7, 5000.0, 0.0)).is_empty());
+
+  // The horizon is a rectangle, not a circle.
+  assert!(tracker.recipients(&mob_update(7, 900.0, 600.0)).is_empty());
+
+  // Clients always receive updates about themselves.
+  assert_eq!(tracker.recipients(&player_update(2, 10000.0, 0.0)), [2]);
+
+  // Packets without a position go to everyone.
+  let leave = ServerPacket::PlayerLeave(PlayerLeave { id: 2 });
+  assert_eq!(tracker.recipients(&leave), [1, 2]);
+}
+
+#[test]
+fn update_emits_leave_horizon() {
+  let mut tracker = tracker();
+
+  tracker.observe(&mob_update(7, 500.0, 0.0));
+  tracker.observe(&player_update(2, 800.0, 0.0));
+  assert!(tracker.update().is_empty());
+
+  let mut visible: Vec<_> = tracker.visible(1).collect();
+  visible.sort_unstable();
+  assert_eq!(
+    visible,
+    [Entity::Player(1), Entity::Player(2), Entity::Mob(7)]
+  );
+
+  tracker.observe(&player_update(1, -2000.0, 0.0));
+  let events = tracker.update();
+
+  assert_eq!(events.len(), 3);
+  assert_eq!(events[0].0, 1);
+  assert_eq!(events[0].1.ty, LeaveHorizonType::Player);
+  assert_eq!(events[0].1.id, 2);
+  assert_eq!(events[1].0, 1);
+  assert_eq!(events[1].1.ty, LeaveHorizonType::Mob);
+  assert_eq!(events[1].1.id, 7);
+  assert_eq!(events[2].0, 2);
+  assert_eq!(events[2].1.ty, LeaveHorizonType::Player);
+  assert_eq!(events[2].1.id, 1);
+
+  // Nothing has changed so no further events are generated.
+  assert!(tracker.update().is_empty());
+}
+
+#[test]
+fn despawned_entities_do_not_leave_horizon() {
+  let mut tracker = tracker();
+
+  tracker.observe(&mob_update(7, 500.0, 0.0));
+  tracker.update();
+
+  tracker.observe(&ServerPacket::MobDespawn(MobDespawn {
+    id: 7,
+    ty: DespawnType::LifetimeEnded,
+  }));
+  assert!(tracker.update().is_empty());
+  assert!(tracker.position(Entity::Mob(7)).is_none());
+}
+
+#[test]
+fn spectate_moves_horizon() {
+  let mut tracker = tracker();
+  tracker.observe(&mob_update(7, 10500.0, 0.0));
+
+  assert!(!tracker.can_see(1, Entity::Mob(7)));
+  assert!(tracker.spectate(1, 2));
+  assert!(tracker.can_see(1, Entity::Mob(7)));
+  assert!(tracker.can_see(1, Entity::Player(1)));
+
+  assert!(tracker.set_horizon(1, 100, 100));
+  assert!(!tracker.can_see(1, Entity::Mob(7)));
+  assert!(!tracker.spectate(3, 2));
+}
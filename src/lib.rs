@@ -51,20 +51,33 @@ mod detail;
 mod enums;
 mod error;
 mod packets;
+mod registry;
+mod traits;
 mod types;
 mod util;
 
 mod client_packet;
 mod server_packet;
 
+#[cfg(feature = "tokio-codec")]
+pub mod codec;
 #[cfg(feature = "serde")]
 pub mod custom;
 
+pub mod batch;
+pub mod command;
+pub mod delta;
+pub mod horizon;
+pub mod physics;
+pub mod replay;
+pub mod state;
 pub mod v5;
 
 pub use self::client_packet::ClientPacket;
 pub use self::enums::*;
 pub use self::packets::*;
-pub use self::server_packet::ServerPacket;
+pub use self::registry::{Handshake, ProtocolRegistry};
+pub use self::server_packet::{Delivery, ServerPacket};
+pub use self::traits::{DynProtocol, Protocol, ProtocolSerializationExt, ServerPacketIterator};
 pub use self::types::*;
 pub use crate::error::EnumValueOutOfRangeError;
@@ -27,6 +27,9 @@ pub struct Chat {
 /// A free form command to be sent to the server. This is used for changing
 /// flags, respawning, spectating players, and selecting upgrades.
 ///
+/// See [`GameCommand`](crate::command::GameCommand) for a typed version of
+/// the commands that the official server understands.
+///
 /// # Changing a flag
 /// ```
 /// # extern crate airmash_protocol;
@@ -221,3 +224,54 @@ pub struct Whisper {
   pub id: u16,
   pub text: BString,
 }
+
+//===================================================================
+// ab-server sync protocol
+//
+// These are sent by the sync service to ab-server, see the matching packets in
+// the server module for details.
+
+/// Response to a [`server::SyncAuth`](crate::server::SyncAuth) challenge.
+#[cfg(feature = "ab-server")]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SyncAuth {
+  pub response: BString,
+}
+
+/// Sent by the sync service once it is ready to receive updates.
+#[cfg(feature = "ab-server")]
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SyncInit {
+  pub sequence: u32,
+  /// Milliseconds since the unix epoch.
+  pub timestamp: f64,
+}
+
+/// An update to a synchronised object sent by the sync service.
+#[cfg(feature = "ab-server")]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SyncUpdate {
+  pub sequence: u32,
+  #[cfg_attr(feature = "serde", serde(rename = "type"))]
+  pub ty: BString,
+  pub id: BString,
+  /// JSON-encoded object data.
+  pub data: BString,
+  /// Milliseconds since the unix epoch.
+  pub timestamp: f64,
+  /// JSON-encoded array describing the event that caused this update.
+  pub event: BString,
+}
+
+/// Acknowledges a [`server::SyncUpdate`](crate::server::SyncUpdate).
+#[cfg(feature = "ab-server")]
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SyncAck {
+  pub sequence: u32,
+  /// Zero if the update was applied successfully.
+  pub result: u8,
+}
@@ -283,7 +283,6 @@ pub struct LoginBot {
 /// Upgraded Login packet introduced in <https://github.com/wight-airmash/ab-protocol>
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-
 pub struct Login2 {
   #[cfg_attr(feature = "serde", serde(flatten))]
   pub login: Login,
@@ -607,14 +606,13 @@ pub struct ScoreUpdate {
   pub total_deaths: u32,
 }
 
-/// End of game packet for CTF and BTR.
+/// End of game packet for CTF and BTR, along with other custom messages.
 ///
 /// # CTF
 /// In CTF, the data of this packet contains a JSON string with 3 fields.
 ///
 /// - `w`: The id of the winning team.
-/// - `b`: The bounty given to each player
-/// of the winning team.
+/// - `b`: The bounty given to each player of the winning team.
 /// - `t`: The time (in seconds) that the banner should remain on screen before
 ///   closing (unless closed by the player.)
 ///
@@ -627,6 +625,15 @@ pub struct ScoreUpdate {
 /// - `k`: The number of kills that the winning player has.
 /// - `t`: The time (in seconds) that the banner should remain on the screen
 ///   before closing (unless closed by the player.)
+///
+/// # Switch Game Suggestion
+/// The data contains a JSON string with up to 2 fields.
+///
+/// - `r`: The room that the player should switch to.
+/// - `m`: An optional message to show along with the suggestion.
+///
+/// With the `serde` feature enabled, `decode_data` will parse the data into a
+/// [`CustomData`](crate::custom::CustomData) based on the packet type.
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ServerCustom {
@@ -707,6 +714,62 @@ pub struct ScoreDetailedFFA {
   pub scores: Vec<ScoreDetailedFFAEntry>,
 }
 
+//===================================================================
+// ab-server sync protocol
+//
+// These packets are only used by ab-server when synchronising persistent state
+// (user accounts, stats, etc.) with an external sync service over a dedicated
+// connection. Regular game clients never see them.
+
+/// Challenge sent by ab-server when a sync connection is opened. The sync
+/// service must respond with a [`client::SyncAuth`](crate::client::SyncAuth).
+#[cfg(feature = "ab-server")]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SyncAuth {
+  pub challenge: BString,
+}
+
+/// Sent once the sync connection has been authenticated. `sequence` is the
+/// sequence number of the next update that ab-server will send.
+#[cfg(feature = "ab-server")]
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SyncInit {
+  pub sequence: u32,
+  /// Milliseconds since the unix epoch.
+  pub timestamp: f64,
+}
+
+/// Start or stop receiving updates for an object from the sync service.
+#[cfg(feature = "ab-server")]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SyncSubscribe {
+  pub active: bool,
+  #[cfg_attr(feature = "serde", serde(rename = "type"))]
+  pub ty: BString,
+  pub id: BString,
+}
+
+/// An update to a synchronised object. Every update must be acknowledged by
+/// the sync service with a [`client::SyncAck`](crate::client::SyncAck).
+#[cfg(feature = "ab-server")]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SyncUpdate {
+  pub sequence: u32,
+  #[cfg_attr(feature = "serde", serde(rename = "type"))]
+  pub ty: BString,
+  pub id: BString,
+  /// JSON-encoded object data.
+  pub data: BString,
+  /// Milliseconds since the unix epoch.
+  pub timestamp: f64,
+  /// JSON-encoded array describing the event that caused this update.
+  pub event: BString,
+}
+
 //===================================================================
 // Extra trait implementations
 
@@ -0,0 +1,215 @@
+use super::frames;
+use crate::enums::MobType;
+use crate::server::{EventRepelMob, MobUpdate, PlayerFireProjectile};
+use crate::types::*;
+
+/// Stats for a single missile type.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MissileInfo {
+  /// Damage done to a plane with no upgrades when hit by this missile.
+  pub damage: Health,
+  /// Radius of the collision circle of the missile.
+  pub radius: Distance,
+  /// Distance that the missile travels before it despawns.
+  pub range: Distance,
+  /// Speed of the missile when it is fired by a stationary plane.
+  pub base_speed: Speed,
+  pub accel: AccelScalar,
+  pub max_speed: Speed,
+}
+
+impl MissileInfo {
+  pub const PREDATOR: Self = Self {
+    damage: 0.4,
+    radius: 3.0,
+    range: 1104.0,
+    base_speed: 4.05,
+    accel: 0.105,
+    max_speed: 9.0,
+  };
+
+  pub const GOLIATH: Self = Self {
+    damage: 1.2,
+    radius: 6.0,
+    range: 1076.0,
+    base_speed: 2.1,
+    accel: 0.0375,
+    max_speed: 6.0,
+  };
+
+  pub const MOHAWK: Self = Self {
+    damage: 0.2,
+    radius: 3.0,
+    range: 1104.0,
+    base_speed: 5.7,
+    accel: 0.14,
+    max_speed: 9.0,
+  };
+
+  pub const TORNADO_SINGLE: Self = Self {
+    damage: 0.4,
+    radius: 3.0,
+    range: 997.0,
+    base_speed: 3.5,
+    accel: 0.0875,
+    max_speed: 7.0,
+  };
+
+  pub const TORNADO_TRIPLE: Self = Self {
+    damage: 0.3,
+    radius: 3.0,
+    range: 581.0,
+    base_speed: 3.5,
+    accel: 0.0875,
+    max_speed: 7.0,
+  };
+
+  pub const PROWLER: Self = Self {
+    damage: 0.45,
+    radius: 3.0,
+    range: 819.0,
+    base_speed: 2.8,
+    accel: 0.07,
+    max_speed: 7.0,
+  };
+
+  /// Get the stats for a mob type.
+  ///
+  /// Returns `None` if the mob type is not a missile.
+  pub fn of(ty: MobType) -> Option<&'static Self> {
+    Some(match ty {
+      MobType::PredatorMissile => &Self::PREDATOR,
+      MobType::GoliathMissile => &Self::GOLIATH,
+      MobType::MohawkMissile => &Self::MOHAWK,
+      MobType::TornadoSingleMissile => &Self::TORNADO_SINGLE,
+      MobType::TornadoTripleMissile => &Self::TORNADO_TRIPLE,
+      MobType::ProwlerMissile => &Self::PROWLER,
+      _ => return None,
+    })
+  }
+
+  /// How long a missile fired by a stationary plane lives for before it
+  /// despawns.
+  pub fn lifetime(&self) -> Time {
+    // Time spent accelerating up to max speed and the distance covered
+    // while doing so.
+    let accel_time = (self.max_speed - self.base_speed) / self.accel;
+    let accel_dist = (self.base_speed + self.max_speed) * 0.5 * accel_time;
+
+    if accel_dist >= self.range {
+      // v0 * t + a * t^2 / 2 = range
+      let v0 = self.base_speed;
+      ((v0 * v0 + 2.0 * self.accel * self.range).sqrt() - v0) / self.accel
+    } else {
+      accel_time + (self.range - accel_dist) / self.max_speed
+    }
+  }
+}
+
+/// The state of a single missile in flight.
+#[derive(Copy, Clone, Debug)]
+pub struct MissileState {
+  pub ty: MobType,
+  pub pos: Position,
+  pub speed: Velocity,
+  pub accel: Accel,
+  pub max_speed: Speed,
+  /// The distance that the missile has travelled since it was last updated
+  /// by the server.
+  pub distance: Distance,
+}
+
+impl MissileState {
+  /// The distance that the missile can still travel, assuming that it was
+  /// fired at the position of the last update from the server.
+  ///
+  /// Returns `None` if the mob is not a missile.
+  pub fn remaining_range(&self) -> Option<Distance> {
+    MissileInfo::of(self.ty).map(|info| (info.range - self.distance).max(0.0))
+  }
+
+  /// Whether the missile has travelled the full range of its type.
+  pub fn is_expired(&self) -> bool {
+    self.remaining_range() == Some(0.0)
+  }
+
+  /// Predict the position of the missile `dt` frames from now without
+  /// modifying it.
+  pub fn predict(&self, dt: Time) -> Position {
+    let mut state = *self;
+    step_missile(&mut state, dt);
+    state.pos
+  }
+}
+
+impl From<&MobUpdate> for MissileState {
+  fn from(p: &MobUpdate) -> Self {
+    Self {
+      ty: p.ty,
+      pos: p.pos,
+      speed: p.speed,
+      accel: p.accel,
+      max_speed: p.max_speed,
+      distance: 0.0,
+    }
+  }
+}
+
+impl From<&PlayerFireProjectile> for MissileState {
+  fn from(p: &PlayerFireProjectile) -> Self {
+    Self {
+      ty: p.ty,
+      pos: p.pos,
+      speed: p.speed,
+      accel: p.accel,
+      max_speed: p.max_speed,
+      distance: 0.0,
+    }
+  }
+}
+
+impl From<&EventRepelMob> for MissileState {
+  fn from(p: &EventRepelMob) -> Self {
+    Self {
+      ty: p.ty,
+      pos: p.pos,
+      speed: p.speed,
+      accel: p.accel,
+      max_speed: p.max_speed,
+      distance: 0.0,
+    }
+  }
+}
+
+/// Advance the state of a missile by `dt` frames.
+///
+/// Like [`step`](super::step), large time steps are split up into frames of
+/// roughly equal length. Missiles stop moving once they have travelled the
+/// full range of their type.
+pub fn step_missile(state: &mut MissileState, dt: Time) {
+  let (frames, frac) = frames(dt);
+
+  let range = MissileInfo::of(state.ty).map(|info| info.range);
+
+  for _ in 0..frames {
+    if let Some(range) = range {
+      if state.distance >= range {
+        break;
+      }
+    }
+
+    state.speed.x += state.accel.x * frac;
+    state.speed.y += state.accel.y * frac;
+
+    let length = state.speed.x.hypot(state.speed.y);
+    if length > state.max_speed {
+      let factor = state.max_speed / length;
+      state.speed.x *= factor;
+      state.speed.y *= factor;
+    }
+
+    state.pos.x += state.speed.x * frac;
+    state.pos.y += state.speed.y * frac;
+    state.distance += state.speed.x.hypot(state.speed.y) * frac;
+  }
+}
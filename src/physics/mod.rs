@@ -0,0 +1,59 @@
+//! Client-side physics prediction.
+//!
+//! The server only sends position updates when something about a plane
+//! changes (e.g. a key is pressed) so clients are expected to extrapolate the
+//! movement of everything in between. The functions here reproduce the
+//! extrapolation done by the official client so that bots can dead-reckon
+//! the position of other players and dodge missiles (see [`step_missile`]).
+//!
+//! All rates are per unit of [`Time`](crate::Time), i.e. per 16.667ms frame.
+//!
+//! ```
+//! # use airmash_protocol::physics::{step, PlaneState};
+//! # use airmash_protocol::PlaneType;
+//! let mut state = PlaneState::new(PlaneType::Predator);
+//! state.keystate.up = true;
+//!
+//! // Predict where the plane will be one second from now.
+//! step(&mut state, 60.0);
+//! assert!(state.pos.y < 0.0);
+//! ```
+
+mod missile;
+mod plane;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::missile::*;
+pub use self::plane::*;
+
+use crate::Time;
+
+/// Bounds of the area that planes are allowed to move within.
+///
+/// This is slightly smaller than the full map since planes are kept a short
+/// distance away from the edge.
+pub mod bounds {
+  use crate::Distance;
+
+  pub const MIN_X: Distance = -16352.0;
+  pub const MAX_X: Distance = 16352.0;
+  pub const MIN_Y: Distance = -8160.0;
+  pub const MAX_Y: Distance = 8160.0;
+}
+
+/// Split a time step into a number of frames of equal length.
+///
+/// This mirrors what the official client does so that predictions don't
+/// depend too much on how often they are updated.
+fn frames(dt: Time) -> (u32, Time) {
+  if dt <= 0.0 {
+    (0, 0.0)
+  } else if dt > 0.51 {
+    let frames = dt.round();
+    (frames as u32, dt / frames)
+  } else {
+    (1, dt)
+  }
+}
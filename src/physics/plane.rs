@@ -0,0 +1,260 @@
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use super::{bounds, frames};
+use crate::enums::PlaneType;
+use crate::server::PlayerUpdate;
+use crate::types::*;
+
+/// Movement constants for a single plane type.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PlaneInfo {
+  /// How fast the plane turns while left or right is held.
+  pub turn_factor: RotationRate,
+  /// How fast the plane accelerates while up or down is held.
+  pub accel_factor: AccelScalar,
+  /// Fraction of the plane's speed that is lost every frame when it is not
+  /// already going faster than its max speed.
+  pub brake_factor: f32,
+  /// Multiplier applied to both acceleration and max speed while boosting.
+  pub boost_factor: f32,
+  pub max_speed: Speed,
+  /// Below this speed (in both axes) the plane is considered to be stopped.
+  pub min_speed: Speed,
+  /// Max speed of the plane while carrying a flag.
+  pub flag_speed: Speed,
+  /// Multiplier applied to max speed while the inferno powerup is active.
+  pub inferno_factor: f32,
+}
+
+impl PlaneInfo {
+  pub const PREDATOR: Self = Self {
+    turn_factor: 0.065,
+    accel_factor: 0.225,
+    brake_factor: 0.025,
+    boost_factor: 1.5,
+    max_speed: 5.5,
+    min_speed: 0.001,
+    flag_speed: 5.0,
+    inferno_factor: 0.75,
+  };
+
+  pub const GOLIATH: Self = Self {
+    turn_factor: 0.04,
+    accel_factor: 0.15,
+    brake_factor: 0.015,
+    boost_factor: 1.0,
+    max_speed: 3.5,
+    min_speed: 0.001,
+    flag_speed: 5.0,
+    inferno_factor: 0.75,
+  };
+
+  pub const MOHAWK: Self = Self {
+    turn_factor: 0.07,
+    accel_factor: 0.275,
+    brake_factor: 0.025,
+    boost_factor: 1.0,
+    max_speed: 6.0,
+    min_speed: 0.001,
+    flag_speed: 5.0,
+    inferno_factor: 0.75,
+  };
+
+  pub const TORNADO: Self = Self {
+    turn_factor: 0.055,
+    accel_factor: 0.2,
+    brake_factor: 0.025,
+    boost_factor: 1.0,
+    max_speed: 4.5,
+    min_speed: 0.001,
+    flag_speed: 5.0,
+    inferno_factor: 0.75,
+  };
+
+  pub const PROWLER: Self = Self {
+    turn_factor: 0.055,
+    accel_factor: 0.2,
+    brake_factor: 0.025,
+    boost_factor: 1.0,
+    max_speed: 4.5,
+    min_speed: 0.001,
+    flag_speed: 5.0,
+    inferno_factor: 0.75,
+  };
+
+  /// Get the movement constants for a plane type.
+  ///
+  /// Returns `None` if the plane type is not one of the ones known by the
+  /// official client.
+  pub fn of(plane: PlaneType) -> Option<&'static Self> {
+    Some(match plane {
+      PlaneType::Predator => &Self::PREDATOR,
+      PlaneType::Goliath => &Self::GOLIATH,
+      PlaneType::Mohawk => &Self::MOHAWK,
+      PlaneType::Tornado => &Self::TORNADO,
+      PlaneType::Prowler => &Self::PROWLER,
+      _ => return None,
+    })
+  }
+}
+
+/// Max speed multipliers for each level of the speed upgrade.
+pub const SPEED_UPGRADE_FACTORS: [f32; 6] = [1.0, 1.05, 1.1, 1.15, 1.2, 1.25];
+
+/// Get the max speed multiplier for the given number of speed upgrades.
+///
+/// Values beyond the last known level use the multiplier of the last level.
+pub fn speed_upgrade_factor(level: u8) -> f32 {
+  let idx = (level as usize).min(SPEED_UPGRADE_FACTORS.len() - 1);
+  SPEED_UPGRADE_FACTORS[idx]
+}
+
+/// The movement-related state of a single plane.
+#[derive(Copy, Clone, Debug)]
+pub struct PlaneState {
+  pub plane: PlaneType,
+  pub pos: Position,
+  pub rot: Rotation,
+  pub speed: Velocity,
+  pub keystate: ServerKeyState,
+  pub upgrades: Upgrades,
+}
+
+impl PlaneState {
+  /// Create a plane that is stationary at the origin.
+  pub fn new(plane: PlaneType) -> Self {
+    Self {
+      plane,
+      pos: Position::new(0.0, 0.0),
+      rot: 0.0,
+      speed: Velocity::new(0.0, 0.0),
+      keystate: ServerKeyState::default(),
+      upgrades: Upgrades::default(),
+    }
+  }
+
+  /// Create the state for a plane from an update sent by the server.
+  pub fn from_update(plane: PlaneType, update: &PlayerUpdate) -> Self {
+    let mut state = Self::new(plane);
+    state.apply_update(update);
+    state
+  }
+
+  /// Replace the predicted state with the authoritative state from the
+  /// server.
+  pub fn apply_update(&mut self, update: &PlayerUpdate) {
+    self.pos = update.pos;
+    self.rot = update.rot;
+    self.speed = update.speed;
+    self.keystate = update.keystate;
+    self.upgrades = update.upgrades;
+  }
+
+  /// The max speed of the plane given its current keystate and upgrades.
+  pub fn max_speed(&self, info: &PlaneInfo) -> Speed {
+    if self.keystate.flagspeed {
+      return info.flag_speed;
+    }
+
+    let mut max_speed = info.max_speed * speed_upgrade_factor(self.upgrades.speed);
+    if self.keystate.boost {
+      max_speed *= info.boost_factor;
+    }
+    if self.upgrades.inferno {
+      max_speed *= info.inferno_factor;
+    }
+    max_speed
+  }
+
+  /// The direction in which the keys that are currently held will accelerate
+  /// the plane, or `None` if they won't accelerate it at all.
+  fn thrust_angle(&self) -> Option<Rotation> {
+    let keys = &self.keystate;
+    // Only planes that are strafing (i.e. mohawks) can move sideways. For
+    // everything else left and right only turn the plane.
+    let left = keys.strafe && keys.left;
+    let right = keys.strafe && keys.right;
+
+    let (forward, side) = match (keys.up, keys.down) {
+      (true, _) => (Some(0.0), FRAC_PI_4),
+      (false, true) => (Some(PI), 3.0 * FRAC_PI_4),
+      (false, false) => (None, FRAC_PI_2),
+    };
+
+    let offset = if left {
+      -side
+    } else if right {
+      side
+    } else {
+      forward?
+    };
+
+    Some(self.rot + offset)
+  }
+
+  fn step_frame(&mut self, info: &PlaneInfo, dt: Time) {
+    let keys = self.keystate;
+
+    if !keys.strafe {
+      if keys.left {
+        self.rot -= dt * info.turn_factor;
+      }
+      if keys.right {
+        self.rot += dt * info.turn_factor;
+      }
+    }
+
+    let boost_factor = match keys.boost {
+      true => info.boost_factor,
+      false => 1.0,
+    };
+
+    let old_speed = self.speed;
+    if let Some(angle) = self.thrust_angle() {
+      let accel = info.accel_factor * boost_factor * dt;
+      self.speed.x += angle.sin() * accel;
+      self.speed.y -= angle.cos() * accel;
+    }
+
+    let max_speed = self.max_speed(info);
+    let length = self.speed.x.hypot(self.speed.y);
+    if length > max_speed {
+      let factor = max_speed / length;
+      self.speed.x *= factor;
+      self.speed.y *= factor;
+    } else if self.speed.x.abs() > info.min_speed || self.speed.y.abs() > info.min_speed {
+      let factor = 1.0 - info.brake_factor * dt;
+      self.speed.x *= factor;
+      self.speed.y *= factor;
+    } else {
+      self.speed = Velocity::new(0.0, 0.0);
+    }
+
+    self.pos.x += old_speed.x * dt + 0.5 * (self.speed.x - old_speed.x) * dt;
+    self.pos.y += old_speed.y * dt + 0.5 * (self.speed.y - old_speed.y) * dt;
+    self.pos.x = self.pos.x.clamp(bounds::MIN_X, bounds::MAX_X);
+    self.pos.y = self.pos.y.clamp(bounds::MIN_Y, bounds::MAX_Y);
+
+    self.rot = self.rot.rem_euclid(2.0 * PI);
+  }
+}
+
+/// Advance the state of a plane by `dt` frames.
+///
+/// Like the official client, large time steps are split up into multiple
+/// frames of roughly equal length so that the result doesn't depend much on
+/// how often this function is called.
+///
+/// Planes with an unknown [`PlaneType`] are not moved.
+pub fn step(state: &mut PlaneState, dt: Time) {
+  let info = match PlaneInfo::of(state.plane) {
+    Some(info) => info,
+    None => return,
+  };
+
+  let (frames, frac) = frames(dt);
+
+  for _ in 0..frames {
+    state.step_frame(info, frac);
+  }
+}
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Duration;
+
+use approx::assert_abs_diff_eq;
+
+use super::*;
+use crate::replay::{ReplayPacket, ReplayReader};
+use crate::server::*;
+use crate::types::*;
+use crate::{MobType, PlaneType, ServerPacket};
+
+fn predator() -> PlaneState {
+  PlaneState::new(PlaneType::Predator)
+}
+
+#[test]
+fn accelerate_from_rest() {
+  let mut state = predator();
+  state.keystate.up = true;
+
+  step(&mut state, 1.0);
+
+  let info = PlaneInfo::PREDATOR;
+  let speed = info.accel_factor * (1.0 - info.brake_factor);
+  assert_abs_diff_eq!(state.speed.x, 0.0);
+  assert_abs_diff_eq!(state.speed.y, -speed);
+  assert_abs_diff_eq!(state.pos.y, -speed / 2.0);
+}
+
+#[test]
+fn speed_is_capped() {
+  let mut state = predator();
+  state.keystate.up = true;
+  step(&mut state, 300.0);
+  assert_abs_diff_eq!(state.speed.y, -5.5, epsilon = 1e-4);
+
+  state.keystate.boost = true;
+  step(&mut state, 300.0);
+  assert_abs_diff_eq!(state.speed.y, -5.5 * 1.5, epsilon = 1e-4);
+
+  state.keystate.flagspeed = true;
+  step(&mut state, 10.0);
+  assert_abs_diff_eq!(state.speed.y, -5.0, epsilon = 1e-4);
+
+  state.keystate.boost = false;
+  state.keystate.flagspeed = false;
+  state.upgrades.speed = 5;
+  step(&mut state, 300.0);
+  assert_abs_diff_eq!(state.speed.y, -5.5 * 1.25, epsilon = 1e-4);
+}
+
+#[test]
+fn plane_slows_down_without_input() {
+  let mut state = predator();
+  state.speed = Velocity::new(2.0, 0.0);
+
+  step(&mut state, 1.0);
+  assert_abs_diff_eq!(state.speed.x, 2.0 * 0.975);
+
+  step(&mut state, 1000.0);
+  assert_eq!(state.speed, Velocity::new(0.0, 0.0));
+}
+
+#[test]
+fn turning_wraps_rotation() {
+  let mut state = predator();
+  state.keystate.left = true;
+
+  step(&mut state, 10.0);
+  assert_abs_diff_eq!(state.rot, 2.0 * PI - 0.65, epsilon = 1e-4);
+  assert_eq!(state.pos, Position::new(0.0, 0.0));
+}
+
+#[test]
+fn mohawk_strafes() {
+  let mut state = PlaneState::new(PlaneType::Mohawk);
+  state.keystate.strafe = true;
+  state.keystate.right = true;
+
+  step(&mut state, 1.0);
+  assert_eq!(state.rot, 0.0);
+  assert!(state.speed.x > 0.0);
+  assert_abs_diff_eq!(state.speed.y, 0.0, epsilon = 1e-6);
+}
+
+#[test]
+fn plane_stays_within_bounds() {
+  let mut state = predator();
+  state.pos = Position::new(bounds::MAX_X - 1.0, 0.0);
+  state.rot = FRAC_PI_2;
+  state.keystate.up = true;
+
+  step(&mut state, 60.0);
+  assert_eq!(state.pos.x, bounds::MAX_X);
+}
+
+#[test]
+fn large_steps_are_split_into_frames() {
+  let mut a = predator();
+  a.keystate.up = true;
+  a.keystate.right = true;
+  let mut b = a;
+
+  step(&mut a, 60.0);
+  for _ in 0..60 {
+    step(&mut b, 1.0);
+  }
+
+  assert_abs_diff_eq!(a.pos.x, b.pos.x, epsilon = 1e-3);
+  assert_abs_diff_eq!(a.pos.y, b.pos.y, epsilon = 1e-3);
+  assert_abs_diff_eq!(a.rot, b.rot, epsilon = 1e-5);
+}
+
+/// Dead-reckon between consecutive updates for the same player within the
+/// replays in `tests/fixtures/physics`. See the README in that directory for
+/// how the replays are captured.
+///
+/// Unlike the tests above this checks the constants against the official
+/// server instead of against values derived from them.
+#[test]
+#[ignore = "requires replays captured from the official server in tests/fixtures/physics"]
+fn dead_reckon_captured_updates() {
+  let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/physics");
+
+  let mut checked = 0;
+  for entry in fs::read_dir(&dir).unwrap() {
+    let path = entry.unwrap().path();
+    if path.extension() == Some("replay".as_ref()) {
+      checked += dead_reckon_replay(&path);
+    }
+  }
+
+  assert!(checked > 0, "no captured updates in {}", dir.display());
+}
+
+/// Check every pair of updates for a player that were sent while its inputs
+/// stayed the same. Returns the number of pairs checked.
+fn dead_reckon_replay(path: &Path) -> usize {
+  let file = BufReader::new(File::open(path).unwrap());
+  let mut reader = ReplayReader::new(file).unwrap();
+
+  let mut planes = HashMap::new();
+  let mut last: HashMap<_, (Duration, PlayerUpdate)> = HashMap::new();
+  let mut checked = 0;
+
+  while let Some((time, _, packet)) = reader.next_packet().unwrap() {
+    let packet = match packet {
+      ReplayPacket::Server(packet) => packet,
+      ReplayPacket::Client(_) => continue,
+    };
+
+    match packet {
+      ServerPacket::Login(login) => {
+        planes.extend(login.players.iter().map(|p| (p.id, p.ty)));
+        last.clear();
+      }
+      ServerPacket::PlayerNew(p) => {
+        planes.insert(p.id, p.ty);
+      }
+      ServerPacket::PlayerType(p) => {
+        planes.insert(p.id, p.ty);
+        last.remove(&p.id);
+      }
+      // Anything that moves a plane other than its own inputs.
+      ServerPacket::PlayerRespawn(PlayerRespawn { id, .. })
+      | ServerPacket::PlayerKill(PlayerKill { id, .. })
+      | ServerPacket::PlayerLeave(PlayerLeave { id })
+      | ServerPacket::EventBounce(EventBounce { id, .. })
+      | ServerPacket::EventBoost(EventBoost { id, .. }) => {
+        last.remove(&id);
+      }
+      ServerPacket::EventRepel(repel) => {
+        for player in &repel.players {
+          last.remove(&player.id);
+        }
+      }
+      ServerPacket::PlayerUpdate(update) => {
+        if let (Some(&plane), Some((prev_time, prev))) =
+          (planes.get(&update.id), last.get(&update.id))
+        {
+          if prev.keystate == update.keystate && prev.upgrades == update.upgrades {
+            // Frame times include network jitter so this is only accurate to
+            // within a frame or so.
+            let frames = (time - *prev_time).as_secs_f32() * 60.0;
+            let mut state = PlaneState::from_update(plane, prev);
+            step(&mut state, frames);
+
+            let error = (state.pos.x - update.pos.x).hypot(state.pos.y - update.pos.y);
+            let tolerance = 2.0 + 2.0 * update.speed.x.hypot(update.speed.y);
+            assert!(
+              error <= tolerance,
+              "{}: player {} at {:?} was predicted at {:?} but was at {:?}",
+              path.display(),
+              update.id,
+              time,
+              state.pos,
+              update.pos,
+            );
+            checked += 1;
+          }
+        }
+
+        last.insert(update.id, (time, update));
+      }
+      _ => (),
+    }
+  }
+
+  checked
+}
+
+#[test]
+fn unknown_plane_does_not_move() {
+  let mut state = PlaneState::new(PlaneType::Unknown(10));
+  state.speed = Velocity::new(1.0, 1.0);
+  step(&mut state, 10.0);
+  assert_eq!(state.pos, Position::new(0.0, 0.0));
+}
+
+fn predator_missile() -> MissileState {
+  let info = MissileInfo::PREDATOR;
+  MissileState::from(&PlayerFireProjectile {
+    id: 1,
+    ty: MobType::PredatorMissile,
+    pos: Position::new(0.0, 0.0),
+    speed: Velocity::new(0.0, -info.base_speed),
+    accel: Accel::new(0.0, -info.accel),
+    max_speed: info.max_speed,
+  })
+}
+
+#[test]
+fn missile_accelerates_to_max_speed() {
+  let mut missile = predator_missile();
+
+  step_missile(&mut missile, 1.0);
+  assert_abs_diff_eq!(missile.speed.y, -4.155);
+  assert_abs_diff_eq!(missile.pos.y, -4.155);
+
+  step_missile(&mut missile, 100.0);
+  assert_abs_diff_eq!(missile.speed.y, -9.0);
+  assert_abs_diff_eq!(missile.speed.x, 0.0);
+}
+
+#[test]
+fn missile_stops_at_end_of_range() {
+  let mut missile = predator_missile();
+  let lifetime = MissileInfo::PREDATOR.lifetime();
+
+  step_missile(&mut missile, lifetime - 2.0);
+  assert!(!missile.is_expired());
+
+  step_missile(&mut missile, 4.0);
+  assert!(missile.is_expired());
+  // Missiles move in whole frames so they may overshoot by less than a frame
+  assert_abs_diff_eq!(-missile.pos.y, MissileInfo::PREDATOR.range, epsilon = 9.0);
+
+  let pos = missile.pos;
+  step_missile(&mut missile, 10.0);
+  assert_eq!(missile.pos, pos);
+}
+
+#[test]
+fn missile_predict_does_not_modify() {
+  let missile = predator_missile();
+  let pos = missile.predict(10.0);
+
+  assert_eq!(missile.pos, Position::new(0.0, 0.0));
+  assert!(pos.y < -40.0);
+}
+
+#[test]
+fn missile_stats() {
+  for ty in [
+    MobType::PredatorMissile,
+    MobType::GoliathMissile,
+    MobType::MohawkMissile,
+    MobType::TornadoSingleMissile,
+    MobType::TornadoTripleMissile,
+    MobType::ProwlerMissile,
+  ] {
+    let info = MissileInfo::of(ty).unwrap();
+    assert!(info.lifetime() > 0.0, "{:?}", ty);
+    assert!(info.base_speed <= info.max_speed, "{:?}", ty);
+  }
+
+  assert!(MissileInfo::of(MobType::Upgrade).is_none());
+  assert!(MissileInfo::of(MobType::Unknown(100)).is_none());
+}
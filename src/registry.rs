@@ -0,0 +1,323 @@
+//! Runtime selection of the protocol used by a client connection.
+
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::batch::BATCH_FLAG;
+use crate::client::{Backup, Login};
+use crate::server::Error;
+use crate::traits::{DynProtocol, Protocol};
+use crate::v5::{self, ErrorExt as _, ProtocolV5};
+use crate::{ErrorType, ServerPacket};
+
+/// The outcome of negotiating a protocol from the first frame sent by a
+/// client.
+pub enum Handshake<SE = v5::Error, DE = v5::Error> {
+  /// The client requested a protocol that is present within the registry.
+  ///
+  /// The first frame can now be decoded using the contained protocol.
+  Accepted(Arc<DynProtocol<SE, DE>>),
+  /// The client requested a protocol version that is not known to the
+  /// registry.
+  Rejected {
+    /// The protocol version that the client requested.
+    version: u8,
+    /// A v5-encoded [`Error`] packet with [`ErrorType::IncorrectProtocol`]
+    /// that should be sent back to the client before closing the connection.
+    response: Vec<u8>,
+  },
+}
+
+impl<SE, DE> fmt::Debug for Handshake<SE, DE>
+where
+  SE: StdError,
+  DE: StdError,
+{
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::Accepted(protocol) => f
+        .debug_tuple("Accepted")
+        .field(&protocol.version())
+        .finish(),
+      Self::Rejected { version, response } => f
+        .debug_struct("Rejected")
+        .field("version", version)
+        .field("response", response)
+        .finish(),
+    }
+  }
+}
+
+/// A set of protocols keyed by their version number.
+///
+/// The version that a client wants to use is sent as the `protocol` field of
+/// the [`Login`] packet. [`negotiate`](Self::negotiate) peeks at this field so
+/// that the rest of the connection can be decoded with the right protocol.
+/// This assumes that all protocols encode the start of the `Login` packet the
+/// same way protocol-v5 does: the packet id followed by the protocol version.
+///
+/// [`Backup`] packets don't carry a protocol version so they are always
+/// assigned the backup version (by default 5).
+///
+/// The [`BATCH_FLAG`] bit of the requested version is not part of the
+/// version. It is ignored when picking the protocol and can be checked
+/// separately with [`batch::requested`](crate::batch::requested).
+///
+/// # Example
+/// ```
+/// # use airmash_protocol::{ProtocolRegistry, Handshake, ClientPacket, v5};
+/// # use airmash_protocol::client::Login;
+/// # fn main() -> Result<(), v5::Error> {
+/// let registry = ProtocolRegistry::new();
+///
+/// let frame = v5::serialize(&ClientPacket::Login(Login {
+///   protocol: 5,
+///   name: "test".into(),
+///   session: "none".into(),
+///   horizon_x: 1920,
+///   horizon_y: 1920,
+///   flag: "UN".into(),
+/// }))?;
+///
+/// match registry.negotiate(&frame)? {
+///   Handshake::Accepted(protocol) => {
+///     let _login = protocol.deserialize_client(&frame)?;
+///     // ... store protocol for the connection
+///   }
+///   Handshake::Rejected { response, .. } => {
+///     // ... send response and close the connection
+///   }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct ProtocolRegistry<SE = v5::Error, DE = v5::Error> {
+  protocols: HashMap<u8, Arc<DynProtocol<SE, DE>>>,
+  backup_version: u8,
+}
+
+impl<SE, DE> ProtocolRegistry<SE, DE>
+where
+  SE: StdError,
+  DE: StdError,
+{
+  /// Create a registry without any protocols registered.
+  pub fn empty() -> Self {
+    Self {
+      protocols: HashMap::new(),
+      backup_version: ProtocolV5::VERSION,
+    }
+  }
+
+  /// Add a protocol to the registry under its version number.
+  ///
+  /// If there was already a protocol registered with the same version then it
+  /// is replaced and returned.
+  pub fn register<P>(&mut self, protocol: P) -> Option<Arc<DynProtocol<SE, DE>>>
+  where
+    P: Protocol<SerializeError = SE, DeserializeError = DE> + 'static,
+  {
+    self.register_arc(Arc::new(protocol))
+  }
+
+  /// Same as [`register`](Self::register) but for an already type-erased
+  /// protocol.
+  pub fn register_arc(
+    &mut self,
+    protocol: Arc<DynProtocol<SE, DE>>,
+  ) -> Option<Arc<DynProtocol<SE, DE>>> {
+    self.protocols.insert(protocol.version(), protocol)
+  }
+
+  /// Remove the protocol with the given version from the registry.
+  pub fn unregister(&mut self, version: u8) -> Option<Arc<DynProtocol<SE, DE>>> {
+    self.protocols.remove(&version)
+  }
+
+  /// Get the protocol for a version, if one is registered.
+  pub fn get(&self, version: u8) -> Option<&Arc<DynProtocol<SE, DE>>> {
+    self.protocols.get(&version)
+  }
+
+  /// All protocol versions within the registry, in no particular order.
+  pub fn versions(&self) -> impl Iterator<Item = u8> + '_ {
+    self.protocols.keys().copied()
+  }
+
+  /// The protocol version that is assigned to connections which open with a
+  /// [`Backup`] packet.
+  pub fn backup_version(&self) -> u8 {
+    self.backup_version
+  }
+
+  /// Set the protocol version that is assigned to connections which open with
+  /// a [`Backup`] packet.
+  pub fn set_backup_version(&mut self, version: u8) {
+    self.backup_version = version;
+  }
+
+  /// Pick the protocol for a connection based on the first frame that the
+  /// client sent.
+  ///
+  /// This only looks at the start of the frame, the frame itself still needs
+  /// to be deserialized using the accepted protocol.
+  ///
+  /// # Errors
+  /// Returns an error if the frame is neither a [`Login`] nor a [`Backup`]
+  /// packet.
+  pub fn negotiate(&self, frame: &[u8]) -> v5::Result<Handshake<SE, DE>> {
+    let version = requested_version(frame)
+      .map_err(|e| e.with_context("ClientPacket"))?
+      .map(|version| version & !BATCH_FLAG)
+      .unwrap_or(self.backup_version);
+
+    Ok(match self.get(version) {
+      Some(protocol) => Handshake::Accepted(
+        protocol
+          .new_connection()
+          .unwrap_or_else(|| protocol.clone()),
+      ),
+      None => Handshake::Rejected {
+        version,
+        response: v5::serialize(&ServerPacket::Error(Error {
+          error: ErrorType::IncorrectProtocol,
+        }))?,
+      },
+    })
+  }
+}
+
+/// Read the requested protocol version from the first frame of a
+/// connection. Returns `None` for `Backup` packets.
+pub(crate) fn requested_version(frame: &[u8]) -> v5::Result<Option<u8>> {
+  match frame.first().copied() {
+    Some(Login::V5_PACKET_NO) => match frame.get(1) {
+      Some(&version) => Ok(Some(version)),
+      None => Err(
+        v5::Error::end_of_buffer(1, 0)
+          .with_offset(1)
+          .with_packet_id(Login::V5_PACKET_NO)
+          .with_context("protocol")
+          .with_context("Login"),
+      ),
+    },
+    Some(Backup::V5_PACKET_NO) => Ok(None),
+    Some(id) => Err(
+      v5::Error::invalid_value(id)
+        .with_offset(0)
+        .with_packet_id(id),
+    ),
+    None => Err(v5::Error::end_of_buffer(1, 0).with_offset(0)),
+  }
+}
+
+impl ProtocolRegistry {
+  /// Create a registry with [`ProtocolV5`] already registered.
+  pub fn new() -> Self {
+    let mut registry = Self::empty();
+    registry.register(ProtocolV5);
+    registry
+  }
+}
+
+impl Default for ProtocolRegistry {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::v5::ErrorKind;
+  use crate::ClientPacket;
+
+  fn login_frame(protocol: u8) -> Vec<u8> {
+    v5::serialize(&ClientPacket::Login(Login {
+      protocol,
+      name: "test".into(),
+      session: "none".into(),
+      horizon_x: 0,
+      horizon_y: 0,
+      flag: "UN".into(),
+    }))
+    .unwrap()
+  }
+
+  #[test]
+  fn accepts_known_version() {
+    let registry = ProtocolRegistry::new();
+
+    match registry.negotiate(&login_frame(5)).unwrap() {
+      Handshake::Accepted(protocol) => assert_eq!(protocol.version(), 5),
+      other => panic!("expected protocol to be accepted, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn ignores_batch_flag() {
+    let registry = ProtocolRegistry::new();
+
+    match registry.negotiate(&login_frame(5 | BATCH_FLAG)).unwrap() {
+      Handshake::Accepted(protocol) => assert_eq!(protocol.version(), 5),
+      other => panic!("expected protocol to be accepted, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn rejects_unknown_version() {
+    let registry = ProtocolRegistry::new();
+
+    let response = match registry.negotiate(&login_frame(6)).unwrap() {
+      Handshake::Rejected { version, response } => {
+        assert_eq!(version, 6);
+        response
+      }
+      other => panic!("expected protocol to be rejected, got {:?}", other),
+    };
+
+    match v5::deserialize(&response).unwrap() {
+      ServerPacket::Error(Error {
+        error: ErrorType::IncorrectProtocol,
+      }) => (),
+      other => panic!("unexpected response packet {:?}", other),
+    }
+  }
+
+  #[test]
+  fn backup_uses_backup_version() {
+    let frame = v5::serialize(&ClientPacket::Backup(Backup {
+      token: "token".into(),
+    }))
+    .unwrap();
+
+    let mut registry = ProtocolRegistry::new();
+    assert!(matches!(
+      registry.negotiate(&frame).unwrap(),
+      Handshake::Accepted(_)
+    ));
+
+    registry.set_backup_version(7);
+    assert!(matches!(
+      registry.negotiate(&frame).unwrap(),
+      Handshake::Rejected { version: 7, .. }
+    ));
+  }
+
+  #[test]
+  fn invalid_first_frame() {
+    let registry = ProtocolRegistry::new();
+
+    let err = registry.negotiate(&[]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::EndOfBuffer);
+
+    let err = registry.negotiate(&[0]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::EndOfBuffer);
+    assert_eq!(err.context(), ["protocol", "Login", "ClientPacket"]);
+
+    let err = registry.negotiate(&[10, 0, 0, 0, 0, 1, 1]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidEnumValue);
+  }
+}
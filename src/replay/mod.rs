@@ -0,0 +1,207 @@
+//! Recording and playback of packet streams.
+//!
+//! A replay file stores every frame sent between a client and a server along
+//! with the time at which it was sent. Frames are stored exactly as they were
+//! sent over the wire (i.e. encoded with protocol v5) so that a replay can be
+//! inspected even if it contains packets that this crate can't decode.
+//!
+//! # Format
+//! All integers are little-endian.
+//!
+//! - A header: the magic bytes `AMREPLAY`, the format version (`u16`), the
+//!   protocol version of the recorded frames (`u8`), the [`GameType`] (`u8`),
+//!   and the room name (`u16` length followed by the bytes).
+//! - Any number of records: a direction tag (`u8`), the timestamp in
+//!   microseconds since the start of the recording (`u64`), and the frame
+//!   (`u32` length followed by the bytes).
+//! - An optional index block: the tag `0xFF`, the number of entries (`u32`),
+//!   and for each entry a timestamp (`u64`) and the file offset of the first
+//!   record at or after that timestamp (`u64`).
+//! - A trailer: the offset of the index block (`u64`) followed by the magic
+//!   bytes `AMRI`.
+//!
+//! The index and trailer are only written once [`ReplayWriter::finish`] is
+//! called. Replays without them can still be read from start to finish but
+//! seeking requires scanning all the records before the target time.
+//!
+//! ```
+//! # use std::io::Cursor;
+//! # use std::time::Duration;
+//! # use airmash_protocol::replay::*;
+//! # use airmash_protocol::{ClientPacket, GameType};
+//! # fn main() -> Result<(), ReplayError> {
+//! let header = ReplayHeader::new("ctf1", GameType::CTF);
+//! let mut writer = ReplayWriter::new(Vec::new(), header)?;
+//! writer.write_client(Duration::from_millis(50), &ClientPacket::Ack)?;
+//! let data = writer.finish()?;
+//!
+//! let mut reader = ReplayReader::new(Cursor::new(data))?;
+//! assert_eq!(reader.header().room, "ctf1");
+//!
+//! for item in &mut reader {
+//!   let (time, direction, packet) = item?;
+//!   println!("{:?} {:?} {:?}", time, direction, packet);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod reader;
+mod writer;
+
+#[cfg(test)]
+mod tests;
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+use bstr::BString;
+
+pub use self::reader::ReplayReader;
+pub use self::writer::ReplayWriter;
+use crate::v5;
+use crate::{ClientPacket, GameType, ServerPacket};
+
+const MAGIC: &[u8; 8] = b"AMREPLAY";
+const INDEX_MAGIC: &[u8; 4] = b"AMRI";
+const INDEX_TAG: u8 = 0xFF;
+const TRAILER_LEN: u64 = 12;
+
+/// The version of the replay format written by [`ReplayWriter`].
+pub const FORMAT_VERSION: u16 = 1;
+
+/// Which way a frame was sent.
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum Direction {
+  /// The frame was sent by the client and contains a [`ClientPacket`].
+  ClientToServer,
+  /// The frame was sent by the server and contains a [`ServerPacket`].
+  ServerToClient,
+}
+
+impl Direction {
+  fn tag(self) -> u8 {
+    match self {
+      Self::ClientToServer => 0,
+      Self::ServerToClient => 1,
+    }
+  }
+
+  fn from_tag(tag: u8) -> Option<Self> {
+    match tag {
+      0 => Some(Self::ClientToServer),
+      1 => Some(Self::ServerToClient),
+      _ => None,
+    }
+  }
+}
+
+/// A decoded packet from a replay.
+#[derive(Clone, Debug)]
+pub enum ReplayPacket {
+  Client(ClientPacket),
+  Server(ServerPacket),
+}
+
+/// Metadata stored at the start of every replay.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayHeader {
+  /// The version of the replay format.
+  ///
+  /// This is always set to [`FORMAT_VERSION`] when writing a replay.
+  pub format_version: u16,
+  /// The protocol version used to encode the frames in the replay.
+  pub protocol_version: u8,
+  pub game_type: GameType,
+  pub room: BString,
+}
+
+impl ReplayHeader {
+  /// Create a header for a replay of protocol v5 frames.
+  pub fn new(room: impl Into<BString>, game_type: GameType) -> Self {
+    Self {
+      format_version: FORMAT_VERSION,
+      protocol_version: v5::ProtocolV5::VERSION,
+      game_type,
+      room: room.into(),
+    }
+  }
+}
+
+/// A single undecoded frame from a replay.
+#[derive(Clone, Debug)]
+pub struct ReplayFrame {
+  /// The time at which the frame was sent, relative to the start of the
+  /// recording.
+  pub time: std::time::Duration,
+  pub direction: Direction,
+  pub data: Vec<u8>,
+}
+
+impl ReplayFrame {
+  /// Decode the packet contained within this frame.
+  pub fn decode(&self) -> v5::Result<ReplayPacket> {
+    Ok(match self.direction {
+      Direction::ClientToServer => ReplayPacket::Client(v5::deserialize(&self.data)?),
+      Direction::ServerToClient => ReplayPacket::Server(v5::deserialize(&self.data)?),
+    })
+  }
+}
+
+/// Error returned when reading or writing a replay.
+#[derive(Debug)]
+pub enum ReplayError {
+  /// The underlying reader or writer failed.
+  Io(io::Error),
+  /// A packet could not be serialized or deserialized.
+  Protocol(v5::Error),
+  /// The file does not start with the replay magic bytes.
+  InvalidMagic,
+  /// The replay was written with a newer version of the format.
+  UnsupportedFormat(u16),
+  /// The frames in the replay use a protocol version other than v5 and so
+  /// can't be decoded. They can still be read using
+  /// [`ReplayReader::next_frame`].
+  UnsupportedProtocol(u8),
+  /// The replay contains a record that is not valid.
+  Corrupt(&'static str),
+  /// A value passed to a [`ReplayWriter`] can't be written to a replay.
+  InvalidInput(&'static str),
+}
+
+impl fmt::Display for ReplayError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Io(e) => write!(f, "io error: {}", e),
+      Self::Protocol(e) => e.fmt(f),
+      Self::InvalidMagic => f.write_str("not a replay file"),
+      Self::UnsupportedFormat(v) => write!(f, "unsupported replay format version {}", v),
+      Self::UnsupportedProtocol(v) => write!(f, "unsupported protocol version {}", v),
+      Self::Corrupt(what) => write!(f, "corrupt replay: {}", what),
+      Self::InvalidInput(what) => write!(f, "invalid input: {}", what),
+    }
+  }
+}
+
+impl StdError for ReplayError {
+  fn source(&self) -> Option<&(dyn StdError + 'static)> {
+    match self {
+      Self::Io(e) => Some(e),
+      Self::Protocol(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+impl From<io::Error> for ReplayError {
+  fn from(e: io::Error) -> Self {
+    Self::Io(e)
+  }
+}
+
+impl From<v5::Error> for ReplayError {
+  fn from(e: v5::Error) -> Self {
+    Self::Protocol(e)
+  }
+}
@@ -0,0 +1,280 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::time::Duration;
+
+use super::*;
+
+/// Reads frames from a replay.
+///
+/// Reading does not do any buffering so `reader` should usually be wrapped in
+/// a [`BufReader`](std::io::BufReader).
+pub struct ReplayReader<R: Read> {
+  reader: R,
+  header: ReplayHeader,
+  /// Offset of the first record.
+  start: u64,
+  /// Set once the index block has been reached.
+  done: bool,
+  peeked: Option<ReplayFrame>,
+  index: Option<Vec<(Duration, u64)>>,
+  /// Set once the iterator has returned an `UnsupportedProtocol` error.
+  unsupported_reported: bool,
+}
+
+impl<R: Read> ReplayReader<R> {
+  /// Create a new reader and read the header from `reader`.
+  pub fn new(mut reader: R) -> Result<Self, ReplayError> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+      return Err(ReplayError::InvalidMagic);
+    }
+
+    let mut buf = [0u8; 6];
+    reader.read_exact(&mut buf)?;
+    let format_version = u16::from_le_bytes([buf[0], buf[1]]);
+    if format_version > FORMAT_VERSION {
+      return Err(ReplayError::UnsupportedFormat(format_version));
+    }
+
+    let room_len = u16::from_le_bytes([buf[4], buf[5]]) as usize;
+    let mut room = vec![0u8; room_len];
+    reader.read_exact(&mut room)?;
+
+    Ok(Self {
+      reader,
+      header: ReplayHeader {
+        format_version,
+        protocol_version: buf[2],
+        game_type: buf[3].into(),
+        room: room.into(),
+      },
+      start: (magic.len() + buf.len() + room_len) as u64,
+      done: false,
+      peeked: None,
+      index: None,
+      unsupported_reported: false,
+    })
+  }
+
+  pub fn header(&self) -> &ReplayHeader {
+    &self.header
+  }
+
+  /// Read the next frame without decoding it.
+  ///
+  /// Returns `None` once all the frames in the replay have been read.
+  pub fn next_frame(&mut self) -> Result<Option<ReplayFrame>, ReplayError> {
+    if let Some(frame) = self.peeked.take() {
+      return Ok(Some(frame));
+    }
+    if self.done {
+      return Ok(None);
+    }
+
+    let frame = self.read_frame();
+    if frame.is_err() {
+      // Don't try to read anything after an invalid record since it's not
+      // possible to know where the next record starts.
+      self.done = true;
+    }
+    frame
+  }
+
+  fn read_frame(&mut self) -> Result<Option<ReplayFrame>, ReplayError> {
+    let mut tag = [0u8];
+    let read = loop {
+      match self.reader.read(&mut tag) {
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+        result => break result?,
+      }
+    };
+    if read == 0 {
+      // The replay was not finished properly. Everything up to this point is
+      // still valid though.
+      self.done = true;
+      return Ok(None);
+    }
+
+    let direction = match tag[0] {
+      INDEX_TAG => {
+        self.done = true;
+        return Ok(None);
+      }
+      tag => Direction::from_tag(tag).ok_or(ReplayError::Corrupt("invalid record tag"))?,
+    };
+
+    let mut buf = [0u8; 12];
+    self.reader.read_exact(&mut buf)?;
+    let mut time = [0u8; 8];
+    time.copy_from_slice(&buf[..8]);
+    let len = u32::from_le_bytes([buf[8], buf[9], buf[10], buf[11]]) as usize;
+
+    // Don't trust the length enough to allocate it all up front since a
+    // corrupt record could claim to be up to 4GiB long.
+    let mut data = Vec::new();
+    (&mut self.reader).take(len as u64).read_to_end(&mut data)?;
+    if data.len() != len {
+      return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+
+    Ok(Some(ReplayFrame {
+      time: Duration::from_micros(u64::from_le_bytes(time)),
+      direction,
+      data,
+    }))
+  }
+
+  /// Read and decode the next packet.
+  ///
+  /// Returns `None` once all the packets in the replay have been read.
+  pub fn next_packet(
+    &mut self,
+  ) -> Result<Option<(Duration, Direction, ReplayPacket)>, ReplayError> {
+    // Check this before reading anything so that no frames are lost.
+    if self.header.protocol_version != v5::ProtocolV5::VERSION {
+      return Err(ReplayError::UnsupportedProtocol(
+        self.header.protocol_version,
+      ));
+    }
+
+    let frame = match self.next_frame()? {
+      Some(frame) => frame,
+      None => return Ok(None),
+    };
+
+    Ok(Some((frame.time, frame.direction, frame.decode()?)))
+  }
+
+  /// Get a reference to the underlying reader.
+  pub fn get_ref(&self) -> &R {
+    &self.reader
+  }
+}
+
+impl<R: Read + Seek> ReplayReader<R> {
+  /// Load the seek index from the end of the replay.
+  ///
+  /// Returns `None` if the replay has no index, which happens when the
+  /// recording was not finished properly.
+  fn load_index(&mut self) -> Result<Option<&[(Duration, u64)]>, ReplayError> {
+    if self.index.is_none() {
+      let pos = self.reader.stream_position()?;
+      let index = self.read_index();
+      self.reader.seek(SeekFrom::Start(pos))?;
+      self.index = Some(index?);
+    }
+
+    Ok(self.index.as_deref().filter(|index| !index.is_empty()))
+  }
+
+  fn read_index(&mut self) -> Result<Vec<(Duration, u64)>, ReplayError> {
+    let end = self.reader.seek(SeekFrom::End(0))?;
+    if end < self.start + TRAILER_LEN {
+      return Ok(Vec::new());
+    }
+
+    let mut trailer = [0u8; TRAILER_LEN as usize];
+    self.reader.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+    self.reader.read_exact(&mut trailer)?;
+    if &trailer[8..] != INDEX_MAGIC {
+      return Ok(Vec::new());
+    }
+
+    let mut offset = [0u8; 8];
+    offset.copy_from_slice(&trailer[..8]);
+    let offset = u64::from_le_bytes(offset);
+    if offset < self.start || offset >= end - TRAILER_LEN {
+      return Err(ReplayError::Corrupt("index offset out of bounds"));
+    }
+
+    self.reader.seek(SeekFrom::Start(offset))?;
+    let mut buf = [0u8; 5];
+    self.reader.read_exact(&mut buf)?;
+    if buf[0] != INDEX_TAG {
+      return Err(ReplayError::Corrupt("index offset does not point to index"));
+    }
+
+    let count = u32::from_le_bytes([buf[1], buf[2], buf[3], buf[4]]) as u64;
+    if count * 16 > end - offset {
+      return Err(ReplayError::Corrupt("index is larger than the replay"));
+    }
+
+    let mut index = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+      let mut entry = [0u8; 16];
+      self.reader.read_exact(&mut entry)?;
+
+      let mut time = [0u8; 8];
+      let mut offset = [0u8; 8];
+      time.copy_from_slice(&entry[..8]);
+      offset.copy_from_slice(&entry[8..]);
+      index.push((
+        Duration::from_micros(u64::from_le_bytes(time)),
+        u64::from_le_bytes(offset),
+      ));
+    }
+
+    Ok(index)
+  }
+
+  /// Move the reader so that the next frame read is the first one sent at or
+  /// after `time`.
+  ///
+  /// This uses the index when the replay has one and falls back to scanning
+  /// from the start of the replay otherwise.
+  pub fn seek(&mut self, time: Duration) -> Result<(), ReplayError> {
+    let offset = match self.load_index()? {
+      Some(index) => {
+        let pos = index.partition_point(|&(t, _)| t <= time);
+        index[pos.saturating_sub(1)].1
+      }
+      None => self.start,
+    };
+
+    self.reader.seek(SeekFrom::Start(offset))?;
+    self.done = false;
+    self.peeked = None;
+
+    while let Some(frame) = self.next_frame()? {
+      if frame.time >= time {
+        self.peeked = Some(frame);
+        break;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Move the reader back to the first frame of the replay.
+  pub fn rewind(&mut self) -> Result<(), ReplayError> {
+    self.reader.seek(SeekFrom::Start(self.start))?;
+    self.done = false;
+    self.peeked = None;
+    Ok(())
+  }
+}
+
+impl<R: Read> Iterator for ReplayReader<R> {
+  type Item = Result<(Duration, Direction, ReplayPacket), ReplayError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    match self.next_packet() {
+      // The error would otherwise be returned forever since `next_packet`
+      // doesn't read anything in that case.
+      Err(ReplayError::UnsupportedProtocol(_)) if self.unsupported_reported => None,
+      Err(e @ ReplayError::UnsupportedProtocol(_)) => {
+        self.unsupported_reported = true;
+        Some(Err(e))
+      }
+      result => result.transpose(),
+    }
+  }
+}
+
+impl<R: Read> std::fmt::Debug for ReplayReader<R> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("ReplayReader")
+      .field("header", &self.header)
+      .finish()
+  }
+}
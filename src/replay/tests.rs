@@ -0,0 +1,232 @@
+use std::io::Cursor;
+use std::time::Duration;
+
+use super::*;
+use crate::client::Chat;
+use crate::server::{ChatPublic, Ping};
+
+fn ms(ms: u64) -> Duration {
+  Duration::from_millis(ms)
+}
+
+fn ping(num: u32) -> ServerPacket {
+  ServerPacket::Ping(Ping { clock: num, num })
+}
+
+/// A replay with a ping from the server every 100ms for 10 seconds.
+fn pings(finish: bool) -> Vec<u8> {
+  let mut writer = ReplayWriter::new(Vec::new(), ReplayHeader::new("ffa1", GameType::FFA)).unwrap();
+  for i in 0..100 {
+    writer.write_server(ms(i as u64 * 100), &ping(i)).unwrap();
+  }
+
+  match finish {
+    true => writer.finish().unwrap(),
+    false => writer.get_ref().clone(),
+  }
+}
+
+fn ping_num(item: Option<Result<(Duration, Direction, ReplayPacket), ReplayError>>) -> u32 {
+  match item.unwrap().unwrap() {
+    (_, Direction::ServerToClient, ReplayPacket::Server(ServerPacket::Ping(p))) => p.num,
+    item => panic!("unexpected item {:?}", item),
+  }
+}
+
+#[test]
+fn roundtrip() {
+  let header = ReplayHeader::new("ctf1", GameType::CTF);
+  let mut writer = ReplayWriter::new(Vec::new(), header.clone()).unwrap();
+  writer
+    .write_client(
+      ms(10),
+      &ClientPacket::Chat(Chat {
+        text: "hello".into(),
+      }),
+    )
+    .unwrap();
+  writer
+    .write_server(
+      ms(25),
+      &ServerPacket::ChatPublic(ChatPublic {
+        id: 1,
+        text: "hello".into(),
+      }),
+    )
+    .unwrap();
+  let data = writer.finish().unwrap();
+
+  let mut reader = ReplayReader::new(Cursor::new(data)).unwrap();
+  assert_eq!(reader.header(), &header);
+
+  match reader.next().unwrap().unwrap() {
+    (time, Direction::ClientToServer, ReplayPacket::Client(ClientPacket::Chat(chat))) => {
+      assert_eq!(time, ms(10));
+      assert_eq!(chat.text, "hello");
+    }
+    item => panic!("unexpected item {:?}", item),
+  }
+  match reader.next().unwrap().unwrap() {
+    (time, Direction::ServerToClient, ReplayPacket::Server(ServerPacket::ChatPublic(chat))) => {
+      assert_eq!(time, ms(25));
+      assert_eq!(chat.id, 1);
+    }
+    item => panic!("unexpected item {:?}", item),
+  }
+  assert!(reader.next().is_none());
+}
+
+#[test]
+fn seek_with_index() {
+  let mut reader = ReplayReader::new(Cursor::new(pings(true))).unwrap();
+
+  reader.seek(ms(5050)).unwrap();
+  assert_eq!(ping_num(reader.next()), 51);
+
+  reader.seek(ms(1000)).unwrap();
+  assert_eq!(ping_num(reader.next()), 10);
+
+  reader.seek(ms(0)).unwrap();
+  assert_eq!(ping_num(reader.next()), 0);
+
+  reader.seek(ms(60_000)).unwrap();
+  assert!(reader.next().is_none());
+
+  reader.rewind().unwrap();
+  assert_eq!(reader.count(), 100);
+}
+
+#[test]
+fn unfinished_replay_is_readable() {
+  let mut reader = ReplayReader::new(Cursor::new(pings(false))).unwrap();
+  assert_eq!(ping_num(reader.next()), 0);
+
+  // No index so this has to scan from the start.
+  reader.seek(ms(9900)).unwrap();
+  assert_eq!(ping_num(reader.next()), 99);
+  assert!(reader.next().is_none());
+}
+
+#[test]
+fn writer_rejects_out_of_order_frames() {
+  let mut writer = ReplayWriter::new(Vec::new(), ReplayHeader::new("", GameType::FFA)).unwrap();
+  writer.write_server(ms(100), &ping(0)).unwrap();
+  assert!(matches!(
+    writer.write_server(ms(50), &ping(1)),
+    Err(ReplayError::InvalidInput(_))
+  ));
+}
+
+#[test]
+fn writer_rejects_long_room_name() {
+  let header = ReplayHeader::new(vec![b'a'; 70000], GameType::FFA);
+  assert!(matches!(
+    ReplayWriter::new(Vec::new(), header),
+    Err(ReplayError::InvalidInput(_))
+  ));
+}
+
+#[test]
+fn invalid_magic() {
+  let data = b"NOTAREPLAYFILE".to_vec();
+  assert!(matches!(
+    ReplayReader::new(Cursor::new(data)),
+    Err(ReplayError::InvalidMagic)
+  ));
+}
+
+#[test]
+fn newer_format_is_rejected() {
+  let mut data = pings(true);
+  data[8] = 0xFF;
+
+  assert!(matches!(
+    ReplayReader::new(Cursor::new(data)),
+    Err(ReplayError::UnsupportedFormat(0x00FF))
+  ));
+}
+
+#[test]
+fn unknown_protocol_still_has_frames() {
+  let mut header = ReplayHeader::new("ffa1", GameType::FFA);
+  header.protocol_version = 6;
+  let mut writer = ReplayWriter::new(Vec::new(), header).unwrap();
+  writer
+    .write_frame(ms(0), Direction::ServerToClient, &[1, 2, 3])
+    .unwrap();
+  let data = writer.finish().unwrap();
+
+  let mut reader = ReplayReader::new(Cursor::new(data.clone())).unwrap();
+  assert!(matches!(
+    reader.next(),
+    Some(Err(ReplayError::UnsupportedProtocol(6)))
+  ));
+  assert!(reader.next().is_none());
+
+  // Trying to decode the frames doesn't consume them.
+  let mut reader = ReplayReader::new(Cursor::new(data)).unwrap();
+  for _ in 0..3 {
+    assert!(matches!(
+      reader.next_packet(),
+      Err(ReplayError::UnsupportedProtocol(6))
+    ));
+  }
+  let frame = reader.next_frame().unwrap().unwrap();
+  assert_eq!(frame.data, [1, 2, 3]);
+}
+
+#[test]
+fn interrupted_reads_are_retried() {
+  /// Fails every other read with `Interrupted`.
+  struct Flaky<R> {
+    inner: R,
+    interrupt: bool,
+  }
+
+  impl<R: io::Read> io::Read for Flaky<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+      self.interrupt = !self.interrupt;
+      match self.interrupt {
+        true => Err(io::ErrorKind::Interrupted.into()),
+        false => self.inner.read(buf),
+      }
+    }
+  }
+
+  let reader = Flaky {
+    inner: Cursor::new(pings(true)),
+    interrupt: false,
+  };
+  let packets: Vec<_> = ReplayReader::new(reader).unwrap().collect();
+  assert_eq!(packets.len(), 100);
+  assert!(packets.iter().all(Result::is_ok));
+}
+
+#[test]
+fn truncated_record_is_an_error() {
+  let mut data = pings(false);
+  data.truncate(data.len() - 2);
+
+  let results: Vec<_> = ReplayReader::new(Cursor::new(data)).unwrap().collect();
+  assert_eq!(results.len(), 100);
+  assert!(matches!(results.last(), Some(Err(ReplayError::Io(_)))));
+}
+
+#[test]
+fn oversized_record_length_is_an_error() {
+  let mut writer = ReplayWriter::new(Vec::new(), ReplayHeader::new("", GameType::FFA)).unwrap();
+  writer
+    .write_frame(ms(0), Direction::ServerToClient, &[1, 2, 3])
+    .unwrap();
+  let mut data = writer.get_ref().clone();
+
+  // Claim that the record is nearly 4GiB long.
+  let len = data.len() - 4 - 3;
+  data[len..len + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+  let mut reader = ReplayReader::new(Cursor::new(data)).unwrap();
+  match reader.next_frame() {
+    Err(ReplayError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+    other => panic!("expected an io error, got {:?}", other),
+  }
+}
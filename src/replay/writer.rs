@@ -0,0 +1,133 @@
+use std::convert::TryFrom;
+use std::io::Write;
+use std::time::Duration;
+
+use super::*;
+use crate::v5::SerializeV5;
+
+/// Writes frames to a replay.
+///
+/// Frames must be written in the order that they were sent. The index that
+/// allows readers to seek is only written once [`finish`](Self::finish) is
+/// called.
+pub struct ReplayWriter<W: Write> {
+  writer: W,
+  offset: u64,
+  last_time: Duration,
+  index_interval: Duration,
+  next_index: Duration,
+  index: Vec<(Duration, u64)>,
+}
+
+impl<W: Write> ReplayWriter<W> {
+  /// Create a new writer and write the header to `writer`.
+  ///
+  /// The header's `format_version` is ignored and [`FORMAT_VERSION`] is
+  /// written instead.
+  pub fn new(mut writer: W, header: ReplayHeader) -> Result<Self, ReplayError> {
+    let room_len = u16::try_from(header.room.len())
+      .map_err(|_| ReplayError::InvalidInput("room name too long"))?;
+
+    let mut buf = Vec::with_capacity(MAGIC.len() + 6 + header.room.len());
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    buf.push(header.protocol_version);
+    buf.push(header.game_type.into());
+    buf.extend_from_slice(&room_len.to_le_bytes());
+    buf.extend_from_slice(&header.room);
+    writer.write_all(&buf)?;
+
+    Ok(Self {
+      writer,
+      offset: buf.len() as u64,
+      last_time: Duration::ZERO,
+      index_interval: Duration::from_secs(1),
+      next_index: Duration::ZERO,
+      index: Vec::new(),
+    })
+  }
+
+  /// Set the minimum amount of time between entries in the seek index.
+  ///
+  /// Smaller intervals make seeking faster at the cost of a larger index. The
+  /// default is one second.
+  pub fn set_index_interval(&mut self, interval: Duration) {
+    self.index_interval = interval;
+  }
+
+  /// Write an already-encoded frame.
+  ///
+  /// Returns an error if `time` is earlier than that of the previous frame.
+  pub fn write_frame(
+    &mut self,
+    time: Duration,
+    direction: Direction,
+    data: &[u8],
+  ) -> Result<(), ReplayError> {
+    if time < self.last_time {
+      return Err(ReplayError::InvalidInput(
+        "replay frames must be written in order",
+      ));
+    }
+    let len =
+      u32::try_from(data.len()).map_err(|_| ReplayError::InvalidInput("frame too large"))?;
+
+    if time >= self.next_index {
+      self.index.push((time, self.offset));
+      self.next_index = time + self.index_interval;
+    }
+
+    let mut buf = [0u8; 13];
+    buf[0] = direction.tag();
+    buf[1..9].copy_from_slice(&(time.as_micros() as u64).to_le_bytes());
+    buf[9..].copy_from_slice(&len.to_le_bytes());
+    self.writer.write_all(&buf)?;
+    self.writer.write_all(data)?;
+
+    self.offset += (buf.len() + data.len()) as u64;
+    self.last_time = time;
+    Ok(())
+  }
+
+  /// Encode and write a packet sent by the server.
+  pub fn write_server(&mut self, time: Duration, packet: &ServerPacket) -> Result<(), ReplayError> {
+    self.write_packet(time, Direction::ServerToClient, packet)
+  }
+
+  /// Encode and write a packet sent by the client.
+  pub fn write_client(&mut self, time: Duration, packet: &ClientPacket) -> Result<(), ReplayError> {
+    self.write_packet(time, Direction::ClientToServer, packet)
+  }
+
+  fn write_packet<P: SerializeV5>(
+    &mut self,
+    time: Duration,
+    direction: Direction,
+    packet: &P,
+  ) -> Result<(), ReplayError> {
+    let data = v5::serialize(packet)?;
+    self.write_frame(time, direction, &data)
+  }
+
+  /// Write the index and trailer, flush, and return the underlying writer.
+  pub fn finish(mut self) -> Result<W, ReplayError> {
+    let mut buf = Vec::with_capacity(5 + self.index.len() * 16 + TRAILER_LEN as usize);
+    buf.push(INDEX_TAG);
+    buf.extend_from_slice(&(self.index.len() as u32).to_le_bytes());
+    for (time, offset) in &self.index {
+      buf.extend_from_slice(&(time.as_micros() as u64).to_le_bytes());
+      buf.extend_from_slice(&offset.to_le_bytes());
+    }
+    buf.extend_from_slice(&self.offset.to_le_bytes());
+    buf.extend_from_slice(INDEX_MAGIC);
+
+    self.writer.write_all(&buf)?;
+    self.writer.flush()?;
+    Ok(self.writer)
+  }
+
+  /// Get a reference to the underlying writer.
+  pub fn get_ref(&self) -> &W {
+    &self.writer
+  }
+}
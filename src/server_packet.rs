@@ -64,6 +64,76 @@ pub enum ServerPacket {
   ChatVoteMuted,
   ServerMessage(ServerMessage),
   ServerCustom(ServerCustom),
+
+  #[cfg(feature = "ab-server")]
+  SyncAuth(SyncAuth),
+  #[cfg(feature = "ab-server")]
+  SyncInit(SyncInit),
+  #[cfg(feature = "ab-server")]
+  SyncSubscribe(SyncSubscribe),
+  #[cfg(feature = "ab-server")]
+  SyncUpdate(SyncUpdate),
+
+  /// A packet with an id that this crate doesn't know about.
+  ///
+  /// This is never produced by default. It is only returned when
+  /// deserializing with
+  /// [`unknown_packets_as_raw`](crate::v5::DeserializeOptions::unknown_packets_as_raw)
+  /// enabled. Serializing it writes out `id` followed by `body` unchanged.
+  Unknown {
+    id: u8,
+    body: Vec<u8>,
+  },
+}
+
+/// Whether a packet can be sent byte-for-byte to more than one client.
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum Delivery {
+  /// The packet describes shared game state and, when it is sent, every
+  /// recipient gets identical bytes. Position updates are in this category
+  /// even though they are only sent to the clients that can see the entity.
+  Broadcast,
+  /// The packet is addressed to a single client, either because it is a
+  /// reply to something that the client sent or because it contains data
+  /// about the recipient itself (e.g. its own score or upgrades).
+  PerRecipient,
+}
+
+impl ServerPacket {
+  /// Whether this packet is normally shared between clients.
+  ///
+  /// Packets which are usually targeted at individual clients but carry no
+  /// recipient-specific data (e.g. [`ServerMessage`]) are classified as
+  /// [`Broadcast`](Delivery::Broadcast) since their bytes don't depend on who
+  /// receives them.
+  ///
+  /// This only describes the v5 encoding, which is what
+  /// [`SharedFrame`](crate::v5::SharedFrame) uses. Protocols that keep state
+  /// for each connection may encode the same packet differently for every
+  /// client. For example, [`ProtocolDelta`](crate::delta::ProtocolDelta)
+  /// encodes [`PlayerUpdate`]s against the baseline that each client has
+  /// acknowledged, so its frames must never be shared between clients.
+  pub fn delivery(&self) -> Delivery {
+    use self::ServerPacket::*;
+
+    match self {
+      Login(_) | Login2(_) | Backup | Ping(_) | PingResult(_) | Ack | Error(_)
+      | CommandReply(_) => Delivery::PerRecipient,
+      PlayerUpgrade(_) | PlayerPowerup(_) | GameSpectate(_) | EventLeaveHorizon(_) => {
+        Delivery::PerRecipient
+      }
+      ScoreUpdate(_) | ScoreDetailedFFA(_) | ScoreDetailedCTF(_) | ScoreDetailedBTR(_) => {
+        Delivery::PerRecipient
+      }
+      ChatVoteMuted => Delivery::PerRecipient,
+
+      #[cfg(feature = "ab-server")]
+      SyncAuth(_) | SyncInit(_) | SyncSubscribe(_) | SyncUpdate(_) => Delivery::PerRecipient,
+
+      Unknown { .. } => Delivery::PerRecipient,
+      _ => Delivery::Broadcast,
+    }
+  }
 }
 
 macro_rules! impl_from_newtype {
@@ -124,6 +194,14 @@ impl_from_newtype!(ChatVoteMutePassed);
 impl_from_newtype!(ServerMessage);
 impl_from_newtype!(ServerCustom);
 
+#[cfg(feature = "ab-server")]
+const _: () = {
+  impl_from_newtype!(SyncAuth);
+  impl_from_newtype!(SyncInit);
+  impl_from_newtype!(SyncSubscribe);
+  impl_from_newtype!(SyncUpdate);
+};
+
 impl_from_empty!(Backup);
 impl_from_empty!(Ack);
 impl_from_empty!(ChatVoteMuted);
@@ -0,0 +1,130 @@
+use bstr::BString;
+
+use crate::enums::*;
+use crate::server::{LoginPlayer, PlayerNew};
+use crate::types::*;
+
+/// Everything that the client knows about a player.
+#[derive(Clone, Debug)]
+pub struct PlayerState {
+  pub id: Player,
+  pub name: BString,
+  pub team: Team,
+  pub plane: PlaneType,
+  pub status: PlayerStatus,
+  pub level: Level,
+  pub flag: FlagCode,
+
+  pub pos: Position,
+  pub rot: Rotation,
+  pub speed: Velocity,
+  pub keystate: ServerKeyState,
+  pub upgrades: Upgrades,
+
+  pub health: Health,
+  pub health_regen: HealthRegen,
+  pub energy: Energy,
+  pub energy_regen: EnergyRegen,
+
+  pub score: Score,
+  /// Low-resolution position from the last [`ScoreBoard`] packet. This is
+  /// known even for players that are outside of the current horizon.
+  ///
+  /// [`ScoreBoard`]: crate::server::ScoreBoard
+  pub minimap_pos: Option<Position>,
+  /// The clock value of the last update that was received for this player.
+  pub last_update: u32,
+  /// Whether the player is currently within the horizon of the client. The
+  /// server does not send position updates for players that are outside the
+  /// horizon so `pos` may be out of date.
+  pub in_horizon: bool,
+}
+
+impl PlayerState {
+  fn new(
+    id: Player,
+    name: BString,
+    team: Team,
+    plane: PlaneType,
+    status: PlayerStatus,
+    flag: FlagCode,
+  ) -> Self {
+    Self {
+      id,
+      name,
+      team,
+      plane,
+      status,
+      level: 0,
+      flag,
+      pos: Position::new(0.0, 0.0),
+      rot: 0.0,
+      speed: Velocity::new(0.0, 0.0),
+      keystate: ServerKeyState::default(),
+      upgrades: Upgrades::default(),
+      health: 1.0,
+      health_regen: 0.0,
+      energy: 1.0,
+      energy_regen: 0.0,
+      score: 0,
+      minimap_pos: None,
+      last_update: 0,
+      in_horizon: true,
+    }
+  }
+
+  /// Whether the player is currently alive.
+  pub fn is_alive(&self) -> bool {
+    self.status == PlayerStatus::Alive
+  }
+}
+
+impl From<&LoginPlayer> for PlayerState {
+  fn from(p: &LoginPlayer) -> Self {
+    Self {
+      level: p.level,
+      pos: p.pos,
+      rot: p.rot,
+      upgrades: p.upgrades,
+      ..Self::new(p.id, p.name.clone(), p.team, p.ty, p.status, p.flag)
+    }
+  }
+}
+
+impl From<&PlayerNew> for PlayerState {
+  fn from(p: &PlayerNew) -> Self {
+    Self {
+      pos: p.pos,
+      rot: p.rot,
+      upgrades: p.upgrades,
+      ..Self::new(p.id, p.name.clone(), p.team, p.ty, p.status, p.flag)
+    }
+  }
+}
+
+/// Everything that the client knows about a mob.
+#[derive(Copy, Clone, Debug)]
+pub struct MobState {
+  pub id: Mob,
+  pub ty: MobType,
+  pub pos: Position,
+  pub speed: Velocity,
+  pub accel: Accel,
+  pub max_speed: Speed,
+  /// The player that fired this mob, if known.
+  pub owner: Option<Player>,
+  /// The clock value of the last update that was received for this mob.
+  ///
+  /// This is 0 for stationary mobs (i.e. powerups and upgrades).
+  pub last_update: u32,
+}
+
+/// State of a CTF flag.
+#[derive(Copy, Clone, Debug)]
+pub struct FlagState {
+  /// The team that the flag belongs to.
+  pub team: Team,
+  pub pos: Position,
+  /// The player currently carrying the flag, if any.
+  pub carrier: Option<Player>,
+}
@@ -0,0 +1,64 @@
+use crate::enums::*;
+use crate::types::*;
+
+/// A change to the [`GameState`](super::GameState) caused by applying a
+/// packet.
+///
+/// These are emitted by [`GameState::apply`](super::GameState::apply) so that
+/// UIs don't need to diff the state themselves.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum StateEvent {
+  /// The client logged in and all previous state was discarded.
+  LoggedIn {
+    id: Player,
+  },
+  /// The game type differs from the one that was previously known.
+  GameTypeChanged(GameType),
+
+  PlayerAdded(Player),
+  PlayerRemoved(Player),
+  /// The position, rotation, velocity, or keystate of a player was updated.
+  PlayerMoved(Player),
+  /// The health or energy of a player changed.
+  PlayerStatsChanged(Player),
+  PlayerRespawned(Player),
+  PlayerKilled {
+    id: Player,
+    killer: Option<Player>,
+  },
+  PlayerTeamChanged {
+    id: Player,
+    team: Team,
+  },
+  PlayerPlaneChanged {
+    id: Player,
+    plane: PlaneType,
+  },
+  PlayerFlagChanged {
+    id: Player,
+    flag: FlagCode,
+  },
+  PlayerLevelChanged {
+    id: Player,
+    level: Level,
+  },
+  /// The player is no longer within the horizon of the client.
+  PlayerLeftHorizon(Player),
+
+  MobAdded(Mob),
+  MobUpdated(Mob),
+  MobRemoved(Mob),
+
+  /// A CTF flag moved or was picked up. The team scores may also have changed.
+  FlagUpdated {
+    team: Team,
+  },
+  /// Player scores or the leaderboard changed.
+  ScoresUpdated,
+
+  /// The client started spectating a different player or stopped spectating.
+  SpectatingChanged(Option<Player>),
+  /// The number of players that are still alive changed.
+  PlayersAliveChanged(u16),
+}
@@ -0,0 +1,516 @@
+//! Client-side mirror of the game world.
+//!
+//! [`GameState`] consumes [`ServerPacket`]s in the order they were received
+//! and keeps track of all players, mobs, and flags that the client knows
+//! about. Every call to [`GameState::apply`] returns the [`StateEvent`]s that
+//! describe what changed.
+//!
+//! ```
+//! # use airmash_protocol::state::{GameState, StateEvent};
+//! # use airmash_protocol::{ServerPacket, server::PlayerLeave};
+//! let mut state = GameState::new();
+//!
+//! for event in state.apply(&ServerPacket::PlayerLeave(PlayerLeave { id: 5 })) {
+//!   match event {
+//!     StateEvent::PlayerRemoved(id) => println!("player {} left", id),
+//!     _ => (),
+//!   }
+//! }
+//! ```
+
+mod entities;
+mod event;
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashMap;
+
+use bstr::{BStr, BString, ByteSlice};
+
+pub use self::entities::{FlagState, MobState, PlayerState};
+pub use self::event::StateEvent;
+use crate::enums::*;
+use crate::server::*;
+use crate::types::*;
+use crate::ServerPacket;
+
+/// The state of the game world as seen by a client.
+#[derive(Clone, Debug, Default)]
+pub struct GameState {
+  me: Option<Player>,
+  clock: u32,
+  game_type: GameType,
+  room: BString,
+  spectating: Option<Player>,
+  players_alive: Option<u16>,
+
+  players: HashMap<Player, PlayerState>,
+  mobs: HashMap<Mob, MobState>,
+  flags: HashMap<Team, FlagState>,
+  team_scores: HashMap<Team, u8>,
+  leaderboard: Vec<ScoreBoardData>,
+}
+
+impl GameState {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The id of the current player, if the client has logged in.
+  pub fn me(&self) -> Option<Player> {
+    self.me
+  }
+
+  /// The state of the current player, if the client has logged in.
+  pub fn my_player(&self) -> Option<&PlayerState> {
+    self.me.and_then(|id| self.player(id))
+  }
+
+  /// The clock value of the most recent packet that carried one.
+  pub fn clock(&self) -> u32 {
+    self.clock
+  }
+
+  pub fn game_type(&self) -> GameType {
+    self.game_type
+  }
+
+  pub fn room(&self) -> &BStr {
+    self.room.as_bstr()
+  }
+
+  /// The player currently being spectated, if the client is spectating.
+  pub fn spectating(&self) -> Option<Player> {
+    self.spectating
+  }
+
+  /// The number of players alive as of the last [`GamePlayersAlive`] packet.
+  pub fn players_alive(&self) -> Option<u16> {
+    self.players_alive
+  }
+
+  pub fn player(&self, id: Player) -> Option<&PlayerState> {
+    self.players.get(&id)
+  }
+
+  /// Look up a player by their name.
+  pub fn player_by_name(&self, name: &[u8]) -> Option<&PlayerState> {
+    self.players.values().find(|p| p.name == name)
+  }
+
+  /// All known players, in no particular order.
+  pub fn players(&self) -> impl Iterator<Item = &PlayerState> + '_ {
+    self.players.values()
+  }
+
+  /// All known players on the given team, in no particular order.
+  pub fn team_players(&self, team: Team) -> impl Iterator<Item = &PlayerState> + '_ {
+    self.players().filter(move |p| p.team == team)
+  }
+
+  pub fn mob(&self, id: Mob) -> Option<&MobState> {
+    self.mobs.get(&id)
+  }
+
+  /// All known mobs, in no particular order.
+  pub fn mobs(&self) -> impl Iterator<Item = &MobState> + '_ {
+    self.mobs.values()
+  }
+
+  /// The CTF flag belonging to the given team.
+  pub fn flag(&self, team: Team) -> Option<&FlagState> {
+    self.flags.get(&team)
+  }
+
+  /// All known CTF flags, in no particular order.
+  pub fn flags(&self) -> impl Iterator<Item = &FlagState> + '_ {
+    self.flags.values()
+  }
+
+  /// The CTF score of the given team.
+  pub fn team_score(&self, team: Team) -> Option<u8> {
+    self.team_scores.get(&team).copied()
+  }
+
+  /// The leaderboard as sent in the last [`ScoreBoard`] packet.
+  pub fn leaderboard(&self) -> &[ScoreBoardData] {
+    &self.leaderboard
+  }
+
+  /// Update the state with a packet received from the server and return all
+  /// the changes that resulted.
+  pub fn apply(&mut self, packet: &ServerPacket) -> Vec<StateEvent> {
+    let mut events = Vec::new();
+    self.apply_with(packet, |event| events.push(event));
+    events
+  }
+
+  /// Update the state with a packet received from the server, calling `emit`
+  /// for every resulting change.
+  ///
+  /// Packets that don't affect any of the state tracked here are ignored.
+  /// This includes [`PlayerUpgrade`] and [`PlayerPowerup`] since neither the
+  /// unspent upgrades nor powerup timers of the local player are tracked.
+  ///
+  /// [`PlayerUpgrade`]: crate::server::PlayerUpgrade
+  /// [`PlayerPowerup`]: crate::server::PlayerPowerup
+  pub fn apply_with<F>(&mut self, packet: &ServerPacket, mut emit: F)
+  where
+    F: FnMut(StateEvent),
+  {
+    match packet {
+      ServerPacket::Login(login) => self.login(login, &mut emit),
+      ServerPacket::Login2(login) => self.login(&login.login, &mut emit),
+
+      ServerPacket::PlayerNew(p) => {
+        self.players.insert(p.id, PlayerState::from(p));
+        emit(StateEvent::PlayerAdded(p.id));
+      }
+      ServerPacket::PlayerLeave(p) => {
+        if self.spectating == Some(p.id) {
+          self.set_spectating(None, &mut emit);
+        }
+        if let Some(player) = self.players.remove(&p.id) {
+          emit(StateEvent::PlayerRemoved(player.id));
+        }
+      }
+      ServerPacket::PlayerUpdate(p) => {
+        self.clock = p.clock;
+        if let Some(player) = self.players.get_mut(&p.id) {
+          player.keystate = p.keystate;
+          player.upgrades = p.upgrades;
+          player.pos = p.pos;
+          player.rot = p.rot;
+          player.speed = p.speed;
+          player.last_update = p.clock;
+          player.in_horizon = true;
+          emit(StateEvent::PlayerMoved(p.id));
+        }
+      }
+      ServerPacket::PlayerRespawn(p) => {
+        // Respawning ends spectate mode for the local player.
+        if self.me == Some(p.id) {
+          self.set_spectating(None, &mut emit);
+        }
+        if let Some(player) = self.players.get_mut(&p.id) {
+          player.status = PlayerStatus::Alive;
+          player.pos = p.pos;
+          player.rot = p.rot;
+          player.speed = Velocity::new(0.0, 0.0);
+          player.upgrades = p.upgrades;
+          player.health = 1.0;
+          player.energy = 1.0;
+          player.in_horizon = true;
+          emit(StateEvent::PlayerRespawned(p.id));
+        }
+      }
+      ServerPacket::PlayerKill(p) => {
+        if let Some(player) = self.players.get_mut(&p.id) {
+          player.status = PlayerStatus::Dead;
+          player.pos = p.pos;
+          emit(StateEvent::PlayerKilled {
+            id: p.id,
+            killer: p.killer,
+          });
+        }
+      }
+      ServerPacket::PlayerType(p) => {
+        if let Some(player) = self.players.get_mut(&p.id) {
+          player.plane = p.ty;
+          emit(StateEvent::PlayerPlaneChanged {
+            id: p.id,
+            plane: p.ty,
+          });
+        }
+      }
+      ServerPacket::PlayerFlag(p) => {
+        if let Some(player) = self.players.get_mut(&p.id) {
+          player.flag = p.flag;
+          emit(StateEvent::PlayerFlagChanged {
+            id: p.id,
+            flag: p.flag,
+          });
+        }
+      }
+      ServerPacket::PlayerLevel(p) => {
+        if let Some(player) = self.players.get_mut(&p.id) {
+          player.level = p.level;
+          emit(StateEvent::PlayerLevelChanged {
+            id: p.id,
+            level: p.level,
+          });
+        }
+      }
+      ServerPacket::PlayerReteam(p) => {
+        for reteam in &p.players {
+          if let Some(player) = self.players.get_mut(&reteam.id) {
+            player.team = reteam.team;
+            emit(StateEvent::PlayerTeamChanged {
+              id: reteam.id,
+              team: reteam.team,
+            });
+          }
+        }
+      }
+      ServerPacket::PlayerHit(p) => {
+        for hit in &p.players {
+          if let Some(player) = self.players.get_mut(&hit.id) {
+            player.health = hit.health;
+            player.health_regen = hit.health_regen;
+            emit(StateEvent::PlayerStatsChanged(hit.id));
+          }
+        }
+      }
+      ServerPacket::PlayerFire(p) => {
+        self.clock = p.clock;
+        if let Some(player) = self.players.get_mut(&p.id) {
+          player.energy = p.energy;
+          player.energy_regen = p.energy_regen;
+          emit(StateEvent::PlayerStatsChanged(p.id));
+        }
+
+        for proj in &p.projectiles {
+          self.update_mob(
+            MobState {
+              id: proj.id,
+              ty: proj.ty,
+              pos: proj.pos,
+              speed: proj.speed,
+              accel: proj.accel,
+              max_speed: proj.max_speed,
+              owner: Some(p.id),
+              last_update: p.clock,
+            },
+            &mut emit,
+          );
+        }
+      }
+
+      ServerPacket::EventBoost(p) => {
+        self.clock = p.clock;
+        if let Some(player) = self.players.get_mut(&p.id) {
+          player.keystate.boost = p.boost;
+          player.pos = p.pos;
+          player.rot = p.rot;
+          player.speed = p.speed;
+          player.energy = p.energy;
+          player.energy_regen = p.energy_regen;
+          player.last_update = p.clock;
+          emit(StateEvent::PlayerMoved(p.id));
+          emit(StateEvent::PlayerStatsChanged(p.id));
+        }
+      }
+      ServerPacket::EventBounce(p) => {
+        self.clock = p.clock;
+        if let Some(player) = self.players.get_mut(&p.id) {
+          player.keystate = p.keystate;
+          player.pos = p.pos;
+          player.rot = p.rot;
+          player.speed = p.speed;
+          player.last_update = p.clock;
+          emit(StateEvent::PlayerMoved(p.id));
+        }
+      }
+      ServerPacket::EventStealth(p) => {
+        if let Some(player) = self.players.get_mut(&p.id) {
+          player.keystate.stealth = p.state;
+          player.energy = p.energy;
+          player.energy_regen = p.energy_regen;
+          emit(StateEvent::PlayerStatsChanged(p.id));
+        }
+      }
+      ServerPacket::EventRepel(p) => {
+        self.clock = p.clock;
+        if let Some(player) = self.players.get_mut(&p.id) {
+          player.pos = p.pos;
+          player.rot = p.rot;
+          player.speed = p.speed;
+          player.energy = p.energy;
+          player.energy_regen = p.energy_regen;
+          player.last_update = p.clock;
+          emit(StateEvent::PlayerMoved(p.id));
+        }
+
+        for repel in &p.players {
+          if let Some(player) = self.players.get_mut(&repel.id) {
+            player.keystate = repel.keystate;
+            player.pos = repel.pos;
+            player.rot = repel.rot;
+            player.speed = repel.speed;
+            player.energy = repel.energy;
+            player.energy_regen = repel.energy_regen;
+            player.health = repel.health;
+            player.health_regen = repel.health_regen;
+            player.last_update = p.clock;
+            emit(StateEvent::PlayerMoved(repel.id));
+            emit(StateEvent::PlayerStatsChanged(repel.id));
+          }
+        }
+
+        for mob in &p.mobs {
+          let owner = self.mobs.get(&mob.id).and_then(|m| m.owner);
+          self.update_mob(
+            MobState {
+              id: mob.id,
+              ty: mob.ty,
+              pos: mob.pos,
+              speed: mob.speed,
+              accel: mob.accel,
+              max_speed: mob.max_speed,
+              owner,
+              last_update: p.clock,
+            },
+            &mut emit,
+          );
+        }
+      }
+      ServerPacket::EventLeaveHorizon(p) => match p.ty {
+        LeaveHorizonType::Player => {
+          if let Some(player) = self.players.get_mut(&p.id) {
+            player.in_horizon = false;
+            emit(StateEvent::PlayerLeftHorizon(p.id));
+          }
+        }
+        _ => self.remove_mob(p.id, &mut emit),
+      },
+
+      ServerPacket::MobUpdate(p) => self.mob_update(p, None, &mut emit),
+      ServerPacket::MobUpdate2(p) => self.mob_update(&p.update, Some(p.owner), &mut emit),
+      ServerPacket::MobUpdateStationary(p) => self.update_mob(
+        MobState {
+          id: p.id,
+          ty: p.ty,
+          pos: p.pos,
+          speed: Velocity::new(0.0, 0.0),
+          accel: Accel::new(0.0, 0.0),
+          max_speed: 0.0,
+          owner: None,
+          last_update: 0,
+        },
+        &mut emit,
+      ),
+      ServerPacket::MobDespawn(p) => self.remove_mob(p.id, &mut emit),
+      ServerPacket::MobDespawnCoords(p) => self.remove_mob(p.id, &mut emit),
+
+      ServerPacket::GameFlag(p) => {
+        let team = p.flag as Team;
+        let carrier = match p.ty {
+          FlagUpdateType::Carrier => p.id,
+          _ => None,
+        };
+
+        self.flags.insert(
+          team,
+          FlagState {
+            team,
+            pos: p.pos,
+            carrier,
+          },
+        );
+        self.team_scores.insert(1, p.blueteam);
+        self.team_scores.insert(2, p.redteam);
+        emit(StateEvent::FlagUpdated { team });
+      }
+      ServerPacket::GameSpectate(p) => self.set_spectating(Some(p.id), &mut emit),
+      ServerPacket::GamePlayersAlive(p) if self.players_alive != Some(p.players) => {
+        self.players_alive = Some(p.players);
+        emit(StateEvent::PlayersAliveChanged(p.players));
+      }
+
+      ServerPacket::ScoreUpdate(p) => {
+        if let Some(player) = self.players.get_mut(&p.id) {
+          player.score = p.score;
+          emit(StateEvent::ScoresUpdated);
+        }
+      }
+      ServerPacket::ScoreBoard(p) => {
+        for data in &p.data {
+          if let Some(player) = self.players.get_mut(&data.id) {
+            player.score = data.score;
+            player.level = data.level;
+          }
+        }
+        for ranking in &p.rankings {
+          if let Some(player) = self.players.get_mut(&ranking.id) {
+            player.minimap_pos = ranking.pos;
+          }
+        }
+
+        self.leaderboard.clone_from(&p.data);
+        emit(StateEvent::ScoresUpdated);
+      }
+
+      _ => (),
+    }
+  }
+
+  fn set_spectating(&mut self, spectating: Option<Player>, emit: &mut impl FnMut(StateEvent)) {
+    if self.spectating != spectating {
+      self.spectating = spectating;
+      emit(StateEvent::SpectatingChanged(spectating));
+    }
+  }
+
+  fn login(&mut self, login: &Login, emit: &mut impl FnMut(StateEvent)) {
+    let game_type = self.game_type;
+
+    *self = Self {
+      me: Some(login.id),
+      clock: login.clock,
+      game_type: login.ty,
+      room: login.room.clone(),
+      players: login
+        .players
+        .iter()
+        .map(|p| (p.id, PlayerState::from(p)))
+        .collect(),
+      ..Self::default()
+    };
+
+    emit(StateEvent::LoggedIn { id: login.id });
+    if game_type != login.ty {
+      emit(StateEvent::GameTypeChanged(login.ty));
+    }
+    for player in &login.players {
+      emit(StateEvent::PlayerAdded(player.id));
+    }
+  }
+
+  fn mob_update(
+    &mut self,
+    p: &MobUpdate,
+    owner: Option<Player>,
+    emit: &mut impl FnMut(StateEvent),
+  ) {
+    self.clock = p.clock;
+
+    let owner = owner.or_else(|| self.mobs.get(&p.id).and_then(|m| m.owner));
+    self.update_mob(
+      MobState {
+        id: p.id,
+        ty: p.ty,
+        pos: p.pos,
+        speed: p.speed,
+        accel: p.accel,
+        max_speed: p.max_speed,
+        owner,
+        last_update: p.clock,
+      },
+      emit,
+    );
+  }
+
+  fn update_mob(&mut self, mob: MobState, emit: &mut impl FnMut(StateEvent)) {
+    match self.mobs.insert(mob.id, mob) {
+      Some(_) => emit(StateEvent::MobUpdated(mob.id)),
+      None => emit(StateEvent::MobAdded(mob.id)),
+    }
+  }
+
+  fn remove_mob(&mut self, id: Mob, emit: &mut impl FnMut(StateEvent)) {
+    if self.mobs.remove(&id).is_some() {
+      emit(StateEvent::MobRemoved(id));
+    }
+  }
+}
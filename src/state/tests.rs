@@ -0,0 +1,299 @@
+use super::*;
+
+fn login_player(id: Player, name: &str, team: Team) -> LoginPlayer {
+  LoginPlayer {
+    id,
+    status: PlayerStatus::Alive,
+    level: 0,
+    name: name.into(),
+    ty: PlaneType::Predator,
+    team,
+    pos: Position::new(0.0, 0.0),
+    rot: 0.0,
+    flag: FlagCode::UnitedNations,
+    upgrades: Upgrades::default(),
+  }
+}
+
+fn logged_in() -> GameState {
+  let mut state = GameState::new();
+  state.apply(&ServerPacket::Login(Login {
+    success: true,
+    id: 1,
+    team: 1,
+    clock: 100,
+    token: "token".into(),
+    ty: GameType::CTF,
+    room: "ctf1".into(),
+    players: vec![login_player(1, "me", 1), login_player(2, "other", 2)],
+  }));
+  state
+}
+
+#[test]
+fn login_resets_state() {
+  let mut state = logged_in();
+  state.apply(&ServerPacket::MobUpdateStationary(MobUpdateStationary {
+    id: 50,
+    ty: MobType::Upgrade,
+    pos: Position::new(10.0, 10.0),
+  }));
+
+  let events = state.apply(&ServerPacket::Login(Login {
+    success: true,
+    id: 3,
+    team: 3,
+    clock: 0,
+    token: "token".into(),
+    ty: GameType::FFA,
+    room: "ffa1".into(),
+    players: vec![login_player(3, "me", 3)],
+  }));
+
+  assert_eq!(
+    events,
+    [
+      StateEvent::LoggedIn { id: 3 },
+      StateEvent::GameTypeChanged(GameType::FFA),
+      StateEvent::PlayerAdded(3),
+    ]
+  );
+  assert_eq!(state.me(), Some(3));
+  assert_eq!(state.my_player().unwrap().name, "me");
+  assert_eq!(state.room(), "ffa1");
+  assert_eq!(state.players().count(), 1);
+  assert_eq!(state.mobs().count(), 0);
+}
+
+#[test]
+fn player_lifecycle() {
+  let mut state = logged_in();
+
+  let events = state.apply(&ServerPacket::PlayerNew(PlayerNew {
+    id: 4,
+    status: PlayerStatus::Alive,
+    name: "new".into(),
+    ty: PlaneType::Goliath,
+    team: 1,
+    pos: Position::new(100.0, 200.0),
+    rot: 1.0,
+    flag: FlagCode::Germany,
+    upgrades: Upgrades::default(),
+  }));
+  assert_eq!(events, [StateEvent::PlayerAdded(4)]);
+  assert_eq!(state.player_by_name(b"new").unwrap().id, 4);
+  assert_eq!(state.team_players(1).count(), 2);
+
+  let events = state.apply(&ServerPacket::PlayerKill(PlayerKill {
+    id: 4,
+    killer: Some(2),
+    pos: Position::new(150.0, 250.0),
+  }));
+  assert_eq!(
+    events,
+    [StateEvent::PlayerKilled {
+      id: 4,
+      killer: Some(2)
+    }]
+  );
+  assert!(!state.player(4).unwrap().is_alive());
+
+  let events = state.apply(&ServerPacket::PlayerReteam(PlayerReteam {
+    players: vec![PlayerReteamPlayer { id: 4, team: 2 }],
+  }));
+  assert_eq!(events, [StateEvent::PlayerTeamChanged { id: 4, team: 2 }]);
+  assert_eq!(state.team_players(2).count(), 2);
+
+  let events = state.apply(&ServerPacket::PlayerLeave(PlayerLeave { id: 4 }));
+  assert_eq!(events, [StateEvent::PlayerRemoved(4)]);
+  assert!(state.player(4).is_none());
+
+  // Leaving twice doesn't emit another event
+  assert!(state
+    .apply(&ServerPacket::PlayerLeave(PlayerLeave { id: 4 }))
+    .is_empty());
+}
+
+#[test]
+fn player_update_moves_player() {
+  let mut state = logged_in();
+  state.apply(&ServerPacket::EventLeaveHorizon(EventLeaveHorizon {
+    ty: LeaveHorizonType::Player,
+    id: 2,
+  }));
+  assert!(!state.player(2).unwrap().in_horizon);
+
+  let events = state.apply(&ServerPacket::PlayerUpdate(PlayerUpdate {
+    clock: 200,
+    id: 2,
+    keystate: ServerKeyState::default(),
+    upgrades: Upgrades::default(),
+    pos: Position::new(-300.0, 40.0),
+    rot: 2.0,
+    speed: Velocity::new(1.0, 2.0),
+  }));
+
+  assert_eq!(events, [StateEvent::PlayerMoved(2)]);
+  let player = state.player(2).unwrap();
+  assert_eq!(player.pos, Position::new(-300.0, 40.0));
+  assert_eq!(player.last_update, 200);
+  assert!(player.in_horizon);
+  assert_eq!(state.clock(), 200);
+}
+
+#[test]
+fn projectiles_become_mobs() {
+  let mut state = logged_in();
+
+  let events = state.apply(&ServerPacket::PlayerFire(PlayerFire {
+    clock: 150,
+    id: 2,
+    energy: 0.5,
+    energy_regen: 0.01,
+    projectiles: vec![PlayerFireProjectile {
+      id: 60,
+      ty: MobType::PredatorMissile,
+      pos: Position::new(0.0, 0.0),
+      speed: Velocity::new(0.0, -5.0),
+      accel: Accel::new(0.0, -0.1),
+      max_speed: 9.0,
+    }],
+  }));
+  assert_eq!(
+    events,
+    [StateEvent::PlayerStatsChanged(2), StateEvent::MobAdded(60)]
+  );
+  assert_eq!(state.mob(60).unwrap().owner, Some(2));
+  assert_eq!(state.player(2).unwrap().energy, 0.5);
+
+  let events = state.apply(&ServerPacket::MobUpdate(MobUpdate {
+    clock: 160,
+    id: 60,
+    ty: MobType::PredatorMissile,
+    pos: Position::new(0.0, -50.0),
+    speed: Velocity::new(0.0, -6.0),
+    accel: Accel::new(0.0, -0.1),
+    max_speed: 9.0,
+  }));
+  assert_eq!(events, [StateEvent::MobUpdated(60)]);
+  // The owner is remembered across updates that don't carry it
+  assert_eq!(state.mob(60).unwrap().owner, Some(2));
+
+  let events = state.apply(&ServerPacket::MobDespawnCoords(MobDespawnCoords {
+    id: 60,
+    ty: MobType::PredatorMissile,
+    pos: Position::new(0.0, -60.0),
+  }));
+  assert_eq!(events, [StateEvent::MobRemoved(60)]);
+  assert!(state.mob(60).is_none());
+}
+
+#[test]
+fn flag_carrier_and_scores() {
+  let mut state = logged_in();
+
+  let events = state.apply(&ServerPacket::GameFlag(GameFlag {
+    ty: FlagUpdateType::Carrier,
+    flag: 2,
+    id: Some(1),
+    pos: Position::new(0.0, 0.0),
+    blueteam: 1,
+    redteam: 2,
+  }));
+  assert_eq!(events, [StateEvent::FlagUpdated { team: 2 }]);
+  assert_eq!(state.flag(2).unwrap().carrier, Some(1));
+  assert_eq!(state.team_score(1), Some(1));
+  assert_eq!(state.team_score(2), Some(2));
+
+  state.apply(&ServerPacket::GameFlag(GameFlag {
+    ty: FlagUpdateType::Position,
+    flag: 2,
+    id: Some(1),
+    pos: Position::new(100.0, 0.0),
+    blueteam: 2,
+    redteam: 2,
+  }));
+  assert_eq!(state.flag(2).unwrap().carrier, None);
+  assert_eq!(state.team_score(1), Some(2));
+}
+
+#[test]
+fn scoreboard_updates_players() {
+  let mut state = logged_in();
+
+  let events = state.apply(&ServerPacket::ScoreBoard(ScoreBoard {
+    data: vec![ScoreBoardData {
+      id: 2,
+      score: 500,
+      level: 3,
+    }],
+    rankings: vec![ScoreBoardRanking {
+      id: 2,
+      pos: Some(Position::new(128.0, 256.0)),
+    }],
+  }));
+
+  assert_eq!(events, [StateEvent::ScoresUpdated]);
+  let player = state.player(2).unwrap();
+  assert_eq!(player.score, 500);
+  assert_eq!(player.level, 3);
+  assert_eq!(player.minimap_pos, Some(Position::new(128.0, 256.0)));
+  assert_eq!(state.leaderboard().len(), 1);
+}
+
+#[test]
+fn apply_with_matches_apply() {
+  let mut a = logged_in();
+  let mut b = logged_in();
+  let packet = ServerPacket::PlayerRespawn(PlayerRespawn {
+    id: 2,
+    pos: Position::new(5.0, 5.0),
+    rot: 0.0,
+    upgrades: Upgrades::default(),
+  });
+
+  let mut events = Vec::new();
+  a.apply_with(&packet, |e| events.push(e));
+  assert_eq!(events, b.apply(&packet));
+  assert_eq!(events, [StateEvent::PlayerRespawned(2)]);
+}
+
+#[test]
+fn spectating_is_cleared() {
+  let respawn = |id| {
+    ServerPacket::PlayerRespawn(PlayerRespawn {
+      id,
+      pos: Position::new(0.0, 0.0),
+      rot: 0.0,
+      upgrades: Upgrades::default(),
+    })
+  };
+
+  let mut state = logged_in();
+  let events = state.apply(&ServerPacket::GameSpectate(GameSpectate { id: 2 }));
+  assert_eq!(state.spectating(), Some(2));
+  assert_eq!(events, [StateEvent::SpectatingChanged(Some(2))]);
+
+  // Other players respawning doesn't affect the local player.
+  state.apply(&respawn(2));
+  assert_eq!(state.spectating(), Some(2));
+
+  let events = state.apply(&respawn(1));
+  assert_eq!(state.spectating(), None);
+  assert!(events.contains(&StateEvent::SpectatingChanged(None)));
+
+  state.apply(&ServerPacket::GameSpectate(GameSpectate { id: 2 }));
+  let events = state.apply(&ServerPacket::PlayerLeave(PlayerLeave { id: 2 }));
+  assert_eq!(state.spectating(), None);
+  assert!(events.contains(&StateEvent::SpectatingChanged(None)));
+}
+
+#[test]
+fn players_alive_changes_are_emitted() {
+  let mut state = logged_in();
+  let alive = |players| ServerPacket::GamePlayersAlive(GamePlayersAlive { players });
+
+  assert_eq!(state.apply(&alive(5)), [StateEvent::PlayersAliveChanged(5)]);
+  assert_eq!(state.players_alive(), Some(5));
+  assert!(state.apply(&alive(5)).is_empty());
+}
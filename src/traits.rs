@@ -1,8 +1,19 @@
 use crate::{ClientPacket, ServerPacket};
 
 use std::error::Error;
+use std::sync::Arc;
 
-type ServerPacketIterator = Box<dyn Iterator<Item = Vec<u8>>>;
+/// Iterator over the binary frames produced when serializing a single packet.
+pub type ServerPacketIterator = Box<dyn Iterator<Item = Vec<u8>>>;
+
+/// A type-erased [`Protocol`] with a given set of error types.
+///
+/// This is the type that should be stored when the protocol used by a
+/// connection is only known at runtime (e.g. `Box<DynProtocol>`). Both error
+/// types default to [`v5::Error`](crate::v5::Error) which is what all the
+/// protocols within this crate use.
+pub type DynProtocol<SE = crate::v5::Error, DE = crate::v5::Error> =
+  dyn Protocol<SerializeError = SE, DeserializeError = DE>;
 
 /// Interface to implement for all protocols.
 pub trait Protocol: Sync + Send {
@@ -97,6 +108,20 @@ pub trait Protocol: Sync + Send {
 
   /// Deserialize a binary packet into a server packet.
   fn deserialize_server(&self, data: &[u8]) -> Result<ServerPacket, Self::DeserializeError>;
+
+  /// Create the instance of this protocol that a new connection should use.
+  ///
+  /// Most protocols are stateless and a single instance can be shared by all
+  /// connections, which is what returning `None` (the default) means.
+  /// Protocols which keep state for each connection should return a fresh
+  /// instance here. This is called by
+  /// [`ProtocolRegistry::negotiate`](crate::ProtocolRegistry::negotiate) for
+  /// every connection that it accepts.
+  fn new_connection(
+    &self,
+  ) -> Option<Arc<DynProtocol<Self::SerializeError, Self::DeserializeError>>> {
+    None
+  }
 }
 
 /// Helper trait to make working with protocols easier.
@@ -136,7 +161,7 @@ pub trait ProtocolSerializationExt<T>: Protocol {
 
 impl<T> ProtocolSerializationExt<ServerPacket> for T
 where
-  T: Protocol + Sync + Send,
+  T: Protocol + Sync + Send + ?Sized,
 {
   fn serialize<U>(&self, packet: U) -> Result<ServerPacketIterator, Self::SerializeError>
   where
@@ -152,7 +177,7 @@ where
 
 impl<T> ProtocolSerializationExt<ClientPacket> for T
 where
-  T: Protocol + Sync + Send,
+  T: Protocol + Sync + Send + ?Sized,
 {
   fn serialize<U>(&self, packet: U) -> Result<ServerPacketIterator, Self::SerializeError>
   where
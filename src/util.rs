@@ -32,6 +32,222 @@ pub(crate) fn variant_eq(value: &[u8], variant: &str) -> bool {
   }
 }
 
+/// Check whether `data` is a single valid JSON value.
+///
+/// This is used to validate popup command replies without depending on
+/// `serde_json`, it doesn't build up the parsed value.
+pub(crate) fn is_valid_json(data: &[u8]) -> bool {
+  // Deeper nesting than this is almost certainly a mistake and we don't want
+  // to overflow the stack on malicious input.
+  const MAX_DEPTH: usize = 128;
+
+  struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+  }
+
+  impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+      self.data.get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+      while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+        self.pos += 1;
+      }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+      if self.peek() == Some(byte) {
+        self.pos += 1;
+        true
+      } else {
+        false
+      }
+    }
+
+    fn literal(&mut self, lit: &[u8]) -> bool {
+      if self.data[self.pos..].starts_with(lit) {
+        self.pos += lit.len();
+        true
+      } else {
+        false
+      }
+    }
+
+    fn digits(&mut self) -> bool {
+      let start = self.pos;
+      while let Some(b'0'..=b'9') = self.peek() {
+        self.pos += 1;
+      }
+      self.pos != start
+    }
+
+    fn number(&mut self) -> bool {
+      self.eat(b'-');
+      if !self.eat(b'0') && !self.digits() {
+        return false;
+      }
+      if self.eat(b'.') && !self.digits() {
+        return false;
+      }
+      if self.eat(b'e') || self.eat(b'E') {
+        let _ = self.eat(b'+') || self.eat(b'-');
+        if !self.digits() {
+          return false;
+        }
+      }
+      true
+    }
+
+    fn string(&mut self) -> bool {
+      if !self.eat(b'"') {
+        return false;
+      }
+
+      loop {
+        match self.peek() {
+          None | Some(0x00..=0x1F) => return false,
+          Some(b'"') => {
+            self.pos += 1;
+            return true;
+          }
+          Some(b'\\') => {
+            self.pos += 1;
+            match self.peek() {
+              Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => self.pos += 1,
+              Some(b'u') => {
+                let hex = self.data.get(self.pos + 1..self.pos + 5);
+                match hex {
+                  Some(hex) if hex.iter().all(u8::is_ascii_hexdigit) => self.pos += 5,
+                  _ => return false,
+                }
+              }
+              _ => return false,
+            }
+          }
+          Some(_) => self.pos += 1,
+        }
+      }
+    }
+
+    fn value(&mut self, depth: usize) -> bool {
+      if depth > MAX_DEPTH {
+        return false;
+      }
+
+      self.skip_ws();
+      let valid = match self.peek() {
+        Some(b'{') => self.object(depth),
+        Some(b'[') => self.array(depth),
+        Some(b'"') => self.string(),
+        Some(b't') => self.literal(b"true"),
+        Some(b'f') => self.literal(b"false"),
+        Some(b'n') => self.literal(b"null"),
+        Some(b'-' | b'0'..=b'9') => self.number(),
+        _ => false,
+      };
+      self.skip_ws();
+      valid
+    }
+
+    fn object(&mut self, depth: usize) -> bool {
+      self.pos += 1;
+      self.skip_ws();
+      if self.eat(b'}') {
+        return true;
+      }
+
+      loop {
+        self.skip_ws();
+        if !self.string() {
+          return false;
+        }
+        self.skip_ws();
+        if !self.eat(b':') || !self.value(depth + 1) {
+          return false;
+        }
+        if self.eat(b'}') {
+          return true;
+        }
+        if !self.eat(b',') {
+          return false;
+        }
+      }
+    }
+
+    fn array(&mut self, depth: usize) -> bool {
+      self.pos += 1;
+      self.skip_ws();
+      if self.eat(b']') {
+        return true;
+      }
+
+      loop {
+        if !self.value(depth + 1) {
+          return false;
+        }
+        if self.eat(b']') {
+          return true;
+        }
+        if !self.eat(b',') {
+          return false;
+        }
+      }
+    }
+  }
+
+  // Text fields are arbitrary bytes but JSON has to be UTF-8.
+  if std::str::from_utf8(data).is_err() {
+    return false;
+  }
+
+  let mut parser = Parser { data, pos: 0 };
+  parser.value(0) && parser.pos == data.len()
+}
+
+#[cfg(test)]
+mod json_tests {
+  use super::is_valid_json;
+
+  #[test]
+  fn valid() {
+    for json in [
+      "{}",
+      "[]",
+      " 0 ",
+      "-1.5e+10",
+      "\"text with \\\"escapes\\\" and \\u00e9\"",
+      r#"{"a": [1, 2, {"b": null}], "c": true, "d": false}"#,
+    ] {
+      assert!(is_valid_json(json.as_bytes()), "{}", json);
+    }
+  }
+
+  #[test]
+  fn invalid() {
+    for json in [
+      "",
+      "Command not found",
+      "{",
+      "{}}",
+      "[1, 2,]",
+      r#"{"a" 1}"#,
+      r#"{'a': 1}"#,
+      "01",
+      "1.",
+      "\"unterminated",
+      "\"bad \\x escape\"",
+      "tru",
+    ] {
+      assert!(!is_valid_json(json.as_bytes()), "{}", json);
+    }
+
+    assert!(!is_valid_json(&[b'"', 0xFF, b'"']));
+    assert!(!is_valid_json("[".repeat(1000).as_bytes()));
+  }
+}
+
 #[cfg(test)]
 mod variant_eq_tests {
   use super::variant_eq;
@@ -0,0 +1,654 @@
+//! Zero-copy views of server packets.
+//!
+//! The types within this module mirror the packets within
+//! [`server`](crate::server) but borrow all their text fields directly from
+//! the buffer being deserialized. Arrays are exposed as an [`ArrayRef`] which
+//! decodes its elements on demand while iterating. Deserializing one of these
+//! views does not allocate.
+//!
+//! Packets that don't contain any text or arrays don't have a borrowed
+//! counterpart, [`ServerPacketRef`] uses the owned packet type for those.
+//!
+//! ```
+//! # use airmash_protocol::{ServerPacket, v5};
+//! # use airmash_protocol::server::ChatPublic;
+//! # use airmash_protocol::v5::borrowed::ServerPacketRef;
+//! # fn main() -> Result<(), v5::Error> {
+//! let bytes = v5::serialize(&ServerPacket::ChatPublic(ChatPublic {
+//!   id: 3,
+//!   text: "hello".into(),
+//! }))?;
+//!
+//! let packet: ServerPacketRef = v5::deserialize(&bytes)?;
+//! match packet {
+//!   ServerPacketRef::ChatPublic(chat) => assert_eq!(chat.text, "hello"),
+//!   _ => unreachable!(),
+//! }
+//!
+//! let _owned: ServerPacket = packet.to_owned();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+
+use bstr::BStr;
+
+use crate::enums::*;
+use crate::server::*;
+use crate::types::*;
+use crate::v5::{AirmashDeserializerV5, DeserializeOptions, DeserializeV5, Result};
+use crate::ServerPacket;
+
+/// A lazily-decoded array borrowed from the deserialization buffer.
+///
+/// All the elements of the array have already been validated when the
+/// containing packet was deserialized so iterating over it cannot fail. The
+/// elements are decoded with the same [`DeserializeOptions`] that were used
+/// to validate them.
+pub struct ArrayRef<'de, T> {
+  data: &'de [u8],
+  len: usize,
+  options: DeserializeOptions,
+  _marker: PhantomData<fn() -> T>,
+}
+
+impl<'de, T> ArrayRef<'de, T> {
+  pub(crate) fn new(data: &'de [u8], len: usize, options: DeserializeOptions) -> Self {
+    Self {
+      data,
+      len,
+      options,
+      _marker: PhantomData,
+    }
+  }
+
+  /// The number of elements within the array.
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// The encoded bytes of the array elements, not including the length
+  /// prefix.
+  pub fn as_bytes(&self) -> &'de [u8] {
+    self.data
+  }
+
+  /// Iterate over the elements of the array, decoding them as we go.
+  pub fn iter(&self) -> ArrayIter<'de, T> {
+    ArrayIter {
+      de: AirmashDeserializerV5::with_options(self.data, self.options),
+      remaining: self.len,
+      _marker: PhantomData,
+    }
+  }
+}
+
+impl<'de, T> Copy for ArrayRef<'de, T> {}
+impl<'de, T> Clone for ArrayRef<'de, T> {
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<'de, T> fmt::Debug for ArrayRef<'de, T>
+where
+  T: DeserializeV5<'de> + fmt::Debug,
+{
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_list().entries(self.iter()).finish()
+  }
+}
+
+impl<'de, T: DeserializeV5<'de>> IntoIterator for ArrayRef<'de, T> {
+  type Item = T;
+  type IntoIter = ArrayIter<'de, T>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
+}
+
+impl<'de, T: DeserializeV5<'de>> IntoIterator for &ArrayRef<'de, T> {
+  type Item = T;
+  type IntoIter = ArrayIter<'de, T>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
+}
+
+impl<'de, T, U> From<ArrayRef<'de, T>> for Vec<U>
+where
+  T: DeserializeV5<'de> + Into<U>,
+{
+  fn from(array: ArrayRef<'de, T>) -> Self {
+    array.iter().map(Into::into).collect()
+  }
+}
+
+/// Iterator over the elements of an [`ArrayRef`].
+///
+/// # Panics
+/// Panics if an element fails to decode. This can't happen for arrays created
+/// by the deserializer since their elements were already decoded once with
+/// the same options.
+pub struct ArrayIter<'de, T> {
+  de: AirmashDeserializerV5<'de>,
+  remaining: usize,
+  _marker: PhantomData<fn() -> T>,
+}
+
+impl<'de, T: DeserializeV5<'de>> Iterator for ArrayIter<'de, T> {
+  type Item = T;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.remaining == 0 {
+      return None;
+    }
+
+    self.remaining -= 1;
+    match self.de.deserialize() {
+      Ok(elem) => Some(elem),
+      Err(e) => panic!("validated array element failed to decode: {}", e),
+    }
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<'de, T: DeserializeV5<'de>> ExactSizeIterator for ArrayIter<'de, T> {}
+impl<'de, T: DeserializeV5<'de>> FusedIterator for ArrayIter<'de, T> {}
+
+macro_rules! decl_ref {
+  {$(
+    $( #[$attr:meta] )*
+    struct $name:ident => $owned:ident {
+      $( $field:ident : $ty:ty $( => $de:ident )? ),* $(,)?
+    }
+  )*} => {
+    $(
+      $( #[$attr] )*
+      #[derive(Copy, Clone, Debug)]
+      pub struct $name<'de> {
+        $( pub $field: $ty, )*
+      }
+
+      impl<'de> $name<'de> {
+        #[doc = concat!("Convert this view into an owned [`", stringify!($owned), "`].")]
+        pub fn to_owned(&self) -> $owned {
+          (*self).into()
+        }
+      }
+
+      impl<'de> From<$name<'de>> for $owned {
+        #[allow(clippy::useless_conversion)]
+        fn from(view: $name<'de>) -> Self {
+          Self {
+            $( $field: view.$field.into(), )*
+          }
+        }
+      }
+
+      impl<'de> DeserializeV5<'de> for $name<'de> {
+        fn deserialize(de: &mut AirmashDeserializerV5<'de>) -> Result<Self> {
+          use crate::v5::error::ErrorExt;
+
+          Ok(Self {
+            $(
+              $field: decl_ref!(de = de $( { $de } )?)
+                .with_context(stringify!($field))?,
+            )*
+          })
+        }
+      }
+    )*
+  };
+  { de = $v:ident } => { $v.deserialize() };
+  { de = $v:ident { $de:ident } } => { $v.$de() };
+}
+
+decl_ref! {
+  /// Borrowed view of a [`LoginPlayer`].
+  struct LoginPlayerRef => LoginPlayer {
+    id: Player,
+    status: PlayerStatus,
+    level: Level,
+    name: &'de BStr => deserialize_text_small_ref,
+    ty: PlaneType,
+    team: Team,
+    pos: Position => deserialize_pos,
+    rot: Rotation => deserialize_rot,
+    flag: FlagCode,
+    upgrades: Upgrades,
+  }
+
+  /// Borrowed view of a [`Login`] packet.
+  struct LoginRef => Login {
+    success: bool,
+    id: Player,
+    team: Team,
+    clock: u32,
+    token: &'de BStr => deserialize_text_small_ref,
+    ty: GameType,
+    room: &'de BStr => deserialize_text_small_ref,
+    players: ArrayRef<'de, LoginPlayerRef<'de>> => deserialize_array_large_ref,
+  }
+
+  /// Borrowed view of a [`Login2`] packet.
+  struct Login2Ref => Login2 {
+    login: LoginRef<'de>,
+    config: &'de BStr => deserialize_text_large_ref,
+    bots: ArrayRef<'de, LoginBot> => deserialize_array_large_ref,
+  }
+
+  /// Borrowed view of a [`CommandReply`] packet.
+  struct CommandReplyRef => CommandReply {
+    ty: CommandReplyType,
+    text: &'de BStr => deserialize_text_large_ref,
+  }
+
+  /// Borrowed view of a [`PlayerNew`] packet.
+  struct PlayerNewRef => PlayerNew {
+    id: Player,
+    status: PlayerStatus,
+    name: &'de BStr => deserialize_text_small_ref,
+    ty: PlaneType,
+    team: Team,
+    pos: Position => deserialize_pos,
+    rot: Rotation => deserialize_rot,
+    flag: FlagCode,
+    upgrades: Upgrades,
+  }
+
+  /// Borrowed view of a [`PlayerFire`] packet.
+  struct PlayerFireRef => PlayerFire {
+    clock: u32,
+    id: Player,
+    energy: Energy => deserialize_energy,
+    energy_regen: EnergyRegen => deserialize_regen,
+    projectiles: ArrayRef<'de, PlayerFireProjectile> => deserialize_array_small_ref,
+  }
+
+  /// Borrowed view of a [`PlayerHit`] packet.
+  struct PlayerHitRef => PlayerHit {
+    id: Mob,
+    ty: MobType,
+    pos: Position => deserialize_pos,
+    owner: Player,
+    players: ArrayRef<'de, PlayerHitPlayer> => deserialize_array_small_ref,
+  }
+
+  /// Borrowed view of a [`PlayerReteam`] packet.
+  struct PlayerReteamRef => PlayerReteam {
+    players: ArrayRef<'de, PlayerReteamPlayer> => deserialize_array_large_ref,
+  }
+
+  /// Borrowed view of an [`EventRepel`] packet.
+  struct EventRepelRef => EventRepel {
+    clock: u32,
+    id: Player,
+    pos: Position => deserialize_pos,
+    rot: Rotation => deserialize_rot,
+    speed: Velocity => deserialize_vel,
+    energy: Energy => deserialize_energy,
+    energy_regen: EnergyRegen => deserialize_regen,
+    players: ArrayRef<'de, EventRepelPlayer> => deserialize_array_small_ref,
+    mobs: ArrayRef<'de, EventRepelMob> => deserialize_array_small_ref,
+  }
+
+  /// Borrowed view of a [`ScoreBoard`] packet.
+  struct ScoreBoardRef => ScoreBoard {
+    data: ArrayRef<'de, ScoreBoardData> => deserialize_array_large_ref,
+    rankings: ArrayRef<'de, ScoreBoardRanking> => deserialize_array_large_ref,
+  }
+
+  /// Borrowed view of a [`ScoreDetailedFFA`] packet.
+  struct ScoreDetailedFFARef => ScoreDetailedFFA {
+    scores: ArrayRef<'de, ScoreDetailedFFAEntry> => deserialize_array_large_ref,
+  }
+
+  /// Borrowed view of a [`ScoreDetailedCTF`] packet.
+  struct ScoreDetailedCTFRef => ScoreDetailedCTF {
+    scores: ArrayRef<'de, ScoreDetailedCTFEntry> => deserialize_array_large_ref,
+  }
+
+  /// Borrowed view of a [`ScoreDetailedBTR`] packet.
+  struct ScoreDetailedBTRRef => ScoreDetailedBTR {
+    scores: ArrayRef<'de, ScoreDetailedBTREntry> => deserialize_array_large_ref,
+  }
+
+  /// Borrowed view of a [`ChatTeam`] packet.
+  struct ChatTeamRef => ChatTeam {
+    id: Player,
+    text: &'de BStr => deserialize_text_small_ref,
+  }
+
+  /// Borrowed view of a [`ChatPublic`] packet.
+  struct ChatPublicRef => ChatPublic {
+    id: Player,
+    text: &'de BStr => deserialize_text_small_ref,
+  }
+
+  /// Borrowed view of a [`ChatSay`] packet.
+  struct ChatSayRef => ChatSay {
+    id: Player,
+    text: &'de BStr => deserialize_text_small_ref,
+  }
+
+  /// Borrowed view of a [`ChatWhisper`] packet.
+  struct ChatWhisperRef => ChatWhisper {
+    from: Player,
+    to: Player,
+    text: &'de BStr => deserialize_text_small_ref,
+  }
+
+  /// Borrowed view of a [`ServerMessage`] packet.
+  struct ServerMessageRef => ServerMessage {
+    ty: ServerMessageType,
+    duration: u32,
+    text: &'de BStr => deserialize_text_large_ref,
+  }
+
+  /// Borrowed view of a [`ServerCustom`] packet.
+  struct ServerCustomRef => ServerCustom {
+    ty: ServerCustomType,
+    data: &'de BStr => deserialize_text_large_ref,
+  }
+}
+
+#[cfg(feature = "ab-server")]
+decl_ref! {
+  /// Borrowed view of a [`SyncAuth`] packet.
+  struct SyncAuthRef => SyncAuth {
+    challenge: &'de BStr => deserialize_text_large_ref,
+  }
+
+  /// Borrowed view of a [`SyncSubscribe`] packet.
+  struct SyncSubscribeRef => SyncSubscribe {
+    active: bool,
+    ty: &'de BStr => deserialize_text_small_ref,
+    id: &'de BStr => deserialize_text_small_ref,
+  }
+
+  /// Borrowed view of a [`SyncUpdate`] packet.
+  struct SyncUpdateRef => SyncUpdate {
+    sequence: u32,
+    ty: &'de BStr => deserialize_text_small_ref,
+    id: &'de BStr => deserialize_text_small_ref,
+    data: &'de BStr => deserialize_text_large_ref,
+    timestamp: f64,
+    event: &'de BStr => deserialize_text_large_ref,
+  }
+}
+
+/// Borrowed view of a [`ServerPacket`].
+///
+/// Variants for packets that contain text or arrays hold a borrowed view of
+/// the packet, all other variants hold the same packet type as
+/// [`ServerPacket`] does.
+#[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
+pub enum ServerPacketRef<'de> {
+  Login(LoginRef<'de>),
+  Login2(Login2Ref<'de>),
+  Backup,
+  Ping(Ping),
+  PingResult(PingResult),
+  Ack,
+  Error(Error),
+  CommandReply(CommandReplyRef<'de>),
+  PlayerNew(PlayerNewRef<'de>),
+  PlayerLeave(PlayerLeave),
+  PlayerUpdate(PlayerUpdate),
+  PlayerFire(PlayerFireRef<'de>),
+  PlayerRespawn(PlayerRespawn),
+  PlayerFlag(PlayerFlag),
+  PlayerHit(PlayerHitRef<'de>),
+  PlayerKill(PlayerKill),
+  PlayerUpgrade(PlayerUpgrade),
+  PlayerType(PlayerType),
+  PlayerPowerup(PlayerPowerup),
+  PlayerLevel(PlayerLevel),
+  PlayerReteam(PlayerReteamRef<'de>),
+  GameFlag(GameFlag),
+  GameSpectate(GameSpectate),
+  GamePlayersAlive(GamePlayersAlive),
+  GameFirewall(GameFirewall),
+  EventRepel(EventRepelRef<'de>),
+  EventBoost(EventBoost),
+  EventBounce(EventBounce),
+  EventStealth(EventStealth),
+  EventLeaveHorizon(EventLeaveHorizon),
+  MobUpdate(MobUpdate),
+  MobUpdate2(MobUpdate2),
+  MobUpdateStationary(MobUpdateStationary),
+  MobDespawn(MobDespawn),
+  MobDespawnCoords(MobDespawnCoords),
+  ScoreUpdate(ScoreUpdate),
+  ScoreBoard(ScoreBoardRef<'de>),
+  ScoreDetailedFFA(ScoreDetailedFFARef<'de>),
+  ScoreDetailedCTF(ScoreDetailedCTFRef<'de>),
+  ScoreDetailedBTR(ScoreDetailedBTRRef<'de>),
+  ChatTeam(ChatTeamRef<'de>),
+  ChatPublic(ChatPublicRef<'de>),
+  ChatSay(ChatSayRef<'de>),
+  ChatWhisper(ChatWhisperRef<'de>),
+  ChatVoteMutePassed(ChatVoteMutePassed),
+  ChatVoteMuted,
+  ServerMessage(ServerMessageRef<'de>),
+  ServerCustom(ServerCustomRef<'de>),
+  #[cfg(feature = "ab-server")]
+  SyncAuth(SyncAuthRef<'de>),
+  #[cfg(feature = "ab-server")]
+  SyncInit(SyncInit),
+  #[cfg(feature = "ab-server")]
+  SyncSubscribe(SyncSubscribeRef<'de>),
+  #[cfg(feature = "ab-server")]
+  SyncUpdate(SyncUpdateRef<'de>),
+  Unknown {
+    id: u8,
+    body: &'de [u8],
+  },
+}
+
+macro_rules! packet_ref_to_owned {
+  {
+    $( $( #[$attr:meta] )* $var:ident $( ( $x:ident ) )? ),* $(,)?
+  } => {
+    impl<'de> From<ServerPacketRef<'de>> for ServerPacket {
+      #[allow(clippy::useless_conversion)]
+      fn from(packet: ServerPacketRef<'de>) -> Self {
+        match packet {
+          $( $( #[$attr] )* ServerPacketRef::$var $( ( $x ) )? => ServerPacket::$var $( ( $x.into() ) )?, )*
+          ServerPacketRef::Unknown { id, body } => ServerPacket::Unknown {
+            id,
+            body: body.to_vec(),
+          },
+        }
+      }
+    }
+  }
+}
+
+packet_ref_to_owned! {
+  Login(x),
+  Login2(x),
+  Backup,
+  Ping(x),
+  PingResult(x),
+  Ack,
+  Error(x),
+  CommandReply(x),
+  PlayerNew(x),
+  PlayerLeave(x),
+  PlayerUpdate(x),
+  PlayerFire(x),
+  PlayerRespawn(x),
+  PlayerFlag(x),
+  PlayerHit(x),
+  PlayerKill(x),
+  PlayerUpgrade(x),
+  PlayerType(x),
+  PlayerPowerup(x),
+  PlayerLevel(x),
+  PlayerReteam(x),
+  GameFlag(x),
+  GameSpectate(x),
+  GamePlayersAlive(x),
+  GameFirewall(x),
+  EventRepel(x),
+  EventBoost(x),
+  EventBounce(x),
+  EventStealth(x),
+  EventLeaveHorizon(x),
+  MobUpdate(x),
+  MobUpdate2(x),
+  MobUpdateStationary(x),
+  MobDespawn(x),
+  MobDespawnCoords(x),
+  ScoreUpdate(x),
+  ScoreBoard(x),
+  ScoreDetailedFFA(x),
+  ScoreDetailedCTF(x),
+  ScoreDetailedBTR(x),
+  ChatTeam(x),
+  ChatPublic(x),
+  ChatSay(x),
+  ChatWhisper(x),
+  ChatVoteMutePassed(x),
+  ChatVoteMuted,
+  ServerMessage(x),
+  ServerCustom(x),
+  #[cfg(feature = "ab-server")]
+  SyncAuth(x),
+  #[cfg(feature = "ab-server")]
+  SyncInit(x),
+  #[cfg(feature = "ab-server")]
+  SyncSubscribe(x),
+  #[cfg(feature = "ab-server")]
+  SyncUpdate(x),
+}
+
+impl<'de> ServerPacketRef<'de> {
+  /// Convert this view into an owned [`ServerPacket`].
+  pub fn to_owned(&self) -> ServerPacket {
+    (*self).into()
+  }
+}
+
+packet_deserialize! {
+  enum ServerPacketRef<'de> {
+    // Login(x),
+    // Login2(x),
+    Backup,
+    Ping(x),
+    PingResult(x),
+    Ack,
+    Error(x),
+    CommandReply(x),
+    PlayerNew(x),
+    PlayerLeave(x),
+    PlayerUpdate(x),
+    PlayerFire(x),
+    PlayerRespawn(x),
+    PlayerFlag(x),
+    PlayerHit(x),
+    PlayerKill(x),
+    PlayerUpgrade(x),
+    PlayerType(x),
+    PlayerPowerup(x),
+    PlayerLevel(x),
+    PlayerReteam(x),
+    GameFlag(x),
+    GameSpectate(x),
+    GamePlayersAlive(x),
+    GameFirewall(x),
+    EventRepel(x),
+    EventBoost(x),
+    EventBounce(x),
+    EventStealth(x),
+    EventLeaveHorizon(x),
+    // MobUpdate(x),
+    // MobUpdate2(x),
+    MobUpdateStationary(x),
+    MobDespawn(x),
+    MobDespawnCoords(x),
+    ScoreUpdate(x),
+    ScoreBoard(x),
+    ScoreDetailedFFA(x),
+    ScoreDetailedCTF(x),
+    ScoreDetailedBTR(x),
+    ChatTeam(x),
+    ChatPublic(x),
+    ChatSay(x),
+    ChatWhisper(x),
+    ChatVoteMutePassed(x),
+    ChatVoteMuted,
+    ServerMessage(x),
+    ServerCustom(x),
+    #[cfg(feature = "ab-server")]
+    SyncAuth(x),
+    #[cfg(feature = "ab-server")]
+    SyncInit(x),
+    #[cfg(feature = "ab-server")]
+    SyncSubscribe(x),
+    #[cfg(feature = "ab-server")]
+    SyncUpdate(x),
+  }
+
+  match de {
+    Login::V5_PACKET_NO => {
+      let login = de.deserialize().with_context("Login")?;
+
+      if de.remainder().is_empty() {
+        return Ok(ServerPacketRef::Login(login));
+      }
+
+      let checkpoint = de.checkpoint();
+      let login2 = de.field("Login2", |de| Ok(Login2Ref {
+        login,
+        config: de.field("config", |de| de.deserialize_text_large_ref())?,
+        bots: de.field("bots", |de| de.deserialize_array_large_ref())?,
+      }));
+
+      match login2 {
+        Ok(login2) => Ok(ServerPacketRef::Login2(login2)),
+        Err(_) if de.options().allows_trailing() => {
+          de.restore(checkpoint);
+          Ok(ServerPacketRef::Login(login))
+        }
+        Err(e) => Err(e),
+      }
+    },
+    MobUpdate::V5_PACKET_NO => {
+      let update = de.deserialize().with_context("MobUpdate")?;
+
+      if de.remainder().is_empty() {
+        return Ok(ServerPacketRef::MobUpdate(update));
+      }
+
+      let checkpoint = de.checkpoint();
+      match de.deserialize_u16() {
+        Ok(owner) => Ok(ServerPacketRef::MobUpdate2(MobUpdate2 { update, owner })),
+        Err(_) if de.options().allows_trailing() => {
+          de.restore(checkpoint);
+          Ok(ServerPacketRef::MobUpdate(update))
+        }
+        Err(e) => Err(e.with_context("owner").with_context("MobUpdate2")),
+      }
+    },
+    id if de.options().keeps_unknown_packets() => {
+      let body = de.deserialize_bytes(de.remainder().len())?;
+      Ok(ServerPacketRef::Unknown { id, body })
+    }
+  }
+}
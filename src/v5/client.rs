@@ -79,6 +79,42 @@ decl_consts! {
   const LocalPing = 225;
 }
 
+// Packet ids used by ab-server for its sync protocol. The source for these is
+// cited in the `ab_server` tests in v5/tests.rs.
+#[cfg(feature = "ab-server")]
+decl_consts! {
+  const SyncAuth = 231;
+  const SyncInit = 232;
+  const SyncUpdate = 233;
+  const SyncAck = 234;
+}
+
+#[cfg(feature = "ab-server")]
+decl_serde! {
+  struct SyncAuth {
+    response => { serialize_text_large, deserialize_text_large },
+  }
+
+  struct SyncInit {
+    sequence,
+    timestamp,
+  }
+
+  struct SyncUpdate {
+    sequence,
+    ty => { serialize_text_small, deserialize_text_small },
+    id => { serialize_text_small, deserialize_text_small },
+    data => { serialize_text_large, deserialize_text_large },
+    timestamp,
+    event => { serialize_text_large, deserialize_text_large },
+  }
+
+  struct SyncAck {
+    sequence,
+    result,
+  }
+}
+
 packet_serde! {
   enum ClientPacket {
     Login(x),
@@ -94,6 +130,14 @@ packet_serde! {
     Whisper(x),
     Say(x),
     VoteMute(x),
-    LocalPing(x)
+    LocalPing(x),
+    #[cfg(feature = "ab-server")]
+    SyncAuth(x),
+    #[cfg(feature = "ab-server")]
+    SyncInit(x),
+    #[cfg(feature = "ab-server")]
+    SyncUpdate(x),
+    #[cfg(feature = "ab-server")]
+    SyncAck(x),
   }
 }
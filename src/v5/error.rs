@@ -5,17 +5,42 @@ pub trait ErrorExt {
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[non_exhaustive]
 pub enum ErrorKind {
   EndOfBuffer,
   InvalidEnumValue,
   ArraySizeTooLarge,
   UnexpectedDataRemaining,
+  /// A [`CommandReply`](crate::server::CommandReply) that is to be shown in a
+  /// popup didn't contain valid JSON. The client would silently ignore it.
+  InvalidPopup,
+  /// A value was outside of the range that its field can represent. This is
+  /// only returned by serializers created with
+  /// [`checked`](crate::v5::AirmashSerializerV5::checked).
+  ValueOutOfRange,
+  /// A fixed-size [`SliceSink`](crate::v5::SliceSink) didn't have enough space
+  /// left for the serialized value.
+  BufferFull,
+  /// Writing to an [`IoSink`](crate::v5::IoSink) failed. The underlying error
+  /// can be retrieved from the sink.
+  Io,
 }
 
 #[derive(Clone, Debug)]
 pub struct Error {
   kind: ErrorKind,
   context: Vec<&'static str>,
+  offset: Option<usize>,
+  packet_id: Option<u8>,
+  detail: Detail,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Detail {
+  None,
+  Length { expected: usize, available: usize },
+  Value(u64),
+  Range { original: f64, encoded: f64 },
 }
 
 impl Error {
@@ -23,9 +48,66 @@ impl Error {
     Self {
       kind,
       context: Vec::new(),
+      offset: None,
+      packet_id: None,
+      detail: Detail::None,
+    }
+  }
+
+  /// Create an [`EndOfBuffer`](ErrorKind::EndOfBuffer) error for a read of
+  /// `expected` bytes when only `available` bytes were left.
+  pub(crate) fn end_of_buffer(expected: usize, available: usize) -> Self {
+    Self {
+      detail: Detail::Length {
+        expected,
+        available,
+      },
+      ..Self::new(ErrorKind::EndOfBuffer)
+    }
+  }
+
+  /// Create a [`BufferFull`](ErrorKind::BufferFull) error for a write of
+  /// `expected` bytes when only `available` bytes of space were left.
+  pub(crate) fn buffer_full(expected: usize, available: usize) -> Self {
+    Self {
+      detail: Detail::Length {
+        expected,
+        available,
+      },
+      ..Self::new(ErrorKind::BufferFull)
+    }
+  }
+
+  /// Create an [`InvalidEnumValue`](ErrorKind::InvalidEnumValue) error for
+  /// the given raw value.
+  pub(crate) fn invalid_value(value: impl Into<u64>) -> Self {
+    Self {
+      detail: Detail::Value(value.into()),
+      ..Self::new(ErrorKind::InvalidEnumValue)
     }
   }
 
+  /// Create a [`ValueOutOfRange`](ErrorKind::ValueOutOfRange) error for a
+  /// value that would have been encoded as `encoded`.
+  pub(crate) fn out_of_range(original: f64, encoded: f64) -> Self {
+    Self {
+      detail: Detail::Range { original, encoded },
+      ..Self::new(ErrorKind::ValueOutOfRange)
+    }
+  }
+
+  /// Set the offset at which the error occurred if it isn't already known.
+  pub(crate) fn with_offset(mut self, offset: usize) -> Self {
+    self.offset.get_or_insert(offset);
+    self
+  }
+
+  /// Set the id of the packet being deserialized if it isn't already known.
+  pub(crate) fn with_packet_id(mut self, id: u8) -> Self {
+    self.packet_id.get_or_insert(id);
+    self
+  }
+
   pub fn kind(&self) -> ErrorKind {
     self.kind
   }
@@ -34,12 +116,94 @@ impl Error {
     &self.context[..]
   }
 
+  /// The offset within the buffer at which the error occurred.
+  ///
+  /// For [`InvalidEnumValue`](ErrorKind::InvalidEnumValue) errors this is the
+  /// offset of the start of the invalid value. For
+  /// [`UnexpectedDataRemaining`](ErrorKind::UnexpectedDataRemaining) errors it
+  /// is the offset of the first byte that was not consumed.
+  pub fn offset(&self) -> Option<usize> {
+    self.offset
+  }
+
+  /// The id byte of the packet that was being deserialized, if it was read
+  /// successfully.
+  pub fn packet_id(&self) -> Option<u8> {
+    self.packet_id
+  }
+
+  /// For [`EndOfBuffer`](ErrorKind::EndOfBuffer) and
+  /// [`BufferFull`](ErrorKind::BufferFull) errors, the number of bytes that
+  /// the failing read or write needed.
+  pub fn expected_len(&self) -> Option<usize> {
+    match self.detail {
+      Detail::Length { expected, .. } => Some(expected),
+      _ => None,
+    }
+  }
+
+  /// For [`EndOfBuffer`](ErrorKind::EndOfBuffer) and
+  /// [`BufferFull`](ErrorKind::BufferFull) errors, the number of bytes that
+  /// were left in the buffer.
+  pub fn available_len(&self) -> Option<usize> {
+    match self.detail {
+      Detail::Length { available, .. } => Some(available),
+      _ => None,
+    }
+  }
+
+  /// For [`InvalidEnumValue`](ErrorKind::InvalidEnumValue) errors, the raw
+  /// value that could not be converted.
+  pub fn invalid_enum_value(&self) -> Option<u64> {
+    match self.detail {
+      Detail::Value(value) => Some(value),
+      _ => None,
+    }
+  }
+
+  /// For [`ValueOutOfRange`](ErrorKind::ValueOutOfRange) errors, the value
+  /// that was passed to the serializer.
+  pub fn original_value(&self) -> Option<f64> {
+    match self.detail {
+      Detail::Range { original, .. } => Some(original),
+      _ => None,
+    }
+  }
+
+  /// For [`ValueOutOfRange`](ErrorKind::ValueOutOfRange) errors, the value
+  /// that a client would have decoded.
+  pub fn encoded_value(&self) -> Option<f64> {
+    match self.detail {
+      Detail::Range { encoded, .. } => Some(encoded),
+      _ => None,
+    }
+  }
+
   fn description(&self) -> &str {
     match self.kind() {
       ErrorKind::EndOfBuffer => "reached end of buffer",
       ErrorKind::InvalidEnumValue => "invalid enum value",
       ErrorKind::ArraySizeTooLarge => "array size too large for type",
       ErrorKind::UnexpectedDataRemaining => "data left over after deserialization finished",
+      ErrorKind::InvalidPopup => "popup command reply text is not valid JSON",
+      ErrorKind::ValueOutOfRange => "value out of range for field",
+      ErrorKind::BufferFull => "not enough space left in buffer",
+      ErrorKind::Io => "failed to write to sink",
+    }
+  }
+
+  /// Whether the error can only come from serializing a value, as opposed to
+  /// parsing one.
+  fn is_serialize_error(&self) -> bool {
+    match self.kind() {
+      ErrorKind::EndOfBuffer | ErrorKind::InvalidEnumValue | ErrorKind::UnexpectedDataRemaining => {
+        false
+      }
+      ErrorKind::ArraySizeTooLarge
+      | ErrorKind::InvalidPopup
+      | ErrorKind::ValueOutOfRange
+      | ErrorKind::BufferFull
+      | ErrorKind::Io => true,
     }
   }
 }
@@ -52,7 +216,31 @@ impl std::error::Error for Error {
 
 impl Display for Error {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    writeln!(f, "failed to parse: {}", self.description())?;
+    let action = if self.is_serialize_error() {
+      "serialize"
+    } else {
+      "parse"
+    };
+    write!(f, "failed to {}: {}", action, self.description())?;
+    match self.detail {
+      Detail::Length {
+        expected,
+        available,
+      } => write!(f, " (needed {} bytes, {} available)", expected, available)?,
+      Detail::Value(value) => write!(f, " ({})", value)?,
+      Detail::Range { original, encoded } => {
+        write!(f, " ({} would be encoded as {})", original, encoded)?
+      }
+      Detail::None => (),
+    }
+    writeln!(f)?;
+
+    if let Some(offset) = self.offset {
+      writeln!(f, "At byte offset: {}", offset)?;
+    }
+    if let Some(id) = self.packet_id {
+      writeln!(f, "Packet id: {}", id)?;
+    }
     writeln!(f, "Field stack:")?;
 
     for field in self.context().iter().copied().rev() {
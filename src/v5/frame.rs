@@ -0,0 +1,105 @@
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use super::{AirmashSerializerV5, Result, SerializeV5, Sink};
+use crate::{Delivery, ServerPacket};
+
+/// A server packet that has already been serialized.
+///
+/// The serialized bytes are reference counted so cloning a `SharedFrame` is
+/// cheap. This allows a packet that is going to many clients to be
+/// serialized once and then pushed onto the send queue of every connection.
+///
+/// `SharedFrame` implements [`SerializeV5`] by writing out the stored bytes
+/// so it can be passed anywhere that a packet can, e.g. to `CodecV5` when
+/// the `tokio-codec` feature is enabled.
+///
+/// ```
+/// # use airmash_protocol::v5::{self, SharedFrame};
+/// # use airmash_protocol::{Delivery, ServerPacket, server::PlayerLeave};
+/// # fn main() -> Result<(), v5::Error> {
+/// let packet = ServerPacket::from(PlayerLeave { id: 5 });
+/// let frame = SharedFrame::encode(&packet)?;
+/// assert_eq!(frame.delivery(), Delivery::Broadcast);
+///
+/// let queues: Vec<Vec<SharedFrame>> = (0..100).map(|_| vec![frame.clone()]).collect();
+/// assert_eq!(&queues[42][0][..], &v5::serialize(&packet)?[..]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SharedFrame {
+  data: Arc<[u8]>,
+  delivery: Delivery,
+}
+
+impl SharedFrame {
+  /// Serialize a packet into a new frame.
+  pub fn encode(packet: &ServerPacket) -> Result<Self> {
+    Ok(Self {
+      data: super::serialize(packet)?.into(),
+      delivery: packet.delivery(),
+    })
+  }
+
+  /// Whether the packet this frame was created from can be sent to more than
+  /// one client. See [`ServerPacket::delivery`].
+  pub fn delivery(&self) -> Delivery {
+    self.delivery
+  }
+
+  pub fn is_broadcast(&self) -> bool {
+    self.delivery == Delivery::Broadcast
+  }
+
+  pub fn as_bytes(&self) -> &[u8] {
+    &self.data
+  }
+
+  /// Whether `self` and `other` share the same buffer.
+  pub fn ptr_eq(&self, other: &Self) -> bool {
+    Arc::ptr_eq(&self.data, &other.data)
+  }
+}
+
+impl Deref for SharedFrame {
+  type Target = [u8];
+
+  fn deref(&self) -> &[u8] {
+    &self.data
+  }
+}
+
+impl AsRef<[u8]> for SharedFrame {
+  fn as_ref(&self) -> &[u8] {
+    &self.data
+  }
+}
+
+impl PartialEq for SharedFrame {
+  fn eq(&self, other: &Self) -> bool {
+    self.data == other.data
+  }
+}
+
+impl Eq for SharedFrame {}
+
+impl fmt::Debug for SharedFrame {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("SharedFrame")
+      .field("data", &&self.data[..])
+      .field("delivery", &self.delivery)
+      .finish()
+  }
+}
+
+impl SerializeV5 for SharedFrame {
+  fn serialize<S: Sink + ?Sized>(&self, ser: &mut AirmashSerializerV5<S>) -> Result {
+    ser.serialize_bytes(&self.data)
+  }
+
+  fn encoded_len(&self) -> Result<usize> {
+    Ok(self.data.len())
+  }
+}
@@ -6,15 +6,13 @@ macro_rules! decl_serde {
   )*} => {
     $(
       impl SerializeV5 for $name {
-        fn serialize<'ser>(&self, ser: &mut AirmashSerializerV5<'ser>) -> Result {
-          #[allow(dead_code)]
-          use crate::v5::error::ErrorExt;
-
+        fn serialize<S: crate::v5::Sink + ?Sized>(&self, ser: &mut AirmashSerializerV5<S>) -> Result {
           let Self { $( $field, )* } = self;
 
           $(
-            decl_serde!(ser = ser => $field $( { $ser } )?)
-              .with_context(stringify!($field))?;
+            ser.field(stringify!($field), |ser| {
+              decl_serde!(ser = ser => $field $( { $ser } )?)
+            })?;
           )*
 
           Ok(())
@@ -23,13 +21,11 @@ macro_rules! decl_serde {
 
       impl<'de> DeserializeV5<'de> for $name {
         fn deserialize(de: &mut AirmashDeserializerV5<'de>) -> Result<Self> {
-          #[allow(dead_code)]
-          use crate::v5::error::ErrorExt;
-
           Ok(Self {
             $(
-              $field: decl_serde!(de = de $( { $de } )?)
-                .with_context(stringify!($field))?,
+              $field: de.field(stringify!($field), |de| {
+                decl_serde!(de = de $( { $de } )?)
+              })?,
             )*
           })
         }
@@ -74,22 +70,32 @@ macro_rules! decl_consts {
 macro_rules! packet_serialize {
   {
     enum $name:ident {
-      $( $var:ident $( ( $x:ident ) )? ),* $(,)?
+      $( $( #[$attr:meta] )* $var:ident $( ( $x:ident ) )? ),* $(,)?
     }
+
+    $(
+      match $ser:ident {
+        $( $pat:pat => $result:expr ),* $(,)?
+      }
+    )?
   } => {
     impl SerializeV5 for $name {
-      fn serialize(&self, ser: &mut AirmashSerializerV5) -> Result {
+      fn serialize<S: crate::v5::Sink + ?Sized>(&self, ser: &mut AirmashSerializerV5<S>) -> Result {
         use crate::v5::ErrorExt as _;
 
         match self {
-          $( $name::$var $( ( $x ) )? => {
+          $( $( #[$attr] )* $name::$var $( ( $x ) )? => {
             ser.serialize_u8($var::V5_PACKET_NO)?;
             $(
-              ser.serialize($x)
+              ser.unnamed(stringify!($var), |ser| ser.serialize($x))
                 .with_context(stringify!($name))
                 .with_context(stringify!($var))?;
             )?
           }, )*
+          $($( $pat => {
+            let $ser = &mut *ser;
+            $result?;
+          }, )*)?
         }
 
         Ok(())
@@ -100,37 +106,43 @@ macro_rules! packet_serialize {
 
 macro_rules! packet_deserialize {
   {
-    enum $name:ident {
-      $( $var:ident $( ( $x:ident ) )? ),* $(,)?
+    enum $name:ident $( < $lt:lifetime > )? {
+      $( $( #[$attr:meta] )* $var:ident $( ( $x:ident ) )? ),* $(,)?
     }
 
     match $de:ident {
-      $( $pat:pat => $result:expr ),* $(,)?
+      $( $pat:pat $( if $guard:expr )? => $result:expr ),* $(,)?
     }
   } => {
-    impl<'de> DeserializeV5<'de> for $name {
+    impl<'de> DeserializeV5<'de> for $name $( < $lt > )? {
       fn deserialize($de: &mut AirmashDeserializerV5<'de>) -> Result<Self> {
         use crate::v5::ErrorExt as _;
 
+        let offset = $de.offset();
+        let id = $de.unnamed("<packet id>", |de| de.deserialize_u8())
+          .with_context(stringify!($name))?;
+
         let mut eval = move || {
-          match $de.deserialize_u8()? {
+          match id {
             $(
+              $( #[$attr] )*
               $var::V5_PACKET_NO =>
                 Ok($name::$var $( ({
                   #[allow(unused_variables)]
                   let $x = ();
-                  $de.deserialize()
-                    .with_context(stringify!($var))?
+                  $de.field(stringify!($var), |de| de.deserialize())?
                 }) )?),
             )*
 
-            $( $pat => $result, )*
+            $( $pat $( if $guard )? => $result, )*
 
-            _ => Err(super::Error::new(super::ErrorKind::InvalidEnumValue))
+            _ => Err(super::Error::invalid_value(id).with_offset(offset))
           }
         };
 
-        eval().with_context(stringify!($name))
+        eval()
+          .map_err(|e| e.with_packet_id(id))
+          .with_context(stringify!($name))
       }
     }
   }
@@ -139,19 +151,19 @@ macro_rules! packet_deserialize {
 macro_rules! packet_serde {
   {
     enum $name:ident {
-      $( $var:ident $( ( $x:ident ) )? ),* $(,)?
+      $( $( #[$attr:meta] )* $var:ident $( ( $x:ident ) )? ),* $(,)?
     }
 
   } => {
     packet_serialize! {
       enum $name {
-        $( $var $( ( $x ) )? ),*
+        $( $( #[$attr] )* $var $( ( $x ) )? ),*
       }
     }
 
     packet_deserialize! {
       enum $name {
-        $( $var $( ( $x ) )? ),*
+        $( $( #[$attr] )* $var $( ( $x ) )? ),*
       }
 
       match de {}
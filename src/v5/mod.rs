@@ -3,10 +3,17 @@
 #[macro_use]
 mod macros;
 
+pub mod borrowed;
+pub mod quantize;
+
 mod client;
 mod error;
+mod frame;
+mod options;
 mod protocol;
+mod protocol_v5;
 mod server;
+mod sink;
 mod types;
 
 #[cfg(test)]
@@ -14,23 +21,61 @@ mod tests;
 
 pub type Result<T = ()> = std::result::Result<T, Error>;
 
+pub use self::borrowed::ServerPacketRef;
 pub use self::error::{Error, ErrorExt, ErrorKind};
-pub use self::protocol::{AirmashDeserializerV5, AirmashSerializerV5, DeserializeV5, SerializeV5};
+pub use self::frame::SharedFrame;
+pub use self::options::DeserializeOptions;
+pub use self::protocol::{
+  AirmashDeserializerV5, AirmashSerializerV5, DeserializeV5, FieldSpan, PrecisionCheck,
+  PrecisionLoss, SerializeV5,
+};
+pub use self::protocol_v5::ProtocolV5;
+pub use self::sink::{IoSink, Sink, SliceSink};
 
 pub fn serialize<T: SerializeV5>(value: &T) -> Result<Vec<u8>> {
   let mut data = vec![];
-  let mut ser = AirmashSerializerV5::new(&mut data);
-  ser.serialize(value)?;
+  serialize_into(value, &mut data)?;
   Ok(data)
 }
 
-pub fn deserialize<'de, T: DeserializeV5<'de>>(data: &'de [u8]) -> Result<T> {
-  let mut de = AirmashDeserializerV5::new(data);
-  let val = de.deserialize()?;
+/// Serialize a value into an existing [`Sink`] instead of allocating a new
+/// buffer.
+///
+/// If serialization fails then part of the value may have already been
+/// written to the sink.
+pub fn serialize_into<T: SerializeV5, S: Sink>(value: &T, sink: &mut S) -> Result {
+  AirmashSerializerV5::new(sink).serialize(value)
+}
 
-  if !de.remainder().is_empty() {
-    return Err(Error::new(ErrorKind::UnexpectedDataRemaining));
-  }
+/// Serialize a value, failing if any field is outside of the range that can
+/// be represented on the wire instead of silently clamping it.
+///
+/// ```
+/// # use airmash_protocol::{v5, ServerPacket, server::EventBounce};
+/// # let mut bounce = EventBounce {
+/// #   clock: 0,
+/// #   id: 1,
+/// #   keystate: Default::default(),
+/// #   pos: [0.0, 0.0].into(),
+/// #   rot: 0.0,
+/// #   speed: [0.0, 0.0].into(),
+/// # };
+/// // Just past the right edge of the map.
+/// bounce.pos.x = 16400.0;
+///
+/// let err = v5::serialize_checked(&ServerPacket::from(bounce)).unwrap_err();
+/// assert_eq!(err.kind(), v5::ErrorKind::ValueOutOfRange);
+/// assert_eq!(err.context(), ["x", "pos", "ServerPacket", "EventBounce"]);
+/// assert_eq!(err.original_value(), Some(16400.0));
+/// assert!(err.encoded_value().unwrap() < 16384.0);
+/// ```
+pub fn serialize_checked<T: SerializeV5>(value: &T) -> Result<Vec<u8>> {
+  let mut data = vec![];
+  let mut ser = AirmashSerializerV5::checked(&mut data, PrecisionCheck::Reject);
+  ser.serialize(value)?;
+  Ok(data)
+}
 
-  Ok(val)
+pub fn deserialize<'de, T: DeserializeV5<'de>>(data: &'de [u8]) -> Result<T> {
+  DeserializeOptions::strict().deserialize(data)
 }
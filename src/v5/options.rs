@@ -0,0 +1,107 @@
+use super::{AirmashDeserializerV5, DeserializeV5, Error, ErrorKind, Result};
+
+/// Options controlling how strict deserialization is.
+///
+/// By default deserialization is strict: packets with unknown ids or trailing
+/// bytes are rejected. Clients that want to keep working against servers that
+/// have extended the protocol can relax this.
+///
+/// Enum values that don't correspond to a known variant are kept in the
+/// enum's `Unknown` variant unless
+/// [`reject_unknown_enums`](Self::reject_unknown_enums) is enabled.
+///
+/// ```
+/// # use airmash_protocol::v5::DeserializeOptions;
+/// # use airmash_protocol::ServerPacket;
+/// let options = DeserializeOptions::lenient();
+///
+/// match options.deserialize(&[200, 1, 2, 3]).unwrap() {
+///   ServerPacket::Unknown { id, body } => {
+///     assert_eq!(id, 200);
+///     assert_eq!(body, [1, 2, 3]);
+///   }
+///   _ => unreachable!(),
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct DeserializeOptions {
+  allow_trailing: bool,
+  unknown_packets_as_raw: bool,
+  reject_unknown_enums: bool,
+}
+
+impl DeserializeOptions {
+  /// Reject packets with unknown ids or trailing data. This is the default.
+  ///
+  /// Unknown enum values are still accepted, use
+  /// [`reject_unknown_enums`](Self::reject_unknown_enums) to reject those as
+  /// well.
+  pub const fn strict() -> Self {
+    Self {
+      allow_trailing: false,
+      unknown_packets_as_raw: false,
+      reject_unknown_enums: false,
+    }
+  }
+
+  /// Enable all the options that make deserialization more forgiving.
+  pub const fn lenient() -> Self {
+    Self {
+      allow_trailing: true,
+      unknown_packets_as_raw: true,
+      reject_unknown_enums: false,
+    }
+  }
+
+  /// Whether bytes left over after the known fields of a packet have been
+  /// read should be ignored instead of causing an
+  /// [`UnexpectedDataRemaining`](ErrorKind::UnexpectedDataRemaining) error.
+  pub const fn allow_trailing(mut self, allow: bool) -> Self {
+    self.allow_trailing = allow;
+    self
+  }
+
+  /// Whether server packets with unknown ids should be returned as
+  /// [`ServerPacket::Unknown`](crate::ServerPacket::Unknown) instead of
+  /// causing an [`InvalidEnumValue`](ErrorKind::InvalidEnumValue) error.
+  pub const fn unknown_packets_as_raw(mut self, enable: bool) -> Self {
+    self.unknown_packets_as_raw = enable;
+    self
+  }
+
+  /// Whether enum values that don't correspond to a known variant should
+  /// cause an [`InvalidEnumValue`](ErrorKind::InvalidEnumValue) error.
+  ///
+  /// By default they are kept in the enum's `Unknown` variant so that
+  /// re-serializing a packet produces the same bytes that were received.
+  pub const fn reject_unknown_enums(mut self, enable: bool) -> Self {
+    self.reject_unknown_enums = enable;
+    self
+  }
+
+  pub const fn allows_trailing(&self) -> bool {
+    self.allow_trailing
+  }
+
+  pub const fn keeps_unknown_packets(&self) -> bool {
+    self.unknown_packets_as_raw
+  }
+
+  pub const fn rejects_unknown_enums(&self) -> bool {
+    self.reject_unknown_enums
+  }
+
+  /// Deserialize a value using these options.
+  ///
+  /// This is the equivalent of [`v5::deserialize`](super::deserialize).
+  pub fn deserialize<'de, T: DeserializeV5<'de>>(&self, data: &'de [u8]) -> Result<T> {
+    let mut de = AirmashDeserializerV5::with_options(data, *self);
+    let val = de.deserialize()?;
+
+    if !self.allow_trailing && !de.remainder().is_empty() {
+      return Err(Error::new(ErrorKind::UnexpectedDataRemaining).with_offset(de.offset()));
+    }
+
+    Ok(val)
+  }
+}
@@ -1,11 +1,17 @@
+use std::cmp::Reverse;
+use std::fmt::Write as _;
+use std::ops::Range;
+
 use bstr::{BStr, BString, ByteSlice};
 
-use super::Result;
+use super::borrowed::ArrayRef;
+use super::sink::LenCounter;
+use super::{DeserializeOptions, Result, Sink};
 use crate::types::VectorExt;
 use crate::v5::{Error, ErrorExt as _, ErrorKind};
 use crate::Vector2;
 
-struct ScalarSpec {
+pub(super) struct ScalarSpec {
   shift: i32,
   mult: f32,
 }
@@ -16,36 +22,196 @@ impl ScalarSpec {
   }
 
   fn de(&self, de: &mut AirmashDeserializerV5) -> Result<f32> {
-    Ok((((de.deserialize_u16()? as i32) - self.shift) as f32) * (1.0 / self.mult))
+    Ok(self.decode(de.deserialize_u16()? as i32))
   }
-  fn ser(&self, ser: &mut AirmashSerializerV5, value: f32) -> Result {
-    ser.serialize_u16(((value * self.mult) as i32 + self.shift).clamp(0, u16::MAX as i32) as u16)
+  fn ser<S: Sink + ?Sized>(&self, ser: &mut AirmashSerializerV5<S>, value: f32) -> Result {
+    let raw = self.encode(ser, value, u16::MAX as i32)?;
+    ser.serialize_u16(raw as u16)
   }
 
   fn de_u8(&self, de: &mut AirmashDeserializerV5) -> Result<f32> {
-    Ok((((de.deserialize_u8()? as i32) - self.shift) as f32) * (1.0 / self.mult))
+    Ok(self.decode(de.deserialize_u8()? as i32))
+  }
+  fn ser_u8<S: Sink + ?Sized>(&self, ser: &mut AirmashSerializerV5<S>, value: f32) -> Result {
+    let raw = self.encode(ser, value, u8::MAX as i32)?;
+    ser.serialize_u8(raw as u8)
   }
-  fn ser_u8(&self, ser: &mut AirmashSerializerV5, value: f32) -> Result {
-    ser.serialize_u8(((value * self.mult) as i32 + self.shift).clamp(0, u8::MAX as i32) as u8)
+
+  /// The value that is decoded after `value` has been encoded into a field
+  /// whose largest raw value is `max`.
+  pub(super) fn quantize(&self, value: f32, max: i32) -> f32 {
+    self.decode(self.raw(value, max).0)
   }
+
+  /// Convert `value` to its wire representation, clamped to `0..=max`. Also
+  /// returns whether the value had to be clamped.
+  fn raw(&self, value: f32, max: i32) -> (i32, bool) {
+    let unclamped = ((value * self.mult) as i32).saturating_add(self.shift);
+    let raw = unclamped.clamp(0, max);
+
+    (raw, raw != unclamped || !value.is_finite())
+  }
+
+  fn decode(&self, raw: i32) -> f32 {
+    ((raw - self.shift) as f32) * (1.0 / self.mult)
+  }
+
+  /// Convert `value` to its wire representation, reporting it to `ser` if it
+  /// had to be clamped.
+  fn encode<S: Sink + ?Sized>(
+    &self,
+    ser: &mut AirmashSerializerV5<S>,
+    value: f32,
+    max: i32,
+  ) -> Result<i32> {
+    let (raw, clamped) = self.raw(value, max);
+
+    if clamped {
+      ser.precision_loss(value.into(), self.decode(raw).into())?;
+    }
+
+    Ok(raw)
+  }
+}
+
+pub(super) const ACCEL_SPEC: ScalarSpec = ScalarSpec::new(32768, 32768.0);
+pub(super) const SPEED_SPEC: ScalarSpec = ScalarSpec::new(32768, 1638.4);
+pub(super) const COORD24_SPEC: ScalarSpec = ScalarSpec::new(8388608, 512.0);
+pub(super) const COORDX_SPEC: ScalarSpec = ScalarSpec::new(32768, 2.0);
+pub(super) const COORDY_SPEC: ScalarSpec = ScalarSpec::new(32768, 4.0);
+pub(super) const ENERGY_SPEC: ScalarSpec = ScalarSpec::new(0, 255.0);
+pub(super) const REGEN_SPEC: ScalarSpec = ScalarSpec::new(32768, 1.0e6);
+pub(super) const ROTATION_SPEC: ScalarSpec = ScalarSpec::new(0, 6553.6);
+
+/// How a checked serializer handles values that can't be represented on the
+/// wire.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PrecisionCheck {
+  /// Encode the value anyway and record a [`PrecisionLoss`]. The recorded
+  /// values can be retrieved with
+  /// [`take_precision_loss`](AirmashSerializerV5::take_precision_loss).
+  Record,
+  /// Fail with a [`ValueOutOfRange`](ErrorKind::ValueOutOfRange) error.
+  Reject,
+}
+
+/// A value that was changed when it was encoded because it is outside the
+/// range that its field can represent.
+///
+/// Rounding to the resolution of a field is expected and is not reported.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrecisionLoss {
+  /// The path to the field, e.g. `PlayerUpdate.pos.x`.
+  pub path: String,
+  /// The value that was passed to the serializer.
+  pub original: f64,
+  /// The value that will be decoded from the wire.
+  pub encoded: f64,
 }
 
-const ACCEL_SPEC: ScalarSpec = ScalarSpec::new(32768, 32768.0);
-const SPEED_SPEC: ScalarSpec = ScalarSpec::new(32768, 1638.4);
-const COORD24_SPEC: ScalarSpec = ScalarSpec::new(8388608, 512.0);
-const COORDX_SPEC: ScalarSpec = ScalarSpec::new(32768, 2.0);
-const COORDY_SPEC: ScalarSpec = ScalarSpec::new(32768, 4.0);
-const ENERGY_SPEC: ScalarSpec = ScalarSpec::new(0, 255.0);
-const REGEN_SPEC: ScalarSpec = ScalarSpec::new(32768, 1.0e6);
-const ROTATION_SPEC: ScalarSpec = ScalarSpec::new(0, 6553.6);
-
-pub struct AirmashSerializerV5<'ser> {
-  data: &'ser mut Vec<u8>,
+struct Check {
+  mode: PrecisionCheck,
+  path: Vec<PathSegment>,
+  losses: Vec<PrecisionLoss>,
 }
 
-impl<'ser> AirmashSerializerV5<'ser> {
-  pub fn new(data: &'ser mut Vec<u8>) -> Self {
-    Self { data }
+pub struct AirmashSerializerV5<'ser, S: Sink + ?Sized> {
+  data: &'ser mut S,
+  check: Option<Box<Check>>,
+}
+
+impl<'ser, S: Sink + ?Sized> AirmashSerializerV5<'ser, S> {
+  /// Create a serializer that writes to `data`.
+  pub fn new(data: &'ser mut S) -> Self {
+    Self { data, check: None }
+  }
+
+  /// Create a serializer that checks every value against the range of the
+  /// field it is written to.
+  ///
+  /// By default values that are out of range are silently clamped (e.g. a
+  /// position beyond the map bounds is moved to the edge of the map). With
+  /// a checked serializer they are either recorded or rejected depending on
+  /// `mode`.
+  pub fn checked(data: &'ser mut S, mode: PrecisionCheck) -> Self {
+    Self {
+      data,
+      check: Some(Box::new(Check {
+        mode,
+        path: Vec::new(),
+        losses: Vec::new(),
+      })),
+    }
+  }
+
+  /// Take all the [`PrecisionLoss`]es that have been recorded so far.
+  ///
+  /// This always returns an empty vector unless the serializer was created
+  /// with [`checked`](Self::checked) using [`PrecisionCheck::Record`].
+  pub fn take_precision_loss(&mut self) -> Vec<PrecisionLoss> {
+    match &mut self.check {
+      Some(check) => std::mem::take(&mut check.losses),
+      None => Vec::new(),
+    }
+  }
+
+  /// Report that `original` could not be represented and was encoded as
+  /// `encoded` instead.
+  pub(crate) fn precision_loss(&mut self, original: f64, encoded: f64) -> Result {
+    let check = match &mut self.check {
+      Some(check) => check,
+      None => return Ok(()),
+    };
+
+    match check.mode {
+      PrecisionCheck::Reject => Err(Error::out_of_range(original, encoded)),
+      PrecisionCheck::Record => {
+        check.losses.push(PrecisionLoss {
+          path: format_path(&check.path),
+          original,
+          encoded,
+        });
+        Ok(())
+      }
+    }
+  }
+
+  /// Serialize a field using `func`, adding `name` to the error context if
+  /// it fails.
+  pub(crate) fn field<T, F>(&mut self, name: &'static str, func: F) -> Result<T>
+  where
+    F: FnOnce(&mut Self) -> Result<T>,
+  {
+    self
+      .scoped(PathSegment::Field(name), func)
+      .with_context(name)
+  }
+
+  /// Record `name` in the path of any [`PrecisionLoss`] reported by `func`
+  /// without adding anything to the error context.
+  pub(crate) fn unnamed<T, F>(&mut self, name: &'static str, func: F) -> Result<T>
+  where
+    F: FnOnce(&mut Self) -> Result<T>,
+  {
+    self.scoped(PathSegment::Field(name), func)
+  }
+
+  fn scoped<T, F>(&mut self, segment: PathSegment, func: F) -> Result<T>
+  where
+    F: FnOnce(&mut Self) -> Result<T>,
+  {
+    match &mut self.check {
+      Some(check) => check.path.push(segment),
+      None => return func(self),
+    }
+
+    let result = func(self);
+
+    if let Some(check) = &mut self.check {
+      check.path.pop();
+    }
+
+    result
   }
 
   pub fn serialize<T: SerializeV5>(&mut self, value: &T) -> Result {
@@ -53,8 +219,7 @@ impl<'ser> AirmashSerializerV5<'ser> {
   }
 
   pub fn serialize_bytes(&mut self, bytes: &[u8]) -> Result {
-    self.data.extend_from_slice(bytes);
-    Ok(())
+    self.data.write_bytes(bytes)
   }
 
   pub fn serialize_u8(&mut self, value: u8) -> Result {
@@ -114,8 +279,10 @@ impl<'ser> AirmashSerializerV5<'ser> {
 
     self.serialize_u8(data.len() as u8)?;
 
-    for elem in data {
-      self.serialize(elem).with_context("<array element>")?;
+    for (idx, elem) in data.iter().enumerate() {
+      self
+        .scoped(PathSegment::Index(idx), |ser| ser.serialize(elem))
+        .with_context("<array element>")?;
     }
 
     Ok(())
@@ -130,8 +297,10 @@ impl<'ser> AirmashSerializerV5<'ser> {
 
     self.serialize_u16(data.len() as u16)?;
 
-    for elem in data {
-      self.serialize(elem).with_context("<array element>")?;
+    for (idx, elem) in data.iter().enumerate() {
+      self
+        .scoped(PathSegment::Index(idx), |ser| ser.serialize(elem))
+        .with_context("<array element>")?;
     }
 
     Ok(())
@@ -155,38 +324,52 @@ impl<'ser> AirmashSerializerV5<'ser> {
   }
 
   pub fn serialize_accel(&mut self, v: Vector2) -> Result {
-    ACCEL_SPEC.ser(self, v.x)?;
-    ACCEL_SPEC.ser(self, v.y)
+    self.field("x", |ser| ACCEL_SPEC.ser(ser, v.x))?;
+    self.field("y", |ser| ACCEL_SPEC.ser(ser, v.y))
   }
   pub fn serialize_low_res_pos(&mut self, pos: Option<Vector2>) -> Result {
-    let x = pos.map(|v| ((v.x / 128.0) as i32 + 128) as u8).unwrap_or(0);
-    let y = pos.map(|v| ((v.y / 128.0) as i32 + 128) as u8).unwrap_or(0);
+    let (x, y) = match pos {
+      Some(pos) => (
+        self.field("x", |ser| ser.serialize_low_res(pos.x))?,
+        self.field("y", |ser| ser.serialize_low_res(pos.y))?,
+      ),
+      None => (0, 0),
+    };
 
     x.serialize(self)?;
     y.serialize(self)
   }
+  fn serialize_low_res(&mut self, v: f32) -> Result<u8> {
+    let raw = (v / 128.0) as i32 + 128;
+    let wrapped = raw as u8;
+
+    if raw != wrapped as i32 || !v.is_finite() {
+      self.precision_loss(v.into(), ((wrapped as i32 - 128) * 128).into())?;
+    }
+
+    Ok(wrapped)
+  }
   pub fn serialize_pos_f32(&mut self, pos: Vector2) -> Result {
     self.serialize_f32(pos.x)?;
     self.serialize_f32(pos.y)
   }
   pub fn serialize_pos(&mut self, pos: Vector2) -> Result {
-    self.serialize_coordx(pos.x)?;
-    self.serialize_coordy(pos.y)
+    self.field("x", |ser| ser.serialize_coordx(pos.x))?;
+    self.field("y", |ser| ser.serialize_coordy(pos.y))
   }
 
   pub fn serialize_pos24(&mut self, pos: Vector2) -> Result {
-    self.serialize_coord24(pos.x)?;
-    self.serialize_coord24(pos.y)
+    self.field("x", |ser| ser.serialize_coord24(pos.x))?;
+    self.field("y", |ser| ser.serialize_coord24(pos.y))
   }
   pub fn serialize_vel(&mut self, pos: Vector2) -> Result {
-    self.serialize_speed(pos.x)?;
-    self.serialize_speed(pos.y)
+    self.field("x", |ser| ser.serialize_speed(pos.x))?;
+    self.field("y", |ser| ser.serialize_speed(pos.y))
   }
 
   pub fn serialize_coord24(&mut self, v: f32) -> Result {
-    let spec = COORD24_SPEC;
-    self
-      .serialize_u24(((v * spec.mult) as i32 + spec.shift).clamp(0, (u32::MAX >> 8) as i32) as u32)
+    let raw = COORD24_SPEC.encode(self, v, (u32::MAX >> 8) as i32)?;
+    self.serialize_u24(raw as u32)
   }
   pub fn serialize_coordx(&mut self, v: f32) -> Result {
     COORDX_SPEC.ser(self, v)
@@ -212,46 +395,204 @@ impl<'ser> AirmashSerializerV5<'ser> {
   }
 }
 
+/// The bytes that a single field was decoded from.
+///
+/// These are recorded by deserializers created with
+/// [`AirmashDeserializerV5::with_trace`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldSpan {
+  /// The path to the field, e.g. `PlayerReteam.players[1].team`.
+  pub path: String,
+  /// How deeply nested the field is.
+  pub depth: usize,
+  /// The offsets of the bytes that the field was decoded from.
+  pub range: Range<usize>,
+}
+
+#[derive(Copy, Clone)]
+enum PathSegment {
+  Field(&'static str),
+  Index(usize),
+}
+
+#[derive(Default)]
+struct Trace {
+  path: Vec<PathSegment>,
+  spans: Vec<FieldSpan>,
+}
+
+fn format_path(segments: &[PathSegment]) -> String {
+  let mut path = String::new();
+  for segment in segments {
+    match segment {
+      PathSegment::Field(name) if path.is_empty() => path.push_str(name),
+      PathSegment::Field(name) => {
+        path.push('.');
+        path.push_str(name);
+      }
+      PathSegment::Index(idx) => {
+        let _ = write!(path, "[{}]", idx);
+      }
+    }
+  }
+  path
+}
+
 pub struct AirmashDeserializerV5<'de> {
   data: &'de [u8],
+  full: &'de [u8],
+  trace: Option<Box<Trace>>,
+  options: DeserializeOptions,
 }
 
 impl<'de> AirmashDeserializerV5<'de> {
   pub fn new(data: &'de [u8]) -> Self {
-    Self { data }
+    Self::with_options(data, DeserializeOptions::default())
+  }
+
+  /// Create a deserializer which uses `options` instead of the default strict
+  /// options.
+  pub fn with_options(data: &'de [u8], options: DeserializeOptions) -> Self {
+    Self {
+      data,
+      full: data,
+      trace: None,
+      options,
+    }
+  }
+
+  pub fn options(&self) -> &DeserializeOptions {
+    &self.options
+  }
+
+  /// Create a deserializer that records which bytes every field was decoded
+  /// from. The recorded spans can be retrieved with
+  /// [`take_trace`](Self::take_trace).
+  ///
+  /// This is much slower than regular deserialization and is meant for
+  /// debugging tools.
+  pub fn with_trace(data: &'de [u8]) -> Self {
+    Self {
+      trace: Some(Box::default()),
+      ..Self::new(data)
+    }
   }
 
   pub fn remainder(&self) -> &'de [u8] {
     self.data
   }
 
+  /// The number of bytes that have been consumed so far.
+  pub fn offset(&self) -> usize {
+    self.full.len() - self.data.len()
+  }
+
+  /// Take all the field spans that have been recorded so far, ordered by
+  /// their position within the buffer.
+  ///
+  /// Fields that failed to deserialize are not included. This always returns
+  /// an empty vector if the deserializer was not created with
+  /// [`with_trace`](Self::with_trace).
+  pub fn take_trace(&mut self) -> Vec<FieldSpan> {
+    let mut spans = match &mut self.trace {
+      Some(trace) => std::mem::take(&mut trace.spans),
+      None => return Vec::new(),
+    };
+
+    spans.sort_by_key(|span| (span.range.start, Reverse(span.range.end), span.depth));
+    spans
+  }
+
   pub fn checkpoint(&self) -> &'de [u8] {
     self.remainder()
   }
   pub fn restore(&mut self, checkpoint: &'de [u8]) {
     self.data = checkpoint;
+
+    let offset = self.offset();
+    if let Some(trace) = &mut self.trace {
+      trace.spans.retain(|span| span.range.end <= offset);
+    }
+  }
+
+  /// Deserialize a field using `func`, adding `name` to the error context if
+  /// it fails.
+  pub(crate) fn field<T, F>(&mut self, name: &'static str, func: F) -> Result<T>
+  where
+    F: FnOnce(&mut Self) -> Result<T>,
+  {
+    self
+      .traced(PathSegment::Field(name), func)
+      .with_context(name)
+  }
+
+  /// Record the span of `func` without adding anything to the error context.
+  pub(crate) fn unnamed<T, F>(&mut self, name: &'static str, func: F) -> Result<T>
+  where
+    F: FnOnce(&mut Self) -> Result<T>,
+  {
+    self.traced(PathSegment::Field(name), func)
+  }
+
+  fn traced<T, F>(&mut self, segment: PathSegment, func: F) -> Result<T>
+  where
+    F: FnOnce(&mut Self) -> Result<T>,
+  {
+    match &mut self.trace {
+      Some(trace) => trace.path.push(segment),
+      None => return func(self),
+    }
+
+    let start = self.offset();
+    let result = func(self);
+    let end = self.offset();
+
+    if let Some(trace) = &mut self.trace {
+      if result.is_ok() {
+        let span = FieldSpan {
+          path: format_path(&trace.path),
+          depth: trace.path.len() - 1,
+          range: start..end,
+        };
+        trace.spans.push(span);
+      }
+      trace.path.pop();
+    }
+
+    result
+  }
+
+  fn deserialize_element<T>(&mut self, index: usize) -> Result<T>
+  where
+    T: DeserializeV5<'de>,
+  {
+    self.traced(PathSegment::Index(index), |de| de.deserialize())
   }
 
   pub fn deserialize<T: DeserializeV5<'de>>(&mut self) -> Result<T> {
     T::deserialize(self)
   }
 
+  fn end_of_buffer(&self, expected: usize) -> Error {
+    Error::end_of_buffer(expected, self.data.len()).with_offset(self.offset())
+  }
+
   pub fn deserialize_fixed<const N: usize>(&mut self) -> Result<[u8; N]> {
     use std::convert::TryInto;
 
     if self.data.len() < N {
-      return Err(Error::new(ErrorKind::EndOfBuffer));
+      return Err(self.end_of_buffer(N));
     }
 
     let slice: [u8; N] = self.data[..N]
       .try_into()
-      .map_err(|_| Error::new(ErrorKind::EndOfBuffer))?;
+      .map_err(|_| self.end_of_buffer(N))?;
     self.data = &self.data[N..];
     Ok(slice)
   }
   pub fn deserialize_bytes(&mut self, len: usize) -> Result<&'de [u8]> {
     if self.data.len() < len {
-      return Err(Error::new(ErrorKind::EndOfBuffer));
+      return Err(self.end_of_buffer(len));
     }
 
     let slice = &self.data[..len];
@@ -261,7 +602,7 @@ impl<'de> AirmashDeserializerV5<'de> {
 
   pub fn deserialize_u8(&mut self) -> Result<u8> {
     if self.data.is_empty() {
-      return Err(Error::new(ErrorKind::EndOfBuffer));
+      return Err(self.end_of_buffer(1));
     }
 
     let value = self.data[0];
@@ -301,7 +642,7 @@ impl<'de> AirmashDeserializerV5<'de> {
     self.deserialize_fixed().map(f32::from_le_bytes)
   }
   pub fn deserialize_f64(&mut self) -> Result<f64> {
-    self.deserialize_fixed().map(f64::from_be_bytes)
+    self.deserialize_fixed().map(f64::from_le_bytes)
   }
 
   pub fn deserialize_u24(&mut self) -> Result<u32> {
@@ -321,8 +662,8 @@ impl<'de> AirmashDeserializerV5<'de> {
     let len = self.deserialize_u8()? as usize;
     let mut data = Vec::with_capacity(len);
 
-    for _ in 0..len {
-      data.push(self.deserialize()?);
+    for i in 0..len {
+      data.push(self.deserialize_element(i)?);
     }
 
     Ok(data)
@@ -334,20 +675,55 @@ impl<'de> AirmashDeserializerV5<'de> {
     let len = self.deserialize_u16()? as usize;
     let mut data = Vec::with_capacity(len);
 
-    for _ in 0..len {
-      data.push(self.deserialize()?);
+    for i in 0..len {
+      data.push(self.deserialize_element(i)?);
     }
 
     Ok(data)
   }
 
-  pub fn deserialize_text_small(&mut self) -> Result<BString> {
+  pub fn deserialize_array_small_ref<T>(&mut self) -> Result<ArrayRef<'de, T>>
+  where
+    T: DeserializeV5<'de>,
+  {
     let len = self.deserialize_u8()? as usize;
-    Ok(self.deserialize_bytes(len)?.into())
+    self.deserialize_array_ref(len)
+  }
+  pub fn deserialize_array_large_ref<T>(&mut self) -> Result<ArrayRef<'de, T>>
+  where
+    T: DeserializeV5<'de>,
+  {
+    let len = self.deserialize_u16()? as usize;
+    self.deserialize_array_ref(len)
+  }
+  fn deserialize_array_ref<T>(&mut self, len: usize) -> Result<ArrayRef<'de, T>>
+  where
+    T: DeserializeV5<'de>,
+  {
+    // The elements are decoded once here so that we know where the array ends
+    // and so that iterating over the array later on can't fail.
+    let start = self.data;
+    for _ in 0..len {
+      self.deserialize::<T>()?;
+    }
+
+    let consumed = start.len() - self.data.len();
+    Ok(ArrayRef::new(&start[..consumed], len, self.options))
+  }
+
+  pub fn deserialize_text_small(&mut self) -> Result<BString> {
+    Ok(self.deserialize_text_small_ref()?.into())
   }
   pub fn deserialize_text_large(&mut self) -> Result<BString> {
+    Ok(self.deserialize_text_large_ref()?.into())
+  }
+  pub fn deserialize_text_small_ref(&mut self) -> Result<&'de BStr> {
+    let len = self.deserialize_u8()? as usize;
+    Ok(self.deserialize_bytes(len)?.as_bstr())
+  }
+  pub fn deserialize_text_large_ref(&mut self) -> Result<&'de BStr> {
     let len = self.deserialize_u16()? as usize;
-    Ok(self.deserialize_bytes(len)?.into())
+    Ok(self.deserialize_bytes(len)?.as_bstr())
   }
 
   pub fn deserialize_accel(&mut self) -> Result<Vector2> {
@@ -391,8 +767,7 @@ impl<'de> AirmashDeserializerV5<'de> {
   }
 
   pub fn deserialize_coord24(&mut self) -> Result<f32> {
-    let spec = COORD24_SPEC;
-    Ok((((self.deserialize_u24()? as i32) - spec.shift) as f32) * (1.0 / spec.mult))
+    Ok(COORD24_SPEC.decode(self.deserialize_u24()? as i32))
   }
   pub fn deserialize_coordx(&mut self) -> Result<f32> {
     COORDX_SPEC.de(self)
@@ -422,7 +797,18 @@ impl<'de> AirmashDeserializerV5<'de> {
 }
 
 pub trait SerializeV5 {
-  fn serialize(&self, ser: &mut AirmashSerializerV5) -> Result;
+  fn serialize<S: Sink + ?Sized>(&self, ser: &mut AirmashSerializerV5<S>) -> Result;
+
+  /// The number of bytes that serializing this value produces.
+  ///
+  /// This can be used to size a buffer exactly before serializing into it.
+  /// Returns the same error as serializing would if the value can't be
+  /// serialized.
+  fn encoded_len(&self) -> Result<usize> {
+    let mut counter = LenCounter::default();
+    self.serialize(&mut AirmashSerializerV5::new(&mut counter))?;
+    Ok(counter.0)
+  }
 }
 
 pub trait DeserializeV5<'de>: Sized {
@@ -432,7 +818,7 @@ pub trait DeserializeV5<'de>: Sized {
 macro_rules! impl_builtin {
   ($ty:ty, $ser:ident, $de:ident) => {
     impl SerializeV5 for $ty {
-      fn serialize<'ser>(&self, ser: &mut AirmashSerializerV5<'ser>) -> Result {
+      fn serialize<S: Sink + ?Sized>(&self, ser: &mut AirmashSerializerV5<S>) -> Result {
         ser.$ser(*self)
       }
     }
@@ -456,7 +842,7 @@ macro_rules! impl_tuple {
     where $( $name: SerializeV5 ),*
     {
       #[allow(unused_variables, non_snake_case)]
-      fn serialize(&self, ser: &mut AirmashSerializerV5) -> Result {
+      fn serialize<S: Sink + ?Sized>(&self, ser: &mut AirmashSerializerV5<S>) -> Result {
 				let ($( $name, )*) = self;
 
         $( $name.serialize(ser)?; )*
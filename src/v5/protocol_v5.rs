@@ -0,0 +1,109 @@
+use crate::server::{EventRepel, PlayerHit};
+use crate::traits::{Protocol, ServerPacketIterator};
+use crate::v5::{Error, Result};
+use crate::{ClientPacket, ServerPacket};
+
+/// The maximum number of elements that can be stored within an array that is
+/// serialized with a `u8` length prefix.
+const SMALL_ARRAY_MAX: usize = u8::MAX as usize;
+
+/// [`Protocol`] implementation for the airmash v5 protocol.
+///
+/// This is a thin wrapper around [`serialize`](super::serialize) and
+/// [`deserialize`](super::deserialize) with some polyfills for packets that
+/// can't be represented directly in protocol-v5. Currently these are
+/// - [`EventRepel`] packets with more than 255 players or mobs, and
+/// - [`PlayerHit`] packets with more than 255 players.
+///
+/// These will be split up into multiple packets, each of which fits within
+/// the limits of the protocol.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ProtocolV5;
+
+impl ProtocolV5 {
+  pub const VERSION: u8 = 5;
+
+  pub fn new() -> Self {
+    Self
+  }
+}
+
+impl Protocol for ProtocolV5 {
+  type SerializeError = Error;
+  type DeserializeError = Error;
+
+  fn version(&self) -> u8 {
+    Self::VERSION
+  }
+
+  fn serialize_client(&self, packet: &ClientPacket) -> Result<ServerPacketIterator> {
+    let data = super::serialize(packet)?;
+    Ok(Box::new(std::iter::once(data)))
+  }
+
+  fn serialize_server(&self, packet: &ServerPacket) -> Result<ServerPacketIterator> {
+    let frames = match packet {
+      ServerPacket::EventRepel(repel) => split_event_repel(repel)
+        .map(|p| super::serialize(&ServerPacket::EventRepel(p)))
+        .collect::<Result<Vec<_>>>()?,
+      ServerPacket::PlayerHit(hit) => split_player_hit(hit)
+        .map(|p| super::serialize(&ServerPacket::PlayerHit(p)))
+        .collect::<Result<Vec<_>>>()?,
+      _ => vec![super::serialize(packet)?],
+    };
+
+    Ok(Box::new(frames.into_iter()))
+  }
+
+  fn deserialize_client(&self, data: &[u8]) -> Result<ClientPacket> {
+    super::deserialize(data)
+  }
+
+  fn deserialize_server(&self, data: &[u8]) -> Result<ServerPacket> {
+    super::deserialize(data)
+  }
+}
+
+/// Split an [`EventRepel`] into a sequence of packets that each have at most
+/// 255 players and 255 mobs.
+///
+/// This will always produce at least one packet.
+fn split_event_repel(repel: &EventRepel) -> impl Iterator<Item = EventRepel> + '_ {
+  let count = chunk_count(repel.players.len()).max(chunk_count(repel.mobs.len()));
+
+  (0..count).map(move |i| EventRepel {
+    clock: repel.clock,
+    id: repel.id,
+    pos: repel.pos,
+    rot: repel.rot,
+    speed: repel.speed,
+    energy: repel.energy,
+    energy_regen: repel.energy_regen,
+    players: chunk(&repel.players, i).to_vec(),
+    mobs: chunk(&repel.mobs, i).to_vec(),
+  })
+}
+
+/// Split a [`PlayerHit`] into a sequence of packets that each have at most 255
+/// players.
+///
+/// This will always produce at least one packet.
+fn split_player_hit(hit: &PlayerHit) -> impl Iterator<Item = PlayerHit> + '_ {
+  (0..chunk_count(hit.players.len())).map(move |i| PlayerHit {
+    id: hit.id,
+    ty: hit.ty,
+    pos: hit.pos,
+    owner: hit.owner,
+    players: chunk(&hit.players, i).to_vec(),
+  })
+}
+
+fn chunk_count(len: usize) -> usize {
+  len.div_ceil(SMALL_ARRAY_MAX).max(1)
+}
+
+fn chunk<T>(data: &[T], index: usize) -> &[T] {
+  let start = (index * SMALL_ARRAY_MAX).min(data.len());
+  let end = (start + SMALL_ARRAY_MAX).min(data.len());
+  &data[start..end]
+}
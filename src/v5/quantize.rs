@@ -0,0 +1,114 @@
+//! The values that clients see after a value has been sent over the wire.
+//!
+//! Most positions, rotations, and speeds are sent as fixed-point numbers
+//! with a limited range and resolution. A server that wants its simulation to
+//! match what clients decode can use these functions to round values the
+//! same way that encoding them would, without having to serialize anything.
+//! The functions are named after the [`AirmashSerializerV5`] methods used to
+//! encode the same kind of value.
+//!
+//! ```
+//! # use airmash_protocol::v5::quantize;
+//! assert_eq!(quantize::coordx(100.3), 100.0);
+//! assert_eq!(quantize::coordy(100.3), 100.25);
+//!
+//! // Values outside of the representable range are clamped.
+//! assert_eq!(quantize::coordx(20000.0), 16383.5);
+//! assert_eq!(quantize::rot(-1.0), 0.0);
+//! ```
+
+use super::protocol::*;
+use crate::server::{MobUpdate, PlayerUpdate};
+use crate::types::VectorExt;
+use crate::{Accel, Position, Velocity};
+
+const U16_MAX: i32 = u16::MAX as i32;
+
+/// Quantize an x coordinate sent with 2 units of precision per unit of
+/// distance (e.g. [`MobUpdate::pos`]).
+pub fn coordx(v: f32) -> f32 {
+  COORDX_SPEC.quantize(v, U16_MAX)
+}
+
+/// Quantize a y coordinate sent with 4 units of precision per unit of
+/// distance (e.g. [`MobUpdate::pos`]).
+pub fn coordy(v: f32) -> f32 {
+  COORDY_SPEC.quantize(v, U16_MAX)
+}
+
+/// Quantize a coordinate sent as a 24-bit value (e.g.
+/// [`PlayerUpdate::pos`]).
+pub fn coord24(v: f32) -> f32 {
+  COORD24_SPEC.quantize(v, (u32::MAX >> 8) as i32)
+}
+
+/// Quantize a single speed component (e.g. [`MobUpdate::max_speed`]).
+pub fn speed(v: f32) -> f32 {
+  SPEED_SPEC.quantize(v, U16_MAX)
+}
+
+/// Quantize an energy value. Unlike the other scalars this is sent as a
+/// single byte.
+pub fn energy(v: f32) -> f32 {
+  ENERGY_SPEC.quantize(v, u8::MAX as i32)
+}
+
+/// Quantize an energy or health regen rate.
+pub fn regen(v: f32) -> f32 {
+  REGEN_SPEC.quantize(v, U16_MAX)
+}
+
+/// Quantize a rotation (e.g. [`PlayerUpdate::rot`]).
+pub fn rot(v: f32) -> f32 {
+  ROTATION_SPEC.quantize(v, U16_MAX)
+}
+
+/// Quantize a position with 16-bit coordinates.
+pub fn pos(v: Position) -> Position {
+  Position::new(coordx(v.x), coordy(v.y))
+}
+
+/// Quantize a position with 24-bit coordinates.
+pub fn pos24(v: Position) -> Position {
+  Position::new(coord24(v.x), coord24(v.y))
+}
+
+/// Quantize a velocity (e.g. [`PlayerUpdate::speed`]).
+pub fn vel(v: Velocity) -> Velocity {
+  Velocity::new(speed(v.x), speed(v.y))
+}
+
+/// Quantize an acceleration (e.g. [`MobUpdate::accel`]).
+pub fn accel(v: Accel) -> Accel {
+  let accel = |v| ACCEL_SPEC.quantize(v, U16_MAX);
+  Accel::new(accel(v.x), accel(v.y))
+}
+
+impl PlayerUpdate {
+  /// The update as a client will decode it.
+  pub fn quantized(&self) -> Self {
+    let mut upgrades = self.upgrades;
+    upgrades.speed &= 7;
+
+    Self {
+      upgrades,
+      pos: pos24(self.pos),
+      rot: rot(self.rot),
+      speed: vel(self.speed),
+      ..*self
+    }
+  }
+}
+
+impl MobUpdate {
+  /// The update as a client will decode it.
+  pub fn quantized(&self) -> Self {
+    Self {
+      pos: pos(self.pos),
+      speed: vel(self.speed),
+      accel: accel(self.accel),
+      max_speed: speed(self.max_speed),
+      ..*self
+    }
+  }
+}
@@ -1,5 +1,7 @@
 use crate::server::*;
-use crate::v5::{AirmashDeserializerV5, AirmashSerializerV5, DeserializeV5, Result, SerializeV5};
+use crate::v5::{
+  AirmashDeserializerV5, AirmashSerializerV5, DeserializeV5, Result, SerializeV5, Sink,
+};
 use crate::ServerPacket;
 
 decl_serde! {
@@ -59,10 +61,29 @@ decl_serde! {
   }
 }
 
-decl_serde! {
-  struct CommandReply {
-    ty,
-    text => { serialize_text_large, deserialize_text_large }
+impl SerializeV5 for CommandReply {
+  fn serialize<S: Sink + ?Sized>(&self, ser: &mut AirmashSerializerV5<S>) -> Result {
+    use crate::v5::{Error, ErrorExt, ErrorKind};
+
+    // The original client fails to show popups that aren't valid JSON without
+    // any indication of what went wrong so we reject them here instead.
+    if self.ty == crate::CommandReplyType::ShowInPopup && !crate::util::is_valid_json(&self.text) {
+      return Err(Error::new(ErrorKind::InvalidPopup).with_context("text"));
+    }
+
+    ser.serialize(&self.ty).with_context("ty")?;
+    ser
+      .serialize_text_large(self.text.as_ref())
+      .with_context("text")
+  }
+}
+
+impl<'de> DeserializeV5<'de> for CommandReply {
+  fn deserialize(de: &mut AirmashDeserializerV5<'de>) -> Result<Self> {
+    Ok(Self {
+      ty: de.field("ty", |de| de.deserialize())?,
+      text: de.field("text", |de| de.deserialize_text_large())?,
+    })
   }
 }
 
@@ -561,6 +582,43 @@ decl_consts! {
   const ServerCustom = 91;
 }
 
+// Packet ids used by ab-server for its sync protocol. The source for these is
+// cited in the `ab_server` tests in v5/tests.rs.
+#[cfg(feature = "ab-server")]
+decl_consts! {
+  const SyncAuth = 240;
+  const SyncInit = 241;
+  const SyncSubscribe = 242;
+  const SyncUpdate = 243;
+}
+
+#[cfg(feature = "ab-server")]
+decl_serde! {
+  struct SyncAuth {
+    challenge => { serialize_text_large, deserialize_text_large },
+  }
+
+  struct SyncInit {
+    sequence,
+    timestamp,
+  }
+
+  struct SyncSubscribe {
+    active,
+    ty => { serialize_text_small, deserialize_text_small },
+    id => { serialize_text_small, deserialize_text_small },
+  }
+
+  struct SyncUpdate {
+    sequence,
+    ty => { serialize_text_small, deserialize_text_small },
+    id => { serialize_text_small, deserialize_text_small },
+    data => { serialize_text_large, deserialize_text_large },
+    timestamp,
+    event => { serialize_text_large, deserialize_text_large },
+  }
+}
+
 packet_serialize! {
   enum ServerPacket {
     Login(x),
@@ -610,7 +668,21 @@ packet_serialize! {
     ChatVoteMutePassed(x),
     ChatVoteMuted,
     ServerMessage(x),
-    ServerCustom(x)
+    ServerCustom(x),
+    #[cfg(feature = "ab-server")]
+    SyncAuth(x),
+    #[cfg(feature = "ab-server")]
+    SyncInit(x),
+    #[cfg(feature = "ab-server")]
+    SyncSubscribe(x),
+    #[cfg(feature = "ab-server")]
+    SyncUpdate(x),
+  }
+
+  match ser {
+    ServerPacket::Unknown { id, body } => ser
+      .serialize_u8(*id)
+      .and_then(|_| ser.serialize_bytes(body))
   }
 }
 
@@ -663,40 +735,65 @@ packet_deserialize! {
     ChatVoteMutePassed(x),
     ChatVoteMuted,
     ServerMessage(x),
-    ServerCustom(x)
+    ServerCustom(x),
+    #[cfg(feature = "ab-server")]
+    SyncAuth(x),
+    #[cfg(feature = "ab-server")]
+    SyncInit(x),
+    #[cfg(feature = "ab-server")]
+    SyncSubscribe(x),
+    #[cfg(feature = "ab-server")]
+    SyncUpdate(x),
   }
 
   match de {
     Login::V5_PACKET_NO => {
-      let login = de.deserialize().with_context("Login")?;
+      let login = de.field("Login", |de| de.deserialize())?;
 
       if de.remainder().is_empty() {
         return Ok(ServerPacket::Login(login));
       }
 
-      Ok(ServerPacket::Login2(Login2 {
-        login,
-        config: de.deserialize_text_large()
-          .with_context("config")
-          .with_context("Login2")?,
-        bots: de.deserialize_array_large()
-          .with_context("bots")
-          .with_context("Login2")?,
-      }))
+      // Newer servers may send a Login packet with extra data that isn't a
+      // valid Login2. If we're allowed to ignore trailing data then we fall
+      // back to just the Login part.
+      let checkpoint = de.checkpoint();
+      let login2 = de.field("Login2", |de| Ok((
+        de.field("config", |de| de.deserialize_text_large())?,
+        de.field("bots", |de| de.deserialize_array_large())?,
+      )));
+
+      match login2 {
+        Ok((config, bots)) => Ok(ServerPacket::Login2(Login2 { login, config, bots })),
+        Err(_) if de.options().allows_trailing() => {
+          de.restore(checkpoint);
+          Ok(ServerPacket::Login(login))
+        }
+        Err(e) => Err(e),
+      }
     },
     MobUpdate::V5_PACKET_NO => {
-      let update = de.deserialize().with_context("MobUpdate")?;
+      let update = de.field("MobUpdate", |de| de.deserialize())?;
 
       if de.remainder().is_empty() {
         return Ok(ServerPacket::MobUpdate(update));
       }
 
-      Ok(ServerPacket::MobUpdate2(MobUpdate2 {
-        update,
-        owner: de.deserialize_u16()
-          .with_context("owner")
-          .with_context("MobUpdate2")?,
-      }))
+      let checkpoint = de.checkpoint();
+      let owner = de.field("MobUpdate2", |de| de.field("owner", |de| de.deserialize_u16()));
+
+      match owner {
+        Ok(owner) => Ok(ServerPacket::MobUpdate2(MobUpdate2 { update, owner })),
+        Err(_) if de.options().allows_trailing() => {
+          de.restore(checkpoint);
+          Ok(ServerPacket::MobUpdate(update))
+        }
+        Err(e) => Err(e),
+      }
+    },
+    id if de.options().keeps_unknown_packets() => {
+      let body = de.unnamed("<body>", |de| de.deserialize_bytes(de.remainder().len()))?;
+      Ok(ServerPacket::Unknown { id, body: body.to_vec() })
     }
   }
 }
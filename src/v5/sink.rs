@@ -0,0 +1,137 @@
+use std::io;
+
+use super::{Error, ErrorKind, Result};
+
+/// A destination for serialized bytes.
+///
+/// This is implemented for `Vec<u8>`, for `bytes::BytesMut` when the
+/// `tokio-codec` feature is enabled, and by [`SliceSink`] and [`IoSink`] for
+/// fixed-size buffers and writers respectively.
+pub trait Sink {
+  fn write_bytes(&mut self, bytes: &[u8]) -> Result;
+}
+
+impl Sink for Vec<u8> {
+  fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+    self.extend_from_slice(bytes);
+    Ok(())
+  }
+}
+
+#[cfg(feature = "bytes")]
+impl Sink for bytes::BytesMut {
+  fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+    self.extend_from_slice(bytes);
+    Ok(())
+  }
+}
+
+/// A sink that writes into a fixed-size buffer.
+///
+/// Writing more bytes than fit within the buffer fails with a
+/// [`BufferFull`](ErrorKind::BufferFull) error.
+///
+/// ```
+/// # use airmash_protocol::v5::{self, SliceSink};
+/// # use airmash_protocol::{ServerPacket, server::PlayerLeave};
+/// let mut buf = [0u8; 16];
+/// let mut sink = SliceSink::new(&mut buf);
+/// v5::serialize_into(&ServerPacket::from(PlayerLeave { id: 5 }), &mut sink).unwrap();
+///
+/// assert_eq!(sink.written(), [11, 5, 0]);
+/// ```
+#[derive(Debug)]
+pub struct SliceSink<'a> {
+  buf: &'a mut [u8],
+  len: usize,
+}
+
+impl<'a> SliceSink<'a> {
+  pub fn new(buf: &'a mut [u8]) -> Self {
+    Self { buf, len: 0 }
+  }
+
+  /// The number of bytes that have been written.
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// The bytes that have been written.
+  pub fn written(&self) -> &[u8] {
+    &self.buf[..self.len]
+  }
+}
+
+impl Sink for SliceSink<'_> {
+  fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+    let available = self.buf.len() - self.len;
+    if bytes.len() > available {
+      return Err(Error::buffer_full(bytes.len(), available));
+    }
+
+    self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+    self.len += bytes.len();
+    Ok(())
+  }
+}
+
+/// A sink that writes to an [`io::Write`].
+///
+/// Since [`Error`] can't carry an [`io::Error`] a failed write returns an
+/// [`Io`](ErrorKind::Io) error and the underlying error can be retrieved with
+/// [`take_error`](IoSink::take_error).
+#[derive(Debug)]
+pub struct IoSink<W> {
+  writer: W,
+  error: Option<io::Error>,
+}
+
+impl<W: io::Write> IoSink<W> {
+  pub fn new(writer: W) -> Self {
+    Self {
+      writer,
+      error: None,
+    }
+  }
+
+  /// Take the error from the last write that failed.
+  pub fn take_error(&mut self) -> Option<io::Error> {
+    self.error.take()
+  }
+
+  pub fn get_ref(&self) -> &W {
+    &self.writer
+  }
+
+  pub fn get_mut(&mut self) -> &mut W {
+    &mut self.writer
+  }
+
+  pub fn into_inner(self) -> W {
+    self.writer
+  }
+}
+
+impl<W: io::Write> Sink for IoSink<W> {
+  fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+    self.writer.write_all(bytes).map_err(|e| {
+      self.error = Some(e);
+      Error::new(ErrorKind::Io)
+    })
+  }
+}
+
+/// A sink that only counts the bytes written to it.
+#[derive(Default)]
+pub(crate) struct LenCounter(pub usize);
+
+impl Sink for LenCounter {
+  fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+    self.0 += bytes.len();
+    Ok(())
+  }
+}
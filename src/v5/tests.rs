@@ -1,10 +1,12 @@
 use approx::*;
 
 use super::serialize;
-use crate::server::PlayerUpdate;
+use crate::server::{Login, MobUpdate, Ping, PlayerUpdate};
 use crate::types::VectorExt;
-use crate::v5::{AirmashDeserializerV5, AirmashSerializerV5};
-use crate::{GameType, ServerKeyState, ServerPacket, Upgrades, Vector2};
+use crate::v5::{
+  AirmashDeserializerV5, AirmashSerializerV5, DeserializeOptions, ErrorKind, ServerPacketRef,
+};
+use crate::{GameType, MobType, ServerKeyState, ServerPacket, Upgrades, Vector2};
 
 #[test]
 fn reference_deserialize_player_update() {
@@ -93,6 +95,20 @@ fn uint24_serde() {
   assert_eq!(deserialize([2, 3, 1]), 0x030201);
 }
 
+#[test]
+fn f64_serde() {
+  let value = 1600000000123.25f64;
+
+  let mut data = Vec::new();
+  AirmashSerializerV5::new(&mut data)
+    .serialize_f64(value)
+    .unwrap();
+  assert_eq!(data, value.to_le_bytes());
+
+  let decoded = AirmashDeserializerV5::new(&data).deserialize_f64().unwrap();
+  assert_eq!(decoded.to_bits(), value.to_bits());
+}
+
 #[test]
 fn login2_extra_invalid() {
   #[rustfmt::skip]
@@ -166,3 +182,924 @@ fn login2_valid() {
   assert_eq!(packet.bots.len(), 1);
   assert_eq!(packet.bots[0].id, 0x101);
 }
+
+#[test]
+fn protocol_v5_splits_large_repel() {
+  use crate::server::{EventRepel, EventRepelMob, EventRepelPlayer};
+  use crate::v5::ProtocolV5;
+  use crate::{MobType, Protocol};
+
+  let player = EventRepelPlayer {
+    id: 1,
+    keystate: ServerKeyState::default(),
+    pos: Vector2::new(0.0, 0.0),
+    rot: 0.0,
+    speed: Vector2::new(0.0, 0.0),
+    energy: 1.0,
+    energy_regen: 0.0,
+    health: 1.0,
+    health_regen: 0.0,
+  };
+  let mob = EventRepelMob {
+    id: 2,
+    ty: MobType::PredatorMissile,
+    pos: Vector2::new(0.0, 0.0),
+    speed: Vector2::new(0.0, 0.0),
+    accel: Vector2::new(0.0, 0.0),
+    max_speed: 0.0,
+  };
+
+  let packet = ServerPacket::EventRepel(EventRepel {
+    clock: 100,
+    id: 3,
+    pos: Vector2::new(0.0, 0.0),
+    rot: 0.0,
+    speed: Vector2::new(0.0, 0.0),
+    energy: 1.0,
+    energy_regen: 0.0,
+    players: vec![player; 300],
+    mobs: vec![mob; 600],
+  });
+
+  // Make sure that the packet actually can't be serialized directly.
+  assert!(serialize(&packet).is_err());
+
+  let protocol: Box<dyn Protocol<SerializeError = _, DeserializeError = _>> = Box::new(ProtocolV5);
+  let frames: Vec<_> = protocol.serialize_server(&packet).unwrap().collect();
+  assert_eq!(frames.len(), 3);
+
+  let counts: Vec<_> = frames
+    .iter()
+    .map(|frame| match protocol.deserialize_server(frame).unwrap() {
+      ServerPacket::EventRepel(repel) => {
+        assert_eq!(repel.clock, 100);
+        assert_eq!(repel.id, 3);
+        (repel.players.len(), repel.mobs.len())
+      }
+      _ => panic!("Wrong packet type"),
+    })
+    .collect();
+
+  assert_eq!(counts, [(255, 255), (45, 255), (0, 90)]);
+}
+
+#[test]
+fn protocol_v5_splits_large_player_hit() {
+  use crate::server::{PlayerHit, PlayerHitPlayer};
+  use crate::v5::ProtocolV5;
+  use crate::{MobType, Protocol};
+
+  let hit = |count| {
+    ServerPacket::PlayerHit(PlayerHit {
+      id: 5,
+      ty: MobType::GoliathMissile,
+      pos: Vector2::new(0.0, 0.0),
+      owner: 6,
+      players: vec![
+        PlayerHitPlayer {
+          id: 7,
+          health: 0.5,
+          health_regen: 0.0,
+        };
+        count
+      ],
+    })
+  };
+
+  let lens = |packet| -> Vec<usize> {
+    ProtocolV5
+      .serialize_server(&packet)
+      .unwrap()
+      .map(|frame| match crate::v5::deserialize(&frame).unwrap() {
+        ServerPacket::PlayerHit(hit) => hit.players.len(),
+        _ => panic!("Wrong packet type"),
+      })
+      .collect()
+  };
+
+  assert_eq!(lens(hit(0)), [0]);
+  assert_eq!(lens(hit(255)), [255]);
+  assert_eq!(lens(hit(256)), [255, 1]);
+}
+
+#[test]
+fn borrowed_login2_matches_owned() {
+  use crate::v5::ServerPacketRef;
+
+  #[rustfmt::skip]
+  let bytes: Vec<u8> = vec![
+    /* packet  */ crate::server::Login::V5_PACKET_NO,
+    /* success */ 1,
+    /* id      */ 1, 0,
+    /* team    */ 1, 0,
+    /* clock   */ 0, 0, 0, 0,
+    /* token   */ 0,
+    /* ty      */ 1,
+    /* room    */ 4, b't', b'e', b's', b't',
+    /* players */ 1, 0,
+      /* id       */ 2, 0,
+      /* status   */ 0,
+      /* level    */ 3,
+      /* name     */ 3, b'b', b'o', b'b',
+      /* ty       */ 1,
+      /* team     */ 2, 0,
+      /* pos      */ 0, 128, 0, 128,
+      /* rot      */ 0, 0,
+      /* flag     */ 10, 0,
+      /* upgrades */ 0,
+    /* config  */ 2, 0, b'{', b'}',
+    /* bots    */ 1, 0,
+      /* id    */ 2, 0
+  ];
+
+  let view: ServerPacketRef = crate::v5::deserialize(&bytes).unwrap();
+  let owned: ServerPacket = crate::v5::deserialize(&bytes).unwrap();
+
+  let login2 = match view {
+    ServerPacketRef::Login2(login2) => login2,
+    _ => panic!("Wrong packet type"),
+  };
+
+  assert_eq!(login2.config, "{}");
+  assert_eq!(login2.login.room, "test");
+  assert_eq!(login2.login.players.len(), 1);
+  let names: Vec<_> = login2.login.players.iter().map(|p| p.name).collect();
+  assert_eq!(names, ["bob"]);
+
+  assert_eq!(format!("{:?}", view.to_owned()), format!("{:?}", owned));
+}
+
+#[test]
+fn borrowed_arrays_match_owned() {
+  use crate::server::{
+    PlayerReteam, PlayerReteamPlayer, ScoreBoard, ScoreBoardData, ScoreBoardRanking,
+  };
+  use crate::v5::ServerPacketRef;
+
+  let packets = vec![
+    ServerPacket::ScoreBoard(ScoreBoard {
+      data: vec![
+        ScoreBoardData {
+          id: 1,
+          score: 100,
+          level: 2,
+        },
+        ScoreBoardData {
+          id: 2,
+          score: 50,
+          level: 0,
+        },
+      ],
+      rankings: vec![
+        ScoreBoardRanking { id: 1, pos: None },
+        ScoreBoardRanking {
+          id: 2,
+          pos: Some(Vector2::new(256.0, -512.0)),
+        },
+      ],
+    }),
+    ServerPacket::PlayerReteam(PlayerReteam {
+      players: vec![
+        PlayerReteamPlayer { id: 1, team: 1 },
+        PlayerReteamPlayer { id: 2, team: 2 },
+        PlayerReteamPlayer { id: 3, team: 1 },
+      ],
+    }),
+  ];
+
+  for packet in packets {
+    let bytes = serialize(&packet).unwrap();
+    let view: ServerPacketRef = crate::v5::deserialize(&bytes).unwrap();
+
+    match view {
+      ServerPacketRef::ScoreBoard(board) => {
+        assert_eq!(board.data.len(), 2);
+        assert_eq!(board.rankings.iter().filter(|r| r.pos.is_some()).count(), 1);
+      }
+      ServerPacketRef::PlayerReteam(reteam) => {
+        let ids: Vec<_> = reteam.players.iter().map(|p| p.id).collect();
+        assert_eq!(ids, [1, 2, 3]);
+      }
+      _ => panic!("Wrong packet type"),
+    }
+
+    assert_eq!(format!("{:?}", view.to_owned()), format!("{:?}", packet));
+  }
+}
+
+#[test]
+fn borrowed_arrays_keep_options() {
+  use crate::server::LoginPlayer;
+  use crate::{FlagCode, PlaneType, PlayerStatus};
+
+  let packet = ServerPacket::Login(Login {
+    success: true,
+    id: 1,
+    team: 1,
+    clock: 0,
+    token: "".into(),
+    ty: GameType::FFA,
+    room: "test".into(),
+    players: vec![LoginPlayer {
+      id: 2,
+      status: PlayerStatus::Alive,
+      level: 0,
+      name: "bob".into(),
+      ty: PlaneType::Unknown(9),
+      team: 2,
+      pos: Vector2::new(0.0, 0.0),
+      rot: 0.0,
+      flag: FlagCode::UnitedNations,
+      upgrades: Upgrades::default(),
+    }],
+  });
+  let bytes = serialize(&packet).unwrap();
+
+  let options = DeserializeOptions::strict().reject_unknown_enums(true);
+  assert!(options.deserialize::<ServerPacketRef>(&bytes).is_err());
+
+  let login = match crate::v5::deserialize(&bytes).unwrap() {
+    ServerPacketRef::Login(login) => login,
+    packet => panic!("unexpected packet {:?}", packet),
+  };
+  let players: Vec<_> = login.players.iter().map(|p| p.ty).collect();
+  assert_eq!(players, [PlaneType::Unknown(9)]);
+}
+
+#[test]
+fn borrowed_truncated_array() {
+  use crate::v5::ServerPacketRef;
+
+  #[rustfmt::skip]
+  let bytes: Vec<u8> = vec![
+    /* packet  */ crate::server::PlayerReteam::V5_PACKET_NO,
+    /* players */ 2, 0,
+      /* id    */ 1, 0,
+      /* team  */ 1, 0,
+      /* id    */ 2, 0,
+  ];
+
+  let err = crate::v5::deserialize::<ServerPacketRef>(&bytes).unwrap_err();
+  assert_eq!(
+    err.context(),
+    ["team", "players", "PlayerReteam", "ServerPacketRef"]
+  );
+}
+
+#[test]
+fn trace_records_field_spans() {
+  use crate::server::{PlayerReteam, PlayerReteamPlayer};
+
+  let packet = ServerPacket::PlayerReteam(PlayerReteam {
+    players: vec![
+      PlayerReteamPlayer { id: 1, team: 2 },
+      PlayerReteamPlayer { id: 3, team: 4 },
+    ],
+  });
+  let data = serialize(&packet).unwrap();
+
+  let mut de = AirmashDeserializerV5::with_trace(&data);
+  de.deserialize::<ServerPacket>().unwrap();
+  let spans: Vec<_> = de
+    .take_trace()
+    .into_iter()
+    .map(|span| (span.path, span.depth, span.range))
+    .collect();
+
+  assert_eq!(
+    spans,
+    [
+      ("<packet id>".to_owned(), 0, 0..1),
+      ("PlayerReteam".to_owned(), 0, 1..11),
+      ("PlayerReteam.players".to_owned(), 1, 1..11),
+      ("PlayerReteam.players[0]".to_owned(), 2, 3..7),
+      ("PlayerReteam.players[0].id".to_owned(), 3, 3..5),
+      ("PlayerReteam.players[0].team".to_owned(), 3, 5..7),
+      ("PlayerReteam.players[1]".to_owned(), 2, 7..11),
+      ("PlayerReteam.players[1].id".to_owned(), 3, 7..9),
+      ("PlayerReteam.players[1].team".to_owned(), 3, 9..11),
+    ]
+  );
+}
+
+#[test]
+fn trace_stops_at_error() {
+  // PlayerReteam with 2 players but only enough data for one and a half.
+  let data = [22, 2, 0, 1, 0, 2, 0, 3, 0];
+
+  let mut de = AirmashDeserializerV5::with_trace(&data);
+  let err = de.deserialize::<ServerPacket>().unwrap_err();
+  assert_eq!(
+    err.context(),
+    ["team", "players", "PlayerReteam", "ServerPacket"]
+  );
+  assert_eq!(de.offset(), 9);
+
+  let last = de.take_trace().pop().unwrap();
+  assert_eq!(last.path, "PlayerReteam.players[1].id");
+  assert_eq!(last.range, 7..9);
+}
+
+#[test]
+fn error_reports_truncation_details() {
+  use crate::client::Login;
+  use crate::ClientPacket;
+
+  let data = serialize(&ClientPacket::Login(Login {
+    protocol: 5,
+    name: "name".into(),
+    session: "none".into(),
+    horizon_x: 1920,
+    horizon_y: 1080,
+    flag: "UN".into(),
+  }))
+  .unwrap();
+
+  // Cut off in the middle of horizon_y
+  let err = super::deserialize::<ClientPacket>(&data[..15]).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::EndOfBuffer);
+  assert_eq!(err.context(), ["horizon_y", "Login", "ClientPacket"]);
+  assert_eq!(err.offset(), Some(14));
+  assert_eq!(err.packet_id(), Some(0));
+  assert_eq!(err.expected_len(), Some(2));
+  assert_eq!(err.available_len(), Some(1));
+  assert_eq!(err.invalid_enum_value(), None);
+}
+
+#[test]
+fn error_reports_invalid_value() {
+  let err = super::deserialize::<ServerPacket>(&[200, 1, 2]).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::InvalidEnumValue);
+  assert_eq!(err.offset(), Some(0));
+  assert_eq!(err.packet_id(), Some(200));
+  assert_eq!(err.invalid_enum_value(), Some(200));
+  assert_eq!(err.expected_len(), None);
+
+  let display = err.to_string();
+  assert!(display.starts_with("failed to parse: "), "{}", display);
+  assert!(display.contains("invalid enum value (200)"), "{}", display);
+  assert!(display.contains("At byte offset: 0"), "{}", display);
+}
+
+#[test]
+fn error_reports_trailing_data() {
+  let err = super::deserialize::<ServerPacket>(&[7, 0]).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::UnexpectedDataRemaining);
+  assert_eq!(err.offset(), Some(1));
+}
+
+#[test]
+fn options_unknown_packets_as_raw() {
+  let options = DeserializeOptions::strict().unknown_packets_as_raw(true);
+  let data = [200, 1, 2, 3];
+
+  let packet: ServerPacket = options.deserialize(&data).unwrap();
+  match &packet {
+    ServerPacket::Unknown { id, body } => {
+      assert_eq!(*id, 200);
+      assert_eq!(body, &[1, 2, 3]);
+    }
+    packet => panic!("unexpected packet {:?}", packet),
+  }
+  assert_eq!(serialize(&packet).unwrap(), data);
+
+  let packet: ServerPacketRef = options.deserialize(&data).unwrap();
+  assert!(matches!(
+    packet,
+    ServerPacketRef::Unknown {
+      id: 200,
+      body: [1, 2, 3]
+    }
+  ));
+
+  // Known packets are still parsed normally
+  assert!(matches!(
+    options.deserialize::<ServerPacket>(&[7]).unwrap(),
+    ServerPacket::Ack
+  ));
+}
+
+#[test]
+fn options_allow_trailing() {
+  let data = serialize(&ServerPacket::Ping(Ping { clock: 5, num: 6 })).unwrap();
+  let mut extended = data.clone();
+  extended.extend_from_slice(&[0xAA, 0xBB]);
+
+  assert!(super::deserialize::<ServerPacket>(&extended).is_err());
+
+  let options = DeserializeOptions::strict().allow_trailing(true);
+  match options.deserialize(&extended).unwrap() {
+    ServerPacket::Ping(ping) => assert_eq!((ping.clock, ping.num), (5, 6)),
+    packet => panic!("unexpected packet {:?}", packet),
+  }
+}
+
+#[test]
+fn options_extended_packets_parse_known_prefix() {
+  let update = MobUpdate {
+    clock: 1,
+    id: 2,
+    ty: MobType::PredatorMissile,
+    pos: Vector2::new(10.0, 20.0),
+    speed: Vector2::new(1.0, 2.0),
+    accel: Vector2::new(0.5, 0.5),
+    max_speed: 3.0,
+  };
+  let mut data = serialize(&ServerPacket::MobUpdate(update)).unwrap();
+  // Too short to be a MobUpdate2
+  data.push(0xFF);
+
+  assert!(super::deserialize::<ServerPacket>(&data).is_err());
+
+  let options = DeserializeOptions::lenient();
+  assert!(matches!(
+    options.deserialize::<ServerPacket>(&data).unwrap(),
+    ServerPacket::MobUpdate(MobUpdate { id: 2, .. })
+  ));
+  assert!(matches!(
+    options.deserialize::<ServerPacketRef>(&data).unwrap(),
+    ServerPacketRef::MobUpdate(MobUpdate { id: 2, .. })
+  ));
+
+  let login = Login {
+    success: true,
+    id: 4,
+    team: 4,
+    clock: 100,
+    token: "token".into(),
+    ty: GameType::FFA,
+    room: "ffa1".into(),
+    players: vec![],
+  };
+  let mut data = serialize(&ServerPacket::Login(login)).unwrap();
+  // Not a valid Login2 config string
+  data.extend_from_slice(&[0xFF, 0xFF, 0x01]);
+
+  assert!(super::deserialize::<ServerPacket>(&data).is_err());
+  match options.deserialize::<ServerPacket>(&data).unwrap() {
+    ServerPacket::Login(login) => assert_eq!(login.room, "ffa1"),
+    packet => panic!("unexpected packet {:?}", packet),
+  }
+  assert!(matches!(
+    options.deserialize::<ServerPacketRef>(&data).unwrap(),
+    ServerPacketRef::Login(_)
+  ));
+}
+
+/// Packets and constants that only exist in ab-server.
+///
+/// All of the values here come from ab-protocol
+/// (<https://github.com/wight-airmash/ab-protocol>), the protocol definitions
+/// shared by ab-server and its sync service. `ids_match_ab_protocol` pins
+/// every one of them so that any change has to be checked against it.
+#[cfg(feature = "ab-server")]
+mod ab_server {
+  use crate::v5::{deserialize, serialize, ServerPacketRef};
+  use crate::{client, server, ClientPacket, ErrorType, ServerCustomType, ServerPacket};
+
+  #[test]
+  fn ids_match_ab_protocol() {
+    assert_eq!(client::SyncAuth::V5_PACKET_NO, 231);
+    assert_eq!(client::SyncInit::V5_PACKET_NO, 232);
+    assert_eq!(client::SyncUpdate::V5_PACKET_NO, 233);
+    assert_eq!(client::SyncAck::V5_PACKET_NO, 234);
+
+    assert_eq!(server::SyncAuth::V5_PACKET_NO, 240);
+    assert_eq!(server::SyncInit::V5_PACKET_NO, 241);
+    assert_eq!(server::SyncSubscribe::V5_PACKET_NO, 242);
+    assert_eq!(server::SyncUpdate::V5_PACKET_NO, 243);
+
+    assert_eq!(u8::from(ErrorType::RespawnThrottled), 32);
+    assert_eq!(u8::from(ErrorType::SpectateThrottled), 33);
+    assert_eq!(u8::from(ErrorType::SyncAuthFailed), 200);
+
+    assert_eq!(u8::from(ServerCustomType::FFA), 3);
+  }
+
+  #[test]
+  fn sync_update_roundtrip() {
+    let update = server::SyncUpdate {
+      sequence: 12,
+      ty: "user".into(),
+      id: "abc".into(),
+      data: r#"{"earnings":10}"#.into(),
+      timestamp: 1600000000123.0,
+      event: r#"["login"]"#.into(),
+    };
+    let bytes = serialize(&ServerPacket::SyncUpdate(update)).unwrap();
+    assert_eq!(bytes[0], 243);
+
+    match deserialize(&bytes).unwrap() {
+      ServerPacket::SyncUpdate(p) => {
+        assert_eq!(p.sequence, 12);
+        assert_eq!(p.id, "abc");
+        assert_eq!(p.timestamp, 1600000000123.0);
+        assert_eq!(p.event, r#"["login"]"#);
+      }
+      packet => panic!("unexpected packet {:?}", packet),
+    }
+
+    match deserialize(&bytes).unwrap() {
+      ServerPacketRef::SyncUpdate(p) => assert_eq!(p.data, r#"{"earnings":10}"#),
+      packet => panic!("unexpected packet {:?}", packet),
+    }
+  }
+
+  #[test]
+  fn sync_client_packets_roundtrip() {
+    let packets = vec![
+      ClientPacket::SyncAuth(client::SyncAuth {
+        response: "secret".into(),
+      }),
+      ClientPacket::SyncInit(client::SyncInit {
+        sequence: 1,
+        timestamp: 5.5,
+      }),
+      ClientPacket::SyncAck(client::SyncAck {
+        sequence: 7,
+        result: 0,
+      }),
+    ];
+
+    for packet in packets {
+      let bytes = serialize(&packet).unwrap();
+      let decoded: ClientPacket = deserialize(&bytes).unwrap();
+      assert_eq!(serialize(&decoded).unwrap(), bytes, "{:?}", packet);
+    }
+  }
+
+  #[test]
+  fn extra_error_codes() {
+    let bytes = serialize(&ServerPacket::Error(server::Error {
+      error: ErrorType::RespawnThrottled,
+    }))
+    .unwrap();
+    assert_eq!(bytes, [8, 32]);
+  }
+}
+
+#[test]
+fn invalid_popup_is_rejected() {
+  use crate::server::CommandReply;
+  use crate::CommandReplyType;
+
+  let reply = |ty, text: &str| {
+    ServerPacket::CommandReply(CommandReply {
+      ty,
+      text: text.into(),
+    })
+  };
+
+  let err = serialize(&reply(CommandReplyType::ShowInPopup, "Unknown command")).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::InvalidPopup);
+  assert_eq!(err.context(), ["text", "ServerPacket", "CommandReply"]);
+
+  for text in ["", "{}}", "[1, 2,]", r#"{'a': 1}"#, "\"bad \\x escape\""] {
+    assert!(
+      serialize(&reply(CommandReplyType::ShowInPopup, text)).is_err(),
+      "{}",
+      text
+    );
+  }
+
+  assert!(serialize(&reply(CommandReplyType::ShowInPopup, r#"{"a":1}"#)).is_ok());
+  assert!(serialize(&reply(CommandReplyType::ShowInConsole, "Unknown command")).is_ok());
+}
+
+#[test]
+fn checked_serializer_records_precision_loss() {
+  use crate::server::{PlayerHit, PlayerHitPlayer};
+  use crate::v5::PrecisionCheck;
+
+  let update = ServerPacket::PlayerUpdate(PlayerUpdate {
+    clock: 0,
+    id: 1,
+    keystate: ServerKeyState::default(),
+    upgrades: Upgrades {
+      speed: 9,
+      ..Default::default()
+    },
+    pos: Vector2::new(-20000.0, 100.0),
+    rot: -0.5,
+    speed: Vector2::new(0.0, 0.0),
+  });
+
+  let mut data = vec![];
+  let mut ser = AirmashSerializerV5::checked(&mut data, PrecisionCheck::Record);
+  ser.serialize(&update).unwrap();
+  let losses = ser.take_precision_loss();
+
+  // The output is the same as with an unchecked serializer.
+  assert_eq!(data, serialize(&update).unwrap());
+
+  let summary: Vec<_> = losses
+    .iter()
+    .map(|loss| (&loss.path[..], loss.original, loss.encoded))
+    .collect();
+  assert_eq!(
+    summary,
+    [
+      ("PlayerUpdate.upgrades.speed", 9.0, 1.0),
+      ("PlayerUpdate.pos.x", -20000.0, -16384.0),
+      ("PlayerUpdate.rot", -0.5, 0.0),
+    ]
+  );
+
+  let hit = ServerPacket::PlayerHit(PlayerHit {
+    id: 1,
+    ty: MobType::PredatorMissile,
+    pos: Vector2::new(0.0, 0.0),
+    owner: 2,
+    players: vec![
+      PlayerHitPlayer {
+        id: 3,
+        health: 0.5,
+        health_regen: 0.0,
+      },
+      PlayerHitPlayer {
+        id: 4,
+        health: 1.5,
+        health_regen: 0.0,
+      },
+    ],
+  });
+
+  let mut data = vec![];
+  let mut ser = AirmashSerializerV5::checked(&mut data, PrecisionCheck::Record);
+  ser.serialize(&hit).unwrap();
+  let losses = ser.take_precision_loss();
+
+  assert_eq!(losses.len(), 1);
+  assert_eq!(losses[0].path, "PlayerHit.players[1].health");
+  assert_eq!(losses[0].original, 1.5);
+  assert_eq!(losses[0].encoded, 1.0);
+}
+
+#[test]
+fn checked_serializer_rejects_out_of_range() {
+  use crate::v5::serialize_checked;
+
+  let mut update = PlayerUpdate {
+    clock: 0,
+    id: 1,
+    keystate: ServerKeyState::default(),
+    upgrades: Upgrades::default(),
+    pos: Vector2::new(16000.0, -8000.0),
+    rot: 1.0,
+    speed: Vector2::new(0.0, 0.0),
+  };
+
+  assert!(serialize_checked(&ServerPacket::PlayerUpdate(update)).is_ok());
+
+  update.pos.y = f32::NAN;
+  let err = serialize_checked(&ServerPacket::PlayerUpdate(update)).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::ValueOutOfRange);
+  assert_eq!(err.context(), ["y", "pos", "ServerPacket", "PlayerUpdate"]);
+  assert!(err.original_value().unwrap().is_nan());
+  assert_eq!(err.encoded_value(), Some(0.0));
+
+  update.pos.y = 0.0;
+  update.rot = 10.0;
+  let err = serialize_checked(&ServerPacket::PlayerUpdate(update)).unwrap_err();
+  assert_eq!(err.context(), ["rot", "ServerPacket", "PlayerUpdate"]);
+}
+
+#[test]
+fn quantize_matches_roundtrip() {
+  use crate::v5::quantize;
+
+  let values = [
+    -20000.0, -16384.0, -8000.3, -1.7, -0.01, 0.0, 0.0001, 0.3, 1.0, 2.71, 9.99, 10.0, 123.456,
+    8191.9, 16383.7, 20000.0,
+  ];
+
+  macro_rules! check {
+    ($quantize:ident => $ser:ident, $de:ident) => {
+      for &value in values.iter() {
+        let mut data = vec![];
+        AirmashSerializerV5::new(&mut data).$ser(value).unwrap();
+        let decoded = AirmashDeserializerV5::new(&data).$de().unwrap();
+
+        assert_eq!(
+          quantize::$quantize(value),
+          decoded,
+          "{}({})",
+          stringify!($quantize),
+          value
+        );
+      }
+    };
+  }
+
+  check!(coordx => serialize_coordx, deserialize_coordx);
+  check!(coordy => serialize_coordy, deserialize_coordy);
+  check!(coord24 => serialize_coord24, deserialize_coord24);
+  check!(speed => serialize_speed, deserialize_speed);
+  check!(energy => serialize_energy, deserialize_energy);
+  check!(regen => serialize_regen, deserialize_regen);
+  check!(rot => serialize_rot, deserialize_rot);
+}
+
+#[test]
+fn quantized_packets_match_roundtrip() {
+  let update = PlayerUpdate {
+    clock: 1234,
+    id: 5,
+    keystate: ServerKeyState::default(),
+    upgrades: Upgrades {
+      speed: 11,
+      shield: true,
+      inferno: false,
+    },
+    pos: Vector2::new(1503.123, -20000.0),
+    rot: 7.777,
+    speed: Vector2::new(0.123456, -3.3996582),
+  };
+
+  let data = serialize(&ServerPacket::PlayerUpdate(update)).unwrap();
+  let decoded = match crate::v5::deserialize(&data).unwrap() {
+    ServerPacket::PlayerUpdate(p) => p,
+    _ => unreachable!(),
+  };
+  let quantized = update.quantized();
+
+  assert_eq!(quantized.pos, decoded.pos);
+  assert_eq!(quantized.rot, decoded.rot);
+  assert_eq!(quantized.speed, decoded.speed);
+  assert_eq!(quantized.upgrades, decoded.upgrades);
+  assert_eq!(quantized.clock, decoded.clock);
+
+  let update = MobUpdate {
+    clock: 99,
+    id: 7,
+    ty: MobType::PredatorMissile,
+    pos: Vector2::new(-700.77, 321.123),
+    speed: Vector2::new(5.5555, -9.0),
+    accel: Vector2::new(0.1234, -0.0001),
+    max_speed: 9.123,
+  };
+
+  let data = serialize(&ServerPacket::MobUpdate(update)).unwrap();
+  let decoded = match crate::v5::deserialize(&data).unwrap() {
+    ServerPacket::MobUpdate(p) => p,
+    _ => unreachable!(),
+  };
+  let quantized = update.quantized();
+
+  assert_eq!(quantized.pos, decoded.pos);
+  assert_eq!(quantized.speed, decoded.speed);
+  assert_eq!(quantized.accel, decoded.accel);
+  assert_eq!(quantized.max_speed, decoded.max_speed);
+}
+
+#[test]
+fn encoded_len_matches_serialize() {
+  use crate::server::{PlayerHit, PlayerHitPlayer, PlayerLeave};
+  use crate::v5::SerializeV5;
+  use crate::MobType;
+
+  let packets = [
+    ServerPacket::PlayerLeave(PlayerLeave { id: 5 }),
+    ServerPacket::Ping(Ping { clock: 1, num: 2 }),
+    ServerPacket::Login(Login {
+      success: true,
+      id: 1,
+      team: 2,
+      clock: 3,
+      token: "token".into(),
+      ty: GameType::FFA,
+      room: "ffa1".into(),
+      players: vec![],
+    }),
+  ];
+
+  for packet in packets.iter() {
+    assert_eq!(
+      packet.encoded_len().unwrap(),
+      serialize(packet).unwrap().len()
+    );
+  }
+
+  // Values that can't be serialized report the same error as serializing.
+  let hit = ServerPacket::PlayerHit(PlayerHit {
+    id: 5,
+    ty: MobType::GoliathMissile,
+    pos: Vector2::new(0.0, 0.0),
+    owner: 6,
+    players: vec![
+      PlayerHitPlayer {
+        id: 7,
+        health: 0.5,
+        health_regen: 0.0,
+      };
+      256
+    ],
+  });
+  let err = hit.encoded_len().unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::ArraySizeTooLarge);
+  assert_eq!(err.context(), serialize(&hit).unwrap_err().context());
+}
+
+#[test]
+fn slice_sink_reports_overflow() {
+  use crate::v5::{serialize_into, SliceSink};
+
+  let packet = ServerPacket::Ping(Ping { clock: 1, num: 2 });
+  let len = serialize(&packet).unwrap().len();
+
+  let mut buf = [0u8; 64];
+  let mut sink = SliceSink::new(&mut buf[..len]);
+  serialize_into(&packet, &mut sink).unwrap();
+  assert_eq!(sink.written(), &serialize(&packet).unwrap()[..]);
+
+  let mut sink = SliceSink::new(&mut buf[..len - 1]);
+  let err = serialize_into(&packet, &mut sink).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::BufferFull);
+  assert_eq!(err.expected_len(), Some(4));
+  assert_eq!(err.available_len(), Some(3));
+
+  let display = err.to_string();
+  assert!(display.starts_with("failed to serialize: "), "{}", display);
+}
+
+#[test]
+fn io_sink_keeps_write_error() {
+  use std::io;
+
+  use crate::v5::{serialize_into, IoSink};
+
+  struct Broken;
+
+  impl io::Write for Broken {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+      Err(io::Error::other("broken"))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  let packet = ServerPacket::Ping(Ping { clock: 1, num: 2 });
+
+  let mut sink = IoSink::new(io::Cursor::new(Vec::new()));
+  serialize_into(&packet, &mut sink).unwrap();
+  assert_eq!(sink.into_inner().into_inner(), serialize(&packet).unwrap());
+
+  let mut sink = IoSink::new(Broken);
+  let err = serialize_into(&packet, &mut sink).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::Io);
+  assert_eq!(sink.take_error().unwrap().to_string(), "broken");
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn serialize_into_bytes_mut() {
+  use bytes::BytesMut;
+
+  use crate::v5::serialize_into;
+
+  let packet = ServerPacket::Ping(Ping { clock: 1, num: 2 });
+
+  let mut buf = BytesMut::new();
+  serialize_into(&packet, &mut buf).unwrap();
+  serialize_into(&packet, &mut buf).unwrap();
+
+  let expected = serialize(&packet).unwrap();
+  assert_eq!(&buf[..expected.len()], &expected[..]);
+  assert_eq!(&buf[expected.len()..], &expected[..]);
+}
+
+#[test]
+fn shared_frame_encodes_once() {
+  use crate::server::{ChatPublic, ScoreUpdate};
+  use crate::v5::{SerializeV5, SharedFrame};
+  use crate::Delivery;
+
+  let packet = ServerPacket::ChatPublic(ChatPublic {
+    id: 3,
+    text: "hello".into(),
+  });
+  let frame = SharedFrame::encode(&packet).unwrap();
+  let clone = frame.clone();
+
+  assert!(frame.ptr_eq(&clone));
+  assert!(frame.is_broadcast());
+  assert_eq!(frame.as_bytes(), &serialize(&packet).unwrap()[..]);
+
+  // Serializing a frame writes out the stored bytes unchanged.
+  assert_eq!(serialize(&clone).unwrap(), frame.as_bytes());
+  assert_eq!(clone.encoded_len().unwrap(), frame.len());
+
+  let score = ServerPacket::ScoreUpdate(ScoreUpdate {
+    id: 1,
+    score: 2,
+    earnings: 3,
+    upgrades: 4,
+    total_kills: 5,
+    total_deaths: 6,
+  });
+  let frame = SharedFrame::encode(&score).unwrap();
+  assert_eq!(frame.delivery(), Delivery::PerRecipient);
+  assert!(!frame.ptr_eq(&SharedFrame::encode(&score).unwrap()));
+  assert_eq!(frame, SharedFrame::encode(&score).unwrap());
+}
@@ -2,7 +2,7 @@ use crate::types::*;
 use crate::v5::*;
 
 impl SerializeV5 for ServerKeyState {
-  fn serialize(&self, ser: &mut AirmashSerializerV5) -> Result {
+  fn serialize<S: Sink + ?Sized>(&self, ser: &mut AirmashSerializerV5<S>) -> Result {
     let val = (self.up as u8)
       | (self.down as u8) << 1
       | (self.left as u8) << 2
@@ -33,7 +33,13 @@ impl<'de> DeserializeV5<'de> for ServerKeyState {
 }
 
 impl SerializeV5 for Upgrades {
-  fn serialize(&self, ser: &mut AirmashSerializerV5) -> Result {
+  fn serialize<S: Sink + ?Sized>(&self, ser: &mut AirmashSerializerV5<S>) -> Result {
+    if self.speed > 7 {
+      ser.field("speed", |ser| {
+        ser.precision_loss(self.speed.into(), (self.speed & 7).into())
+      })?;
+    }
+
     let val: u8 =
       (self.speed & 7) | ((self.shield as u8 & 1) << 3) | ((self.inferno as u8 & 1) << 4);
     val.serialize(ser)
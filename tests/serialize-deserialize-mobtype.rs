@@ -3,6 +3,7 @@
 use airmash_protocol::MobType;
 use serde::{Deserialize, Serialize};
 
+#[allow(dead_code)]
 #[derive(Serialize, Deserialize)]
 struct TestStruct {
   mob: MobType,